@@ -0,0 +1,124 @@
+// benches/request_pipeline.rs
+
+//! Request-side cost of the proxy: parsing a body once versus once per stage,
+//! and buffering versus streaming a large embedding request end to end.
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    http::Request,
+    Router,
+};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    create_router,
+    handlers::parsed::ParsedRequest,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn chat_body(messages: usize) -> Bytes {
+    let messages: Vec<Value> = (0..messages)
+        .map(|i| json!({"role": "user", "content": format!("Message {i}: {}", "lorem ipsum ".repeat(40))}))
+        .collect();
+    Bytes::from(
+        json!({"model": "gemini-2.5-flash", "stream": true, "messages": messages}).to_string(),
+    )
+}
+
+fn embed_body(size: usize) -> Bytes {
+    let text = "x".repeat(size);
+    Bytes::from(json!({"content": {"parts": [{"text": text}]}}).to_string())
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let body = chat_body(200);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    group.bench_function("parse_once", |b| {
+        b.iter(|| {
+            let request = ParsedRequest::new("/v1/chat/completions", body.clone());
+            black_box((request.model().is_some(), request.is_streaming()))
+        })
+    });
+    // What the handler did before: model, stream flag and body each parsed separately.
+    group.bench_function("parse_per_stage", |b| {
+        b.iter(|| {
+            let model = serde_json::from_slice::<Value>(&body).unwrap()["model"].clone();
+            let stream = serde_json::from_slice::<Value>(&body).unwrap()["stream"].as_bool();
+            let json = serde_json::from_slice::<Value>(&body).unwrap();
+            black_box((model, stream, json))
+        })
+    });
+    group.finish();
+}
+
+fn app(runtime: &Runtime, server: &MockServer, temp_dir: &TempDir, stream: bool) -> Router {
+    let config = AppConfig {
+        server: ServerConfig {
+            stream_request_bodies: stream,
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["bench-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let (state, _rx) = runtime
+        .block_on(AppState::new(&config, &temp_dir.path().join("config.yaml")))
+        .expect("AppState failed");
+    create_router(Arc::new(state))
+}
+
+fn bench_embed_request(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(async {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"embedding": {"values": [0.1]}})),
+            )
+            .mount(&server)
+            .await;
+        server
+    });
+    let temp_dir = tempfile::tempdir().unwrap();
+    let body = embed_body(4 * 1024 * 1024);
+
+    let mut group = c.benchmark_group("embed_request_4mb");
+    group.sample_size(20);
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    for (name, stream) in [("buffered", false), ("streamed", true)] {
+        let app = app(&runtime, &server, &temp_dir, stream);
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    Request::post("/v1beta/models/text-embedding-004:embedContent")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.clone()))
+                        .unwrap()
+                },
+                |request| {
+                    runtime.block_on(async {
+                        let response = app.clone().oneshot(request).await.unwrap();
+                        assert!(response.status().is_success());
+                        to_bytes(response.into_body(), usize::MAX).await.unwrap()
+                    })
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_embed_request);
+criterion_main!(benches);
@@ -1,10 +1,12 @@
 // src/admin.rs
 use crate::{
+    circuit_breaker::{BreakerId, CircuitBreakerSnapshot, CircuitState, ForcedState},
     config::{self, AppConfig},
     error::{AppError, Result},
     key_manager::{FlattenedKeyInfo, KeyManagerTrait},
     state::AppState,
     storage::key_state::KeyState,
+    usage::{self, UsageQuery, UsageReport},
 };
 use axum::{
     body::Body,
@@ -22,7 +24,7 @@ use md5;
 use rand::{thread_rng, Rng};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use sysinfo::{CpuRefreshKind, Disks, System};
 use tokio::sync::Mutex;
@@ -147,12 +149,22 @@ pub fn admin_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
         .route("/keys/:key_id/verify", post(verify_key))
         .route("/keys/:key_id/reset", post(reset_key))
         .route("/config", put(update_config))
+        .route("/circuit-breakers/force", post(force_circuit_breaker))
         .route_layer(middleware::from_fn(csrf_middleware))
         .route_layer(middleware::from_fn_with_state(
             state.clone(),
             crate::middleware::admin_auth_middleware,
         ));
 
+    // Read-only routes that require admin authentication. Reads need no CSRF
+    // token.
+    let authed_read_routes = Router::new()
+        .route("/circuit-breakers", get(list_circuit_breakers))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::middleware::admin_auth_middleware,
+        ));
+
     // Combine all admin routes under a common `/admin` prefix.
     Router::new().nest(
         "/admin",
@@ -164,9 +176,12 @@ pub fn admin_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
             .route("/config", get(get_config))
             .route("/metrics", get(get_metrics_summary))
             .route("/model-stats", get(get_model_stats))
+            .route("/usage", get(get_usage))
+            .route("/usage/daily", get(get_daily_cost_report))
             .route("/csrf-token", get(get_csrf_token))
             .route("/login", post(login))
             .merge(authed_routes)
+            .merge(authed_read_routes)
             .layer(CookieManagerLayer::new())
             .layer(middleware::from_fn_with_state(state, rate_limit_middleware)), // Add rate limiting to all admin routes
     )
@@ -300,6 +315,68 @@ impl KeyInfo {
     }
 }
 
+/// A key's circuit breaker, or its breaker for one model.
+#[derive(Debug, Serialize)]
+pub struct CircuitBreakerInfo {
+    pub name: String,
+    pub key_id: String,
+    pub key_preview: String,
+    pub group_name: String,
+    pub target_url: String,
+    pub model: Option<String>,
+    pub state: CircuitState,
+    /// Whether the state is held by `POST /admin/circuit-breakers/force`.
+    pub forced: bool,
+    pub failure_count: usize,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    /// When an open breaker next lets a request probe the key. `None` while
+    /// the state is forced, since no probe happens until it is released.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+impl CircuitBreakerInfo {
+    fn new(
+        key_info: &FlattenedKeyInfo,
+        model: Option<String>,
+        name: String,
+        snapshot: CircuitBreakerSnapshot,
+    ) -> Self {
+        let next_attempt_at = snapshot
+            .next_attempt
+            .filter(|_| !snapshot.forced)
+            .map(|at| {
+                let remaining = at.saturating_duration_since(std::time::Instant::now());
+                Utc::now() + chrono::Duration::from_std(remaining).unwrap_or_default()
+            });
+        Self {
+            name,
+            key_id: format!("{:x}", md5::compute(key_info.key.expose_secret())),
+            key_preview: KeyInfo::create_key_preview(key_info.key.expose_secret()),
+            group_name: key_info.group_name.clone(),
+            target_url: key_info.target_url.clone(),
+            model,
+            state: snapshot.state,
+            forced: snapshot.forced,
+            failure_count: snapshot.failure_count,
+            total_requests: snapshot.total_requests,
+            total_failures: snapshot.total_failures,
+            next_attempt_at,
+        }
+    }
+}
+
+/// Forces the breakers of one key, or of every key with a target URL.
+#[derive(Debug, Deserialize)]
+pub struct ForceCircuitBreakerRequest {
+    /// Key id as listed by `GET /admin/keys`.
+    pub key_id: Option<String>,
+    pub target_url: Option<String>,
+    /// Forces the keys' breakers for this model instead of their own.
+    pub model: Option<String>,
+    pub state: ForcedState,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ListKeysQuery {
     pub group: Option<String>,
@@ -612,6 +689,142 @@ pub async fn get_model_stats(
     }))
 }
 
+/// Lists the circuit breaker of every key and those of keys and models that
+/// have seen traffic.
+#[axum::debug_handler]
+pub async fn list_circuit_breakers(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CircuitBreakerInfo>>> {
+    let all_key_info = state.key_manager.read().await.get_all_key_info().await;
+    // Breakers are created on first use; keys that have not been used are
+    // listed as closed without creating theirs.
+    let mut ids: BTreeSet<BreakerId> = state
+        .circuit_breakers
+        .breakers()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    ids.extend(all_key_info.keys().map(|api_key| (api_key.clone(), None)));
+
+    let mut breakers = Vec::new();
+    for (api_key, model) in ids {
+        if let Some(key_info) = all_key_info.get(&api_key) {
+            let (name, snapshot) = state
+                .circuit_breakers
+                .peek(&api_key, model.as_deref())
+                .await;
+            breakers.push(CircuitBreakerInfo::new(key_info, model, name, snapshot));
+        }
+    }
+    Ok(Json(breakers))
+}
+
+/// Forces circuit breakers open or closed, or back to automatic, for a key or
+/// for every key of a target, and returns the breakers changed.
+#[axum::debug_handler]
+pub async fn force_circuit_breaker(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ForceCircuitBreakerRequest>,
+) -> Result<Json<Vec<CircuitBreakerInfo>>> {
+    let all_key_info = state.key_manager.read().await.get_all_key_info().await;
+    let (field, keys): (&str, Vec<&FlattenedKeyInfo>) = match (&request.key_id, &request.target_url)
+    {
+        (Some(key_id), None) => (
+            "key_id",
+            all_key_info
+                .values()
+                .filter(|info| format!("{:x}", md5::compute(info.key.expose_secret())) == *key_id)
+                .collect(),
+        ),
+        (None, Some(target_url)) => (
+            "target_url",
+            all_key_info
+                .values()
+                .filter(|info| info.target_url == *target_url)
+                .collect(),
+        ),
+        _ => {
+            return Err(AppError::validation(
+                "key_id",
+                "Exactly one of key_id and target_url is required",
+            ))
+        }
+    };
+    if keys.is_empty() {
+        return Err(AppError::validation(field, "No configured key matches"));
+    }
+
+    let mut changed = Vec::with_capacity(keys.len());
+    for key_info in keys {
+        let breaker = state
+            .circuit_breakers
+            .breaker(key_info.key.expose_secret(), request.model.as_deref());
+        breaker.force(request.state).await;
+        changed.push(CircuitBreakerInfo::new(
+            key_info,
+            request.model.clone(),
+            breaker.name().to_string(),
+            breaker.snapshot().await,
+        ));
+    }
+    info!(
+        state = ?request.state,
+        breakers = changed.len(),
+        "Circuit breakers forced by admin"
+    );
+    Ok(Json(changed))
+}
+
+/// Reports token usage recorded from upstream responses, filtered by time
+/// range and grouped by key, group, model and/or client.
+#[axum::debug_handler]
+pub async fn get_usage(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageReport>> {
+    Ok(Json(state.usage.report(&query)?))
+}
+
+/// Output format of `GET /admin/usage/daily`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ReportFormatQuery {
+    /// `json` (default) or `csv`.
+    pub format: Option<String>,
+}
+
+/// Reports usage and cost per UTC day, grouped like `GET /admin/usage`, as
+/// JSON or CSV.
+#[axum::debug_handler]
+pub async fn get_daily_cost_report(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UsageQuery>,
+    Query(format): Query<ReportFormatQuery>,
+) -> Result<Response> {
+    let currency = state
+        .config
+        .read()
+        .await
+        .pricing
+        .clone()
+        .unwrap_or_default()
+        .currency;
+    let report = state.usage.daily_report(&query, &currency)?;
+    match format.format.as_deref().unwrap_or("json") {
+        "json" => Ok(Json(report).into_response()),
+        "csv" => {
+            let dimensions = usage::group_by_dimensions(query.group_by.as_deref())?;
+            Ok((
+                [(http::header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+                report.to_csv(&dimensions),
+            )
+                .into_response())
+        }
+        other => Err(AppError::InvalidRequest {
+            message: format!("Unknown report format '{other}'; use json or csv"),
+        }),
+    }
+}
+
 /// Handles admin login by setting a secure, HttpOnly cookie with the admin token.
 #[axum::debug_handler]
 pub async fn login(
@@ -760,6 +973,10 @@ pub async fn reload_state_from_config(
         .reload(&new_config, state.redis_pool.clone())
         .await?;
 
+    state
+        .response_cache
+        .configure(&new_config, state.redis_pool.as_ref());
+
     *config_guard = new_config;
     *http_clients_guard = new_http_clients;
 
@@ -825,9 +1042,9 @@ async fn calculate_key_status_summary(
 fn get_key_status_str(key_state: Option<&KeyState>) -> (&'static str, Option<DateTime<Utc>>) {
     match key_state {
         Some(state) => {
-            if state.is_blocked {
+            if !state.is_available() {
                 // This status aligns with the `temporarily_unavailable_keys` field in `KeyStatus`.
-                ("unavailable", state.last_failure)
+                ("unavailable", state.blocked_until.or(state.last_failure))
             } else {
                 ("available", None)
             }
@@ -859,6 +1076,7 @@ mod tests {
             is_blocked: false,
             consecutive_failures: 0,
             last_failure: None,
+            blocked_until: None,
         };
         assert_eq!(
             get_key_status_str(Some(&state_available)),
@@ -871,6 +1089,7 @@ mod tests {
             is_blocked: true,
             consecutive_failures: 3,
             last_failure: Some(now),
+            blocked_until: None,
         };
         assert_eq!(
             get_key_status_str(Some(&state_unavailable)),
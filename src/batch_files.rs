@@ -0,0 +1,126 @@
+// src/batch_files.rs
+
+//! Local helpers for Gemini batch job files.
+//!
+//! A batch input file is JSONL with one `{"key": ..., "request": ...}` object
+//! per line. `split_jsonl` spreads a large input over several files, one per
+//! key, so each part can be submitted as its own batch job; `merge_jsonl`
+//! joins the result files again, optionally in the order of the input.
+//!
+//! These helpers only rewrite files and never contact the API. Spreading the
+//! work over keys comes from submitting each part as its own batch job through
+//! the proxy, which rotates keys and pins every job to the key that created it.
+
+use crate::error::{AppError, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Reads the JSON lines of a batch file. Lines without a `key` get their
+/// zero-based line index as key, so results can be matched back to inputs.
+fn read_lines<R: BufRead>(reader: R) -> Result<Vec<(String, Value)>> {
+    let mut lines = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| AppError::Io {
+            operation: "read batch file".to_string(),
+            message: e.to_string(),
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: Value = serde_json::from_str(&line).map_err(|e| AppError::Validation {
+            field: format!("line {}", index + 1),
+            message: format!("invalid JSON: {e}"),
+        })?;
+        let key = match value.get("key") {
+            Some(Value::String(key)) => key.clone(),
+            Some(key) => key.to_string(),
+            None => {
+                let key = index.to_string();
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("key".to_string(), Value::String(key.clone()));
+                }
+                key
+            }
+        };
+        lines.push((key, value));
+    }
+    Ok(lines)
+}
+
+/// Splits a batch input file into at most `parts` contiguous, evenly sized
+/// chunks of JSON lines. Empty chunks are omitted.
+pub fn split_jsonl<R: BufRead>(input: R, parts: usize) -> Result<Vec<Vec<String>>> {
+    if parts == 0 {
+        return Err(AppError::Validation {
+            field: "parts".to_string(),
+            message: "must be at least 1".to_string(),
+        });
+    }
+    let lines = read_lines(input)?;
+    let chunk_size = (lines.len() + parts - 1) / parts;
+    Ok(lines
+        .chunks(chunk_size.max(1))
+        .map(|chunk| chunk.iter().map(|(_, value)| value.to_string()).collect())
+        .collect())
+}
+
+/// Returns the request keys of a batch input file in input order, using the
+/// same line-index fallback as `split_jsonl`.
+pub fn input_keys<R: BufRead>(input: R) -> Result<Vec<String>> {
+    Ok(read_lines(input)?.into_iter().map(|(key, _)| key).collect())
+}
+
+/// Concatenates result files. With `order`, results are sorted by the position
+/// of their key in it; results with unknown keys keep their place at the end.
+pub fn merge_jsonl<R: BufRead>(results: Vec<R>, order: Option<&[String]>) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for reader in results {
+        lines.extend(read_lines(reader)?);
+    }
+    if let Some(order) = order {
+        let position: HashMap<&str, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.as_str(), i))
+            .collect();
+        lines.sort_by_key(|(key, _)| position.get(key.as_str()).copied().unwrap_or(usize::MAX));
+    }
+    Ok(lines
+        .into_iter()
+        .map(|(_, value)| value.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_assigns_missing_keys_and_balances_parts() {
+        let input = "{\"key\":\"a\",\"request\":{}}\n{\"request\":{}}\n\n{\"request\":{}}\n";
+        let parts = split_jsonl(Cursor::new(input), 2).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].len(), 2);
+        assert_eq!(parts[1], vec![r#"{"key":"3","request":{}}"#]);
+        assert!(split_jsonl(Cursor::new("{}"), 0).is_err());
+    }
+
+    #[test]
+    fn test_merge_restores_input_order() {
+        let order = input_keys(Cursor::new(
+            "{\"key\":\"x\"}\n{\"key\":\"y\"}\n{\"key\":\"z\"}\n",
+        ))
+        .unwrap();
+        let first = Cursor::new("{\"key\":\"z\",\"response\":3}\n");
+        let second =
+            Cursor::new("{\"key\":\"y\",\"response\":2}\n{\"key\":\"x\",\"response\":1}\n");
+        let merged = merge_jsonl(vec![first, second], Some(&order)).unwrap();
+        let keys: Vec<Value> = merged
+            .iter()
+            .map(|l| serde_json::from_str::<Value>(l).unwrap()["response"].clone())
+            .collect();
+        assert_eq!(keys, vec![1, 2, 3]);
+    }
+}
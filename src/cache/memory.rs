@@ -0,0 +1,94 @@
+// src/cache/memory.rs
+
+use super::{CacheStore, CachedResponse};
+use crate::error::Result;
+use async_trait::async_trait;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// In-process cache store bounded by entry count with LRU eviction.
+pub struct MemoryCacheStore {
+    entries: Mutex<LruCache<String, (Instant, CachedResponse)>>,
+}
+
+impl MemoryCacheStore {
+    pub fn new(max_entries: usize) -> Self {
+        let capacity = NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCacheStore {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let mut entries = self.entries.lock();
+        match entries.get(key) {
+            Some((expires_at, response)) if *expires_at > Instant::now() => {
+                Ok(Some(response.clone()))
+            }
+            Some(_) => {
+                entries.pop(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, key: &str, response: &CachedResponse, ttl: Duration) -> Result<()> {
+        self.entries
+            .lock()
+            .put(key.to_string(), (Instant::now() + ttl, response.clone()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+
+    fn response(body: &'static str) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            headers: vec![],
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_evicts_least_recently_used() {
+        let store = MemoryCacheStore::new(2);
+        let ttl = Duration::from_secs(60);
+        store.put("a", &response("a"), ttl).await.unwrap();
+        store.put("b", &response("b"), ttl).await.unwrap();
+        assert!(store.get("a").await.unwrap().is_some());
+        store.put("c", &response("c"), ttl).await.unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert!(store.get("b").await.unwrap().is_none());
+        assert_eq!(store.get("a").await.unwrap(), Some(response("a")));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_expires_entries() {
+        let store = MemoryCacheStore::new(4);
+        store
+            .put("a", &response("a"), Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,411 @@
+// src/cache/mod.rs
+
+//! Opt-in cache for successful non-streaming responses.
+//!
+//! Requests are keyed by a hash of the method, path, query (without the API
+//! key), model and canonicalised JSON body, so byte-for-byte repeats of
+//! embedding jobs and `temperature: 0` evals are served without touching a key.
+
+pub mod memory;
+pub mod redis;
+
+pub use memory::MemoryCacheStore;
+pub use redis::RedisCacheStore;
+
+use crate::config::{AppConfig, CacheBackend, ResponseCacheConfig};
+use crate::error::Result;
+use crate::group_selection;
+use crate::handlers::RequestContext;
+use crate::hedging;
+use crate::retry_budget::ATTEMPTS_HEADER;
+use async_trait::async_trait;
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use deadpool_redis::Pool;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Response header reporting whether the response came from the cache.
+pub const CACHE_STATUS_HEADER: &str = "x-proxy-cache";
+
+/// A stored upstream response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "base64_body")]
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    /// Captures a buffered response. Framing headers are dropped since they
+    /// are recomputed when the response is replayed.
+    pub fn new(status: StatusCode, headers: &HeaderMap, body: Bytes) -> Self {
+        let headers = headers
+            .iter()
+            .filter(|(name, _)| {
+                *name != header::CONTENT_LENGTH
+                    && *name != header::TRANSFER_ENCODING
+                    && *name != header::DATE
+                    && name.as_str() != CACHE_STATUS_HEADER
+            })
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+        Self {
+            status: status.as_u16(),
+            headers,
+            body,
+        }
+    }
+
+    /// Rebuilds the HTTP response.
+    pub fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let headers = response.headers_mut();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        response
+    }
+}
+
+mod base64_body {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        body: &Bytes,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD
+            .decode(encoded)
+            .map(Bytes::from)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Backend storage for cached responses.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    /// Returns the unexpired entry for `key`, if any.
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>>;
+
+    /// Stores `response` under `key` for `ttl`.
+    async fn put(&self, key: &str, response: &CachedResponse, ttl: Duration) -> Result<()>;
+}
+
+/// How a single request interacts with the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheLookup {
+    pub key: String,
+    /// False when the client sent `Cache-Control: no-cache`; the fresh
+    /// response is still stored.
+    pub read: bool,
+}
+
+struct ActiveCache {
+    config: ResponseCacheConfig,
+    store: Arc<dyn CacheStore>,
+}
+
+/// The response cache shared by all handlers. Disabled unless
+/// `response_cache` is configured; reconfigured on hot reload.
+#[derive(Default)]
+pub struct ResponseCache {
+    active: RwLock<Option<Arc<ActiveCache>>>,
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: &AppConfig, redis_pool: Option<&Pool>) -> Self {
+        let cache = Self::default();
+        cache.configure(config, redis_pool);
+        cache
+    }
+
+    /// Applies the `response_cache` section of `config`. The in-memory store
+    /// is kept across reloads unless its capacity changed.
+    pub fn configure(&self, config: &AppConfig, redis_pool: Option<&Pool>) {
+        let Some(cache_config) = config.response_cache.clone() else {
+            *self.active.write() = None;
+            return;
+        };
+
+        let current = self.active.read().clone();
+        if let Some(current) = current.filter(|c| {
+            c.config.backend == cache_config.backend
+                && c.config.max_entries == cache_config.max_entries
+        }) {
+            *self.active.write() = Some(Arc::new(ActiveCache {
+                config: cache_config,
+                store: current.store.clone(),
+            }));
+            return;
+        }
+
+        let store: Arc<dyn CacheStore> = match cache_config.backend {
+            CacheBackend::Memory => Arc::new(MemoryCacheStore::new(cache_config.max_entries)),
+            CacheBackend::Redis => match redis_pool {
+                Some(pool) => Arc::new(RedisCacheStore::new(
+                    pool.clone(),
+                    config.redis_key_prefix.as_deref(),
+                )),
+                None => {
+                    warn!("Response cache uses the redis backend but no Redis pool is available; cache disabled.");
+                    *self.active.write() = None;
+                    return;
+                }
+            },
+        };
+        info!(backend = ?cache_config.backend, ttl_secs = cache_config.ttl_secs, "Response cache enabled");
+        *self.active.write() = Some(Arc::new(ActiveCache {
+            config: cache_config,
+            store,
+        }));
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.active.read().is_some()
+    }
+
+    /// Decides whether the request may be served from or stored in the cache.
+    pub fn lookup_for(
+        &self,
+        ctx: &RequestContext<'_>,
+        model: &Option<String>,
+        is_streaming: bool,
+    ) -> Option<CacheLookup> {
+        let active = self.active.read().clone()?;
+        let (read, write) = cache_control(ctx.headers);
+        let path = ctx.uri.path();
+        // Only calls that create nothing upstream are safe to replay.
+        if !write || is_streaming || !hedging::is_hedgeable(ctx.method, path) {
+            return None;
+        }
+        let body = ctx.json?;
+        if active.config.deterministic_only && !is_deterministic(path, body) {
+            return None;
+        }
+        Some(CacheLookup {
+            key: request_key(ctx, model, body),
+            read,
+        })
+    }
+
+    pub async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let active = self.active.read().clone()?;
+        match active.store.get(key).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!(error = %e, "Response cache lookup failed");
+                None
+            }
+        }
+    }
+
+    /// Stores `response`. Headers describing the request that fetched it,
+    /// such as `X-Proxy-Attempts`, are not stored with it.
+    pub async fn put(&self, key: &str, response: &CachedResponse) {
+        let Some(active) = self.active.read().clone() else {
+            return;
+        };
+        let mut response = response.clone();
+        response
+            .headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(ATTEMPTS_HEADER));
+        let ttl = Duration::from_secs(active.config.ttl_secs);
+        if let Err(e) = active.store.put(key, &response, ttl).await {
+            warn!(error = %e, "Failed to store response in cache");
+        }
+    }
+}
+
+/// Sets the `X-Proxy-Cache` header to `HIT` or `MISS`.
+pub fn set_cache_status(headers: &mut HeaderMap, hit: bool) {
+    headers.insert(
+        CACHE_STATUS_HEADER,
+        HeaderValue::from_static(if hit { "HIT" } else { "MISS" }),
+    );
+}
+
+/// Returns `(read, write)` permissions from the request's `Cache-Control`.
+fn cache_control(headers: &HeaderMap) -> (bool, bool) {
+    let mut read = true;
+    let mut write = true;
+    for value in headers.get_all(header::CACHE_CONTROL) {
+        let Ok(value) = value.to_str() else { continue };
+        for directive in value.split(',').map(|d| d.trim().to_ascii_lowercase()) {
+            match directive.as_str() {
+                "no-cache" => read = false,
+                "no-store" => {
+                    read = false;
+                    write = false;
+                }
+                _ => {}
+            }
+        }
+    }
+    (read, write)
+}
+
+/// Embeddings and zero-temperature generations are the calls whose repeats
+/// are expected to produce the same answer.
+fn is_deterministic(path: &str, body: &Value) -> bool {
+    if path.contains(":embedContent")
+        || path.contains(":batchEmbedContents")
+        || path.ends_with("/embeddings")
+    {
+        return true;
+    }
+    let temperature = body
+        .get("temperature")
+        .or_else(|| body.pointer("/generationConfig/temperature"))
+        .or_else(|| body.pointer("/generation_config/temperature"))
+        .and_then(Value::as_f64);
+    temperature == Some(0.0)
+}
+
+/// Hashes everything that affects the upstream answer. The body is
+/// re-serialised so key order and whitespace don't matter.
+pub(crate) fn request_key(
+    ctx: &RequestContext<'_>,
+    model: &Option<String>,
+    body: &Value,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(ctx.method.as_str());
+    hasher.update([0]);
+    hasher.update(ctx.uri.path());
+    hasher.update([0]);
+    if let Some(query) = ctx.uri.query() {
+        let mut pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+            .filter(|(name, _)| name != "key")
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        pairs.sort();
+        for (name, value) in pairs {
+            hasher.update(format!("{name}={value}&"));
+        }
+    }
+    hasher.update([0]);
+    hasher.update(model.as_deref().unwrap_or_default());
+    hasher.update([0]);
+    // Requests pinned to different key pools are kept apart.
+    for name in [group_selection::GROUP_HEADER, group_selection::TIER_HEADER] {
+        if let Some(value) = ctx.headers.get(name) {
+            hasher.update(name);
+            hasher.update(value.as_bytes());
+        }
+    }
+    // Compressed and plain bodies must not be served to the wrong client.
+    if let Some(encoding) = ctx.headers.get(header::ACCEPT_ENCODING) {
+        hasher.update(encoding.as_bytes());
+    }
+    hasher.update([0]);
+    hasher.update(body.to_string());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, Uri};
+    use serde_json::json;
+
+    fn context<'a>(uri: &'a Uri, headers: &'a HeaderMap, body: &'a Bytes) -> RequestContext<'a> {
+        RequestContext {
+            method: &Method::POST,
+            uri,
+            headers,
+            body,
+            json: None,
+        }
+    }
+
+    #[test]
+    fn test_cache_key_ignores_api_key_and_json_layout() {
+        let headers = HeaderMap::new();
+        let uri_a: Uri = "/v1beta/models/m:generateContent?key=a".parse().unwrap();
+        let uri_b: Uri = "/v1beta/models/m:generateContent?key=b".parse().unwrap();
+        let body_a = Bytes::from(r#"{"contents":[],"generationConfig":{"temperature":0}}"#);
+        let body_b = Bytes::from(r#"{ "generationConfig": {"temperature": 0}, "contents": [] }"#);
+        let model = Some("m".to_string());
+
+        let key_a = request_key(
+            &context(&uri_a, &headers, &body_a),
+            &model,
+            &serde_json::from_slice(&body_a).unwrap(),
+        );
+        let key_b = request_key(
+            &context(&uri_b, &headers, &body_b),
+            &model,
+            &serde_json::from_slice(&body_b).unwrap(),
+        );
+        assert_eq!(key_a, key_b);
+    }
+
+    #[test]
+    fn test_deterministic_detection() {
+        assert!(is_deterministic(
+            "/v1beta/models/text-embedding-004:embedContent",
+            &json!({})
+        ));
+        assert!(is_deterministic("/v1/embeddings", &json!({})));
+        assert!(is_deterministic(
+            "/v1/chat/completions",
+            &json!({"temperature": 0})
+        ));
+        assert!(!is_deterministic(
+            "/v1beta/models/m:generateContent",
+            &json!({"generationConfig": {"temperature": 0.7}})
+        ));
+        assert!(!is_deterministic(
+            "/v1beta/models/m:generateContent",
+            &json!({})
+        ));
+    }
+
+    #[test]
+    fn test_cache_control_directives() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(cache_control(&headers), (true, true));
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("No-Cache"));
+        assert_eq!(cache_control(&headers), (false, true));
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("max-age=0, no-store"),
+        );
+        assert_eq!(cache_control(&headers), (false, false));
+    }
+}
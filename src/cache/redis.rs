@@ -0,0 +1,49 @@
+// src/cache/redis.rs
+
+use super::{CacheStore, CachedResponse};
+use crate::error::Result;
+use async_trait::async_trait;
+use deadpool_redis::Pool;
+use redis::AsyncCommands;
+use std::time::Duration;
+
+const CACHE_KEY_SEGMENT: &str = "cache:";
+
+/// Cache store shared between proxy instances through Redis. Entries expire
+/// via `SET ... EX`.
+pub struct RedisCacheStore {
+    pool: Pool,
+    key_prefix: String,
+}
+
+impl RedisCacheStore {
+    pub fn new(pool: Pool, key_prefix: Option<&str>) -> Self {
+        Self {
+            pool,
+            key_prefix: key_prefix.unwrap_or("gemini_proxy:").to_string(),
+        }
+    }
+
+    fn redis_key(&self, key: &str) -> String {
+        format!("{}{CACHE_KEY_SEGMENT}{key}", self.key_prefix)
+    }
+}
+
+#[async_trait]
+impl CacheStore for RedisCacheStore {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let mut conn = self.pool.get().await?;
+        let raw: Option<String> = conn.get(self.redis_key(key)).await?;
+        raw.map(|raw| serde_json::from_str(&raw).map_err(Into::into))
+            .transpose()
+    }
+
+    async fn put(&self, key: &str, response: &CachedResponse, ttl: Duration) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let raw = serde_json::to_string(response)?;
+        let _: () = conn
+            .set_ex(self.redis_key(key), raw, ttl.as_secs().max(1))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+// src/catalog.rs
+
+//! Aggregated model catalog.
+//!
+//! Upstream models are fetched periodically with the next available key of each
+//! group and merged with the configured `model_aliases`. The catalog is served in both
+//! the OpenAI (`/v1/models`) and Gemini (`/v1beta/models`) list formats, with
+//! an `available` flag for models whose key pool is currently exhausted.
+
+use crate::{
+    config::AppConfig,
+    error::{AppError, Result},
+    key_manager::FlattenedKeyInfo,
+    state::AppState,
+};
+use parking_lot::RwLock;
+use reqwest::StatusCode;
+use secrecy::ExposeSecret;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// Interval used to re-check the configuration while the catalog is disabled.
+const DISABLED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Cached upstream model listings, keyed by group name.
+#[derive(Debug, Default)]
+pub struct ModelCatalog {
+    upstream: RwLock<HashMap<String, Vec<Value>>>,
+    refreshed_at: RwLock<Option<Instant>>,
+}
+
+/// A merged catalog entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogModel {
+    /// Bare model id, without the `models/` prefix.
+    pub id: String,
+    /// The upstream Gemini model object, if the model was listed upstream.
+    pub upstream: Option<Value>,
+    /// Groups whose keys serve this model.
+    pub groups: BTreeSet<String>,
+    /// Whether at least one key that would serve this model is unblocked.
+    pub available: bool,
+}
+
+impl ModelCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true once at least one refresh has completed.
+    pub fn is_loaded(&self) -> bool {
+        self.refreshed_at.read().is_some()
+    }
+
+    /// Returns whether any group's upstream listing includes `model`.
+    pub fn contains(&self, model: &str) -> bool {
+        self.upstream.read().values().flatten().any(|entry| {
+            entry
+                .get("name")
+                .and_then(Value::as_str)
+                .is_some_and(|name| name.trim_start_matches("models/") == model)
+        })
+    }
+
+    /// Fetches the model list for every group with the group's next available
+    /// key, so catalog calls rotate like proxied ones and the key's breaker
+    /// records the outcome. Groups whose fetch fails keep their previous listing.
+    pub async fn refresh(&self, state: &Arc<AppState>) {
+        let configured: BTreeSet<String> = state
+            .config
+            .read()
+            .await
+            .groups
+            .iter()
+            .map(|g| g.name.clone())
+            .collect();
+
+        let mut fetched = HashMap::new();
+        for group in &configured {
+            let key_info = match state
+                .key_manager
+                .read()
+                .await
+                .get_next_available_key_info_for_model(Some(group), None)
+                .await
+            {
+                Ok(Some(info)) => info,
+                Ok(None) => {
+                    debug!(group, "No available key to fetch upstream models");
+                    continue;
+                }
+                Err(e) => {
+                    warn!(group, error = %e, "Failed to select a key for the model list");
+                    continue;
+                }
+            };
+            match fetch_models(state, &key_info).await {
+                Ok(models) => {
+                    state
+                        .circuit_breakers
+                        .record_success(key_info.key.expose_secret(), None)
+                        .await;
+                    debug!(group, count = models.len(), "Fetched upstream models");
+                    fetched.insert(group.clone(), models);
+                }
+                Err(e) => warn!(group, error = %e, "Failed to fetch upstream model list"),
+            }
+        }
+
+        let mut upstream = self.upstream.write();
+        upstream.retain(|group, _| configured.contains(group));
+        upstream.extend(fetched);
+        *self.refreshed_at.write() = Some(Instant::now());
+    }
+
+    /// Builds the merged catalog for the current configuration and key states.
+    pub async fn models(&self, state: &Arc<AppState>) -> Vec<CatalogModel> {
+        let config = state.config.read().await.clone();
+        let key_info = state.key_manager.read().await.get_all_key_info().await;
+        let healthy = healthy_keys(state, &key_info).await;
+
+        let mut available_groups: BTreeSet<&str> = BTreeSet::new();
+        for info in key_info.values() {
+            if healthy.contains(info.key.expose_secret()) {
+                available_groups.insert(info.group_name.as_str());
+            }
+        }
+
+        let upstream = self.upstream.read().clone();
+        merge(&config, &upstream, &available_groups)
+    }
+}
+
+/// Merges upstream listings with configured aliases and computes availability
+/// the same way the proxy routes requests: aliased models are served by their
+/// alias group, everything else by the whole key pool.
+fn merge(
+    config: &AppConfig,
+    upstream: &HashMap<String, Vec<Value>>,
+    available_groups: &BTreeSet<&str>,
+) -> Vec<CatalogModel> {
+    let mut models: BTreeMap<String, CatalogModel> = BTreeMap::new();
+
+    for (group, listing) in upstream {
+        for model in listing {
+            let Some(name) = model.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let id = name.trim_start_matches("models/").to_string();
+            models
+                .entry(id.clone())
+                .or_insert_with(|| CatalogModel {
+                    id,
+                    upstream: Some(model.clone()),
+                    groups: BTreeSet::new(),
+                    available: false,
+                })
+                .groups
+                .insert(group.clone());
+        }
+    }
+
+    for group in &config.groups {
+        for alias in &group.model_aliases {
+            models
+                .entry(alias.clone())
+                .or_insert_with(|| CatalogModel {
+                    id: alias.clone(),
+                    upstream: None,
+                    groups: BTreeSet::new(),
+                    available: false,
+                })
+                .groups
+                .insert(group.name.clone());
+        }
+    }
+
+    for model in models.values_mut() {
+        model.available = match config.get_group_for_model(&model.id) {
+            // An aliased model is only routed to its alias group, whichever
+            // groups listed it upstream.
+            Some(group) => {
+                model.groups = BTreeSet::from([group.to_string()]);
+                available_groups.contains(group)
+            }
+            None => !available_groups.is_empty(),
+        };
+    }
+
+    models.into_values().collect()
+}
+
+/// Renders the catalog as an OpenAI `GET /v1/models` response.
+pub fn to_openai_list(models: &[CatalogModel]) -> Value {
+    let data: Vec<Value> = models
+        .iter()
+        .map(|model| {
+            json!({
+                "id": model.id,
+                "object": "model",
+                "created": 0,
+                "owned_by": "google",
+                "available": model.available,
+                "groups": model.groups,
+            })
+        })
+        .collect();
+    json!({ "object": "list", "data": data })
+}
+
+/// Renders the catalog as a Gemini `GET /v1beta/models` response.
+pub fn to_gemini_list(models: &[CatalogModel]) -> Value {
+    let models: Vec<Value> = models
+        .iter()
+        .map(|model| {
+            let mut entry = model.upstream.clone().unwrap_or_else(|| {
+                json!({
+                    "name": format!("models/{}", model.id),
+                    "displayName": model.id,
+                })
+            });
+            entry["available"] = json!(model.available);
+            entry["groups"] = json!(model.groups);
+            entry
+        })
+        .collect();
+    json!({ "models": models })
+}
+
+/// Periodically refreshes the catalog. The interval is re-read from the
+/// configuration on every cycle so hot reloads take effect.
+pub fn spawn_refresh_task(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let interval = state.config.read().await.server.model_catalog_refresh_secs;
+            if interval == 0 {
+                tokio::time::sleep(DISABLED_POLL_INTERVAL).await;
+                continue;
+            }
+            state.model_catalog.refresh(&state).await;
+            info!("Model catalog refreshed");
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    })
+}
+
+async fn healthy_keys(
+    state: &Arc<AppState>,
+    key_info: &HashMap<String, FlattenedKeyInfo>,
+) -> BTreeSet<String> {
+    match state.key_manager.read().await.get_key_states().await {
+        Ok(states) => key_info
+            .keys()
+            .filter(|key| states.get(*key).map_or(true, |s| s.is_available()))
+            .cloned()
+            .collect(),
+        Err(e) => {
+            warn!(error = %e, "Failed to read key states; treating all keys as healthy");
+            key_info.keys().cloned().collect()
+        }
+    }
+}
+
+/// Fetches every page of the model list with `key_info`. Failures are recorded
+/// on the key's breaker; the caller records the success.
+async fn fetch_models(state: &Arc<AppState>, key_info: &FlattenedKeyInfo) -> Result<Vec<Value>> {
+    let breakers = &state.circuit_breakers;
+    let key = key_info.key.expose_secret();
+    let setup = async {
+        let client = state.get_client(key_info.proxy_url.as_deref()).await?;
+        let list_url = url::Url::parse(&key_info.target_url)?.join("/v1beta/models")?;
+        Ok::<_, AppError>((client, list_url))
+    };
+    let (client, list_url) = match setup.await {
+        Ok(setup) => setup,
+        Err(e) => {
+            breakers.release(key, None).await;
+            return Err(e);
+        }
+    };
+    let mut models = Vec::new();
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut url = list_url.clone();
+        url.query_pairs_mut()
+            .append_pair("pageSize", "1000")
+            .append_pair("key", key);
+        if let Some(token) = &page_token {
+            url.query_pairs_mut().append_pair("pageToken", token);
+        }
+
+        let response = match client.get(url).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                breakers.record_failure(key, None).await;
+                return Err(e.into());
+            }
+        };
+        let status = response.status();
+        if !status.is_success() {
+            // Rate limits and transient errors say nothing about the key.
+            if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                breakers.release(key, None).await;
+            } else {
+                breakers.record_failure(key, None).await;
+            }
+            return Err(AppError::internal(format!(
+                "model list returned status {status}"
+            )));
+        }
+        let page: Value = match response.json().await {
+            Ok(page) => page,
+            Err(e) => {
+                breakers.record_failure(key, None).await;
+                return Err(e.into());
+            }
+        };
+        models.extend(
+            page.get("models")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default(),
+        );
+
+        page_token = page
+            .get("nextPageToken")
+            .and_then(Value::as_str)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        if page_token.is_none() {
+            return Ok(models);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeyGroup;
+
+    #[test]
+    fn test_merge_adds_aliases_and_availability() {
+        let config = AppConfig {
+            groups: vec![
+                KeyGroup {
+                    name: "flash".to_string(),
+                    model_aliases: vec!["gemini-2.0-flash".to_string()],
+                    ..Default::default()
+                },
+                KeyGroup {
+                    name: "pro".to_string(),
+                    model_aliases: vec!["my-pro".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let upstream = HashMap::from([(
+            "flash".to_string(),
+            vec![
+                json!({"name": "models/gemini-2.0-flash", "displayName": "Gemini 2.0 Flash"}),
+                json!({"name": "models/text-embedding-004"}),
+            ],
+        )]);
+        let available = BTreeSet::from(["flash"]);
+
+        let models = merge(&config, &upstream, &available);
+        let by_id: HashMap<&str, &CatalogModel> =
+            models.iter().map(|m| (m.id.as_str(), m)).collect();
+
+        assert_eq!(models.len(), 3);
+        assert!(by_id["gemini-2.0-flash"].available);
+        assert!(by_id["text-embedding-004"].available);
+        assert!(!by_id["my-pro"].available, "pro group has no healthy keys");
+        assert!(by_id["my-pro"].upstream.is_none());
+
+        assert_eq!(
+            by_id["gemini-2.0-flash"].groups,
+            BTreeSet::from(["flash".to_string()])
+        );
+
+        let gemini = to_gemini_list(&models);
+        assert_eq!(gemini["models"][1]["name"], "models/my-pro");
+        let openai = to_openai_list(&models);
+        assert_eq!(openai["data"][0]["id"], "gemini-2.0-flash");
+    }
+
+    #[test]
+    fn test_listed_models_follow_their_alias_group() {
+        let config = AppConfig {
+            groups: vec![
+                KeyGroup {
+                    name: "free".to_string(),
+                    ..Default::default()
+                },
+                KeyGroup {
+                    name: "pro".to_string(),
+                    model_aliases: vec!["gemini-2.5-pro".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let upstream = HashMap::from([(
+            "free".to_string(),
+            vec![
+                json!({"name": "models/gemini-2.5-pro", "displayName": "Gemini 2.5 Pro"}),
+                json!({"name": "models/gemini-2.0-flash"}),
+            ],
+        )]);
+        let available = BTreeSet::from(["free"]);
+
+        let models = merge(&config, &upstream, &available);
+        let by_id: HashMap<&str, &CatalogModel> =
+            models.iter().map(|m| (m.id.as_str(), m)).collect();
+
+        let pro = by_id["gemini-2.5-pro"];
+        assert_eq!(pro.groups, BTreeSet::from(["pro".to_string()]));
+        assert!(!pro.available, "only the pro group serves it");
+        assert_eq!(
+            pro.upstream.as_ref().unwrap()["displayName"],
+            "Gemini 2.5 Pro"
+        );
+        assert_eq!(
+            by_id["gemini-2.0-flash"].groups,
+            BTreeSet::from(["free".to_string()])
+        );
+        assert!(by_id["gemini-2.0-flash"].available);
+    }
+}
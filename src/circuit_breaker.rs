@@ -1,16 +1,41 @@
+use crate::key_manager::KeyManager;
+use crate::metrics::{CircuitBreakerState as MetricsState, METRICS};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CircuitState {
     Closed,   // Normal operation
     Open,     // Circuit is open, failing fast
     HalfOpen, // Testing if service recovered
 }
 
+impl From<&CircuitState> for MetricsState {
+    fn from(state: &CircuitState) -> Self {
+        match state {
+            CircuitState::Closed => Self::Closed,
+            CircuitState::Open => Self::Open,
+            CircuitState::HalfOpen => Self::HalfOpen,
+        }
+    }
+}
+
+/// A state forced on a breaker by an operator, or `Auto` to let it follow
+/// request outcomes again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForcedState {
+    Open,
+    Closed,
+    Auto,
+}
+
 #[derive(Debug, Clone)]
 pub struct CircuitBreakerConfig {
     pub failure_threshold: usize,
@@ -28,37 +53,82 @@ impl Default for CircuitBreakerConfig {
     }
 }
 
+impl From<&crate::config::app::CircuitBreakerConfig> for CircuitBreakerConfig {
+    fn from(config: &crate::config::app::CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold as usize,
+            recovery_timeout: Duration::from_secs(config.recovery_timeout_secs),
+            success_threshold: config.half_open_max_calls as usize,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CircuitBreakerState {
     state: CircuitState,
     failure_count: usize,
     success_count: usize,
+    // Probes admitted since the breaker last went half-open
+    half_open_calls: usize,
+    // Set while an operator holds the breaker open or closed
+    forced: bool,
     last_failure_time: Option<Instant>,
     next_attempt: Option<Instant>,
 }
 
+/// Point-in-time view of a breaker for the admin API. The default is the
+/// view of a breaker that has not been created yet: closed, with no history.
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerSnapshot {
+    pub state: CircuitState,
+    pub forced: bool,
+    pub failure_count: usize,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    /// When an open breaker next admits a half-open probe.
+    pub next_attempt: Option<Instant>,
+}
+
+impl Default for CircuitBreakerSnapshot {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            forced: false,
+            failure_count: 0,
+            total_requests: 0,
+            total_failures: 0,
+            next_attempt: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CircuitBreaker {
-    config: CircuitBreakerConfig,
+    config: parking_lot::RwLock<CircuitBreakerConfig>,
     state: Arc<RwLock<CircuitBreakerState>>,
     total_requests: AtomicU64,
     total_failures: AtomicU64,
+    // Registry tick of the last lookup, used to evict idle (key, model) breakers
+    last_used: AtomicU64,
     name: String,
 }
 
 impl CircuitBreaker {
     pub fn new(name: String, config: CircuitBreakerConfig) -> Self {
         Self {
-            config,
+            config: parking_lot::RwLock::new(config),
             state: Arc::new(RwLock::new(CircuitBreakerState {
                 state: CircuitState::Closed,
                 failure_count: 0,
                 success_count: 0,
+                half_open_calls: 0,
+                forced: false,
                 last_failure_time: None,
                 next_attempt: None,
             })),
             total_requests: AtomicU64::new(0),
             total_failures: AtomicU64::new(0),
+            last_used: AtomicU64::new(0),
             name,
         }
     }
@@ -68,10 +138,8 @@ impl CircuitBreaker {
         F: FnOnce() -> Fut,
         Fut: std::future::Future<Output = Result<T, E>>,
     {
-        self.total_requests.fetch_add(1, Ordering::Relaxed);
-
         // Check if we should allow the request
-        if !self.should_allow_request().await {
+        if !self.try_acquire().await {
             debug!(circuit_breaker = %self.name, "Circuit breaker is open, failing fast");
             return Err(CircuitBreakerError::CircuitOpen);
         }
@@ -79,42 +147,100 @@ impl CircuitBreaker {
         // Execute the operation
         match operation().await {
             Ok(result) => {
-                self.on_success().await;
+                self.record_success().await;
                 Ok(result)
             }
             Err(error) => {
-                self.on_failure().await;
+                self.record_failure().await;
                 Err(CircuitBreakerError::OperationFailed(error))
             }
         }
     }
 
-    async fn should_allow_request(&self) -> bool {
+    /// Returns whether `try_acquire` would admit a request, without taking a
+    /// half-open probe.
+    pub async fn is_available(&self) -> bool {
+        let state = self.state.read().await;
+        let success_threshold = self.config.read().success_threshold;
+        if state.forced {
+            return state.state == CircuitState::Closed;
+        }
+        match state.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => state.next_attempt.is_some_and(|t| Instant::now() >= t),
+            CircuitState::HalfOpen => {
+                state.half_open_calls < success_threshold
+                    || state.next_attempt.is_some_and(|t| Instant::now() >= t)
+            }
+        }
+    }
+
+    /// Admits a request unless the breaker is open. Once the recovery timeout
+    /// has passed, the breaker goes half-open and admits up to
+    /// `success_threshold` requests as probes; if their outcomes are never
+    /// recorded, a new round of probes starts after another recovery timeout.
+    pub async fn try_acquire(&self) -> bool {
+        let admitted = self.acquire_slot().await;
+        if admitted {
+            self.total_requests.fetch_add(1, Ordering::Relaxed);
+        }
+        admitted
+    }
+
+    /// `try_acquire` without counting the request.
+    async fn acquire_slot(&self) -> bool {
         let mut state = self.state.write().await;
         let now = Instant::now();
+        let config = self.config.read().clone();
+        if state.forced {
+            return state.state == CircuitState::Closed;
+        }
 
         match state.state {
             CircuitState::Closed => true,
             CircuitState::Open => {
-                if let Some(next_attempt) = state.next_attempt {
-                    if now >= next_attempt {
-                        info!(circuit_breaker = %self.name, "Circuit breaker transitioning to half-open");
-                        state.state = CircuitState::HalfOpen;
-                        state.success_count = 0;
-                        true
-                    } else {
-                        false
-                    }
+                if state.next_attempt.is_some_and(|t| now >= t) {
+                    info!(circuit_breaker = %self.name, "Circuit breaker transitioning to half-open");
+                    self.set_state(&mut state, CircuitState::HalfOpen);
+                    state.success_count = 0;
+                    state.half_open_calls = 1;
+                    state.next_attempt = Some(now + config.recovery_timeout);
+                    true
+                } else {
+                    false
+                }
+            }
+            CircuitState::HalfOpen => {
+                if state.half_open_calls < config.success_threshold {
+                    state.half_open_calls += 1;
+                    true
+                } else if state.next_attempt.is_some_and(|t| now >= t) {
+                    state.half_open_calls = 1;
+                    state.next_attempt = Some(now + config.recovery_timeout);
+                    true
                 } else {
                     false
                 }
             }
-            CircuitState::HalfOpen => true,
         }
     }
 
-    async fn on_success(&self) {
+    /// Gives back a half-open probe taken by `acquire_slot` for a request that
+    /// was not sent.
+    async fn release_slot(&self) {
         let mut state = self.state.write().await;
+        if !state.forced && state.state == CircuitState::HalfOpen {
+            state.half_open_calls = state.half_open_calls.saturating_sub(1);
+        }
+    }
+
+    /// Records a successful request; enough successes close a half-open breaker.
+    pub async fn record_success(&self) {
+        let mut state = self.state.write().await;
+        let success_threshold = self.config.read().success_threshold;
+        if state.forced {
+            return;
+        }
 
         match state.state {
             CircuitState::Closed => {
@@ -123,11 +249,12 @@ impl CircuitBreaker {
             }
             CircuitState::HalfOpen => {
                 state.success_count += 1;
-                if state.success_count >= self.config.success_threshold {
+                if state.success_count >= success_threshold {
                     info!(circuit_breaker = %self.name, "Circuit breaker closing after successful recovery");
-                    state.state = CircuitState::Closed;
+                    self.set_state(&mut state, CircuitState::Closed);
                     state.failure_count = 0;
                     state.success_count = 0;
+                    state.half_open_calls = 0;
                     state.last_failure_time = None;
                     state.next_attempt = None;
                 }
@@ -139,40 +266,106 @@ impl CircuitBreaker {
         }
     }
 
-    async fn on_failure(&self) {
+    /// Records a failed request, opening the breaker at the failure threshold
+    /// or when a half-open probe fails.
+    pub async fn record_failure(&self) {
         let mut state = self.state.write().await;
         let now = Instant::now();
+        let config = self.config.read().clone();
 
         self.total_failures.fetch_add(1, Ordering::Relaxed);
+        if state.forced {
+            return;
+        }
         state.failure_count += 1;
         state.last_failure_time = Some(now);
 
         match state.state {
             CircuitState::Closed => {
-                if state.failure_count >= self.config.failure_threshold {
+                if state.failure_count >= config.failure_threshold {
                     warn!(
                         circuit_breaker = %self.name,
                         failure_count = state.failure_count,
-                        threshold = self.config.failure_threshold,
+                        threshold = config.failure_threshold,
                         "Circuit breaker opening due to failures"
                     );
-                    state.state = CircuitState::Open;
-                    state.next_attempt = Some(now + self.config.recovery_timeout);
+                    self.set_state(&mut state, CircuitState::Open);
+                    METRICS.record_circuit_breaker_trip(self.name.clone());
+                    state.next_attempt = Some(now + config.recovery_timeout);
                 }
             }
             CircuitState::HalfOpen => {
                 warn!(circuit_breaker = %self.name, "Circuit breaker reopening after failed recovery attempt");
-                state.state = CircuitState::Open;
-                state.next_attempt = Some(now + self.config.recovery_timeout);
+                self.set_state(&mut state, CircuitState::Open);
+                state.next_attempt = Some(now + config.recovery_timeout);
                 state.success_count = 0;
+                state.half_open_calls = 0;
             }
             CircuitState::Open => {
                 // Update next attempt time
-                state.next_attempt = Some(now + self.config.recovery_timeout);
+                state.next_attempt = Some(now + config.recovery_timeout);
             }
         }
     }
 
+    /// Holds the breaker open or closed regardless of request outcomes, or
+    /// with `Auto` releases it in its current state.
+    pub async fn force(&self, forced: ForcedState) {
+        let mut state = self.state.write().await;
+        match forced {
+            ForcedState::Open => {
+                warn!(circuit_breaker = %self.name, "Circuit breaker forced open");
+                self.set_state(&mut state, CircuitState::Open);
+                state.next_attempt = Some(Instant::now() + self.config.read().recovery_timeout);
+                state.forced = true;
+            }
+            ForcedState::Closed => {
+                warn!(circuit_breaker = %self.name, "Circuit breaker forced closed");
+                self.set_state(&mut state, CircuitState::Closed);
+                state.failure_count = 0;
+                state.success_count = 0;
+                state.half_open_calls = 0;
+                state.next_attempt = None;
+                state.forced = true;
+            }
+            ForcedState::Auto => {
+                info!(circuit_breaker = %self.name, "Circuit breaker back to automatic");
+                state.forced = false;
+            }
+        }
+    }
+
+    pub async fn snapshot(&self) -> CircuitBreakerSnapshot {
+        let state = self.state.read().await;
+        let stats = self.get_stats();
+        CircuitBreakerSnapshot {
+            state: state.state.clone(),
+            forced: state.forced,
+            failure_count: state.failure_count,
+            total_requests: stats.total_requests,
+            total_failures: stats.total_failures,
+            next_attempt: match state.state {
+                CircuitState::Closed => None,
+                _ => state.next_attempt,
+            },
+        }
+    }
+
+    /// Moves to `to` and exports the new state.
+    fn set_state(&self, state: &mut CircuitBreakerState, to: CircuitState) {
+        METRICS.record_circuit_breaker_state(self.name.clone(), (&to).into());
+        state.state = to;
+    }
+
+    /// Replaces the thresholds; the current state is kept.
+    pub fn reconfigure(&self, config: CircuitBreakerConfig) {
+        *self.config.write() = config;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub async fn get_state(&self) -> CircuitState {
         self.state.read().await.state.clone()
     }
@@ -185,6 +378,184 @@ impl CircuitBreaker {
     }
 }
 
+/// Identifies a breaker by API key and model, `None` for the key's own breaker.
+pub type BreakerId = (String, Option<String>);
+
+/// Most (key, model) breakers kept at once. Model names come from requests,
+/// so beyond this the least recently used healthy breaker is dropped.
+const MAX_MODEL_BREAKERS: usize = 1024;
+
+/// Circuit breakers per API key and per (key, model), created on first use.
+///
+/// A key's breaker counts consecutive failures across all models, so a
+/// success on one model resets it while the (key, model) breaker of a
+/// failing model keeps counting. Key selection skips a key if either is open.
+#[derive(Debug)]
+pub struct CircuitBreakerRegistry {
+    config: parking_lot::RwLock<CircuitBreakerConfig>,
+    breakers: parking_lot::RwLock<HashMap<BreakerId, Arc<CircuitBreaker>>>,
+    clock: AtomicU64,
+}
+
+impl CircuitBreakerRegistry {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config: parking_lot::RwLock::new(config),
+            breakers: parking_lot::RwLock::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the breaker for `api_key`, or for `api_key` and `model` if a
+    /// model is given.
+    pub fn breaker(&self, api_key: &str, model: Option<&str>) -> Arc<CircuitBreaker> {
+        let id = (api_key.to_string(), model.map(str::to_string));
+        let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+        if let Some(breaker) = self.breakers.read().get(&id) {
+            breaker.last_used.store(tick, Ordering::Relaxed);
+            return breaker.clone();
+        }
+        let name = Self::breaker_name(api_key, model);
+        let config = self.config.read().clone();
+        let mut breakers = self.breakers.write();
+        if model.is_some() && !breakers.contains_key(&id) {
+            Self::evict_model_breaker(&mut breakers);
+        }
+        let breaker = breakers
+            .entry(id)
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(name, config)));
+        breaker.last_used.store(tick, Ordering::Relaxed);
+        breaker.clone()
+    }
+
+    /// Makes room for a new (key, model) breaker once `MAX_MODEL_BREAKERS`
+    /// exist, dropping the least recently used one. Closed breakers with no
+    /// failures go first so that open breakers keep their state.
+    fn evict_model_breaker(breakers: &mut HashMap<BreakerId, Arc<CircuitBreaker>>) {
+        let model_breakers = breakers.iter().filter(|((_, model), _)| model.is_some());
+        if model_breakers.clone().count() < MAX_MODEL_BREAKERS {
+            return;
+        }
+        let is_healthy = |breaker: &CircuitBreaker| {
+            breaker.state.try_read().is_ok_and(|state| {
+                state.state == CircuitState::Closed && !state.forced && state.failure_count == 0
+            })
+        };
+        let victim = model_breakers
+            .min_by_key(|(_, breaker)| {
+                (
+                    !is_healthy(breaker),
+                    breaker.last_used.load(Ordering::Relaxed),
+                )
+            })
+            .map(|(id, _)| id.clone());
+        if let Some(id) = victim {
+            breakers.remove(&id);
+        }
+    }
+
+    fn breaker_name(api_key: &str, model: Option<&str>) -> String {
+        match model {
+            Some(model) => format!("key_{}_{model}", KeyManager::preview_key_str(api_key)),
+            None => format!("key_{}", KeyManager::preview_key_str(api_key)),
+        }
+    }
+
+    /// Returns the name and state of a breaker without creating it; a breaker
+    /// not created yet reports as closed.
+    pub async fn peek(
+        &self,
+        api_key: &str,
+        model: Option<&str>,
+    ) -> (String, CircuitBreakerSnapshot) {
+        let id = (api_key.to_string(), model.map(str::to_string));
+        let breaker = self.breakers.read().get(&id).cloned();
+        match breaker {
+            Some(breaker) => (breaker.name().to_string(), breaker.snapshot().await),
+            None => (
+                Self::breaker_name(api_key, model),
+                CircuitBreakerSnapshot::default(),
+            ),
+        }
+    }
+
+    fn breakers_for(&self, api_key: &str, model: Option<&str>) -> Vec<Arc<CircuitBreaker>> {
+        let mut breakers = vec![self.breaker(api_key, None)];
+        if let Some(model) = model.filter(|m| !m.is_empty()) {
+            breakers.push(self.breaker(api_key, Some(model)));
+        }
+        breakers
+    }
+
+    /// Admits a request on `api_key` for `model` if neither breaker is open,
+    /// taking a half-open probe where needed. If one breaker refuses, probes
+    /// already taken on the other are given back, and only admitted requests
+    /// are counted.
+    pub async fn try_acquire(&self, api_key: &str, model: Option<&str>) -> bool {
+        let breakers = self.breakers_for(api_key, model);
+        for breaker in &breakers {
+            if !breaker.is_available().await {
+                return false;
+            }
+        }
+        for (taken, breaker) in breakers.iter().enumerate() {
+            if !breaker.acquire_slot().await {
+                for breaker in &breakers[..taken] {
+                    breaker.release_slot().await;
+                }
+                return false;
+            }
+        }
+        for breaker in &breakers {
+            breaker.total_requests.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+
+    pub async fn record_success(&self, api_key: &str, model: Option<&str>) {
+        for breaker in self.breakers_for(api_key, model) {
+            breaker.record_success().await;
+        }
+    }
+
+    pub async fn record_failure(&self, api_key: &str, model: Option<&str>) {
+        for breaker in self.breakers_for(api_key, model) {
+            breaker.record_failure().await;
+        }
+    }
+
+    /// Gives back the half-open probes taken by `try_acquire` for a request
+    /// whose outcome says nothing about the key.
+    pub async fn release(&self, api_key: &str, model: Option<&str>) {
+        for breaker in self.breakers_for(api_key, model) {
+            breaker.release_slot().await;
+        }
+    }
+
+    /// Returns every breaker created so far, ordered by key and model.
+    pub fn breakers(&self) -> Vec<(BreakerId, Arc<CircuitBreaker>)> {
+        let mut breakers: Vec<_> = self
+            .breakers
+            .read()
+            .iter()
+            .map(|(id, breaker)| (id.clone(), breaker.clone()))
+            .collect();
+        breakers.sort_by(|(a, _), (b, _)| a.cmp(b));
+        breakers
+    }
+
+    /// Applies new thresholds to existing and future breakers and drops the
+    /// breakers of keys that are no longer configured.
+    pub fn reconfigure(&self, config: CircuitBreakerConfig, is_configured: impl Fn(&str) -> bool) {
+        *self.config.write() = config.clone();
+        let mut breakers = self.breakers.write();
+        breakers.retain(|(api_key, _), _| is_configured(api_key));
+        for breaker in breakers.values() {
+            breaker.reconfigure(config.clone());
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CircuitBreakerStats {
     pub total_requests: u64,
@@ -255,4 +626,46 @@ mod tests {
         // Should be closed now
         assert_eq!(cb.get_state().await, CircuitState::Closed);
     }
+
+    #[tokio::test]
+    async fn test_released_probe_can_be_taken_again() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 1,
+            recovery_timeout: Duration::from_millis(50),
+            success_threshold: 1,
+        };
+        let cb = CircuitBreaker::new("test".to_string(), config);
+        cb.record_failure().await;
+        assert!(!cb.try_acquire().await);
+
+        sleep(Duration::from_millis(60)).await;
+        assert!(cb.acquire_slot().await);
+        cb.release_slot().await;
+        assert!(cb.try_acquire().await);
+        assert!(!cb.try_acquire().await);
+
+        // Refused calls and released probes are not requests.
+        assert_eq!(cb.get_stats().total_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_model_breakers_are_bounded_and_keep_open_ones() {
+        let registry = CircuitBreakerRegistry::new(CircuitBreakerConfig {
+            failure_threshold: 1,
+            ..Default::default()
+        });
+        registry.record_failure("key", Some("model-0")).await;
+        for i in 1..=MAX_MODEL_BREAKERS {
+            registry.breaker("key", Some(&format!("model-{i}")));
+        }
+
+        let breakers = registry.breakers();
+        let models: Vec<_> = breakers
+            .iter()
+            .filter_map(|((_, model), _)| model.as_deref())
+            .collect();
+        assert_eq!(models.len(), MAX_MODEL_BREAKERS);
+        assert!(models.contains(&"model-0"), "open breaker was evicted");
+        assert!(!models.contains(&"model-1"));
+    }
 }
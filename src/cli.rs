@@ -92,6 +92,14 @@ pub enum Commands {
         #[command(subcommand)]
         template: GenerateCommands,
     },
+
+    /// Split and merge batch job files locally. Each part is submitted as its
+    /// own batch job through the proxy, which picks the key and records it as
+    /// the job's owner
+    BatchFiles {
+        #[command(subcommand)]
+        action: BatchFileCommands,
+    },
 }
 
 #[derive(Subcommand)]
@@ -117,6 +125,39 @@ pub enum KeyCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum BatchFileCommands {
+    /// Split a JSONL batch input file into one part per key
+    Split {
+        /// Batch input file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Number of parts (defaults to the number of configured API keys)
+        #[arg(short, long)]
+        parts: Option<usize>,
+
+        /// Directory for the part files (defaults to the input's directory)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+
+    /// Merge JSONL batch result files
+    Merge {
+        /// Result files, in part order
+        #[arg(value_name = "FILE", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Original input file whose request order the results should follow
+        #[arg(long, value_name = "FILE")]
+        order_by: Option<PathBuf>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum GenerateCommands {
     /// Generate example configuration file
@@ -0,0 +1,240 @@
+// src/coalesce.rs
+
+//! Coalescing of identical in-flight requests.
+//!
+//! The first request for a key spawns the upstream call as a detached task;
+//! identical requests arriving while it runs subscribe to the same result.
+//! The buffered response (including error responses) is fanned out to every
+//! waiter. The task keeps running if the initiating client disconnects and is
+//! aborted only once no waiter is left.
+
+use crate::cache::CachedResponse;
+use crate::error::{AppError, Result};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+use tracing::debug;
+
+struct Flight {
+    id: u64,
+    result: watch::Receiver<Option<CachedResponse>>,
+    abort: AbortHandle,
+}
+
+/// Tracks upstream calls that are currently in flight, keyed by request hash.
+#[derive(Default)]
+pub struct RequestCoalescer {
+    flights: Mutex<HashMap<String, Arc<Flight>>>,
+    next_id: AtomicU64,
+}
+
+impl std::fmt::Debug for RequestCoalescer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestCoalescer")
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+/// Outcome of [`RequestCoalescer::run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coalesced {
+    pub response: CachedResponse,
+    /// True when this caller joined a call started by another request.
+    pub shared: bool,
+}
+
+impl RequestCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct upstream calls currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.flights.lock().len()
+    }
+
+    /// Runs `start()` for the first caller with `key` and shares its result
+    /// with every caller that arrives before it completes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Internal` if the shared task panicked.
+    pub async fn run<F, Fut>(self: &Arc<Self>, key: String, start: F) -> Result<Coalesced>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = CachedResponse> + Send + 'static,
+    {
+        let (flight, shared) = {
+            let mut flights = self.flights.lock();
+            match flights.get(&key) {
+                Some(flight) => (flight.clone(), true),
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let (tx, rx) = watch::channel(None);
+                    let future = start();
+                    let coalescer = Arc::clone(self);
+                    let task_key = key.clone();
+                    // The task cannot remove its entry before it is inserted
+                    // because it needs the lock we are holding.
+                    let task = tokio::spawn(async move {
+                        let response = future.await;
+                        let _ = tx.send(Some(response));
+                        coalescer.finish(&task_key, id);
+                    });
+                    let flight = Arc::new(Flight {
+                        id,
+                        result: rx,
+                        abort: task.abort_handle(),
+                    });
+                    flights.insert(key.clone(), flight.clone());
+                    (flight, false)
+                }
+            }
+        };
+
+        if shared {
+            debug!(key = %key, "Joining in-flight upstream request");
+        }
+
+        let mut waiter = Waiter {
+            coalescer: self,
+            key,
+            flight,
+            done: false,
+        };
+        let mut result = waiter.flight.result.clone();
+        let response = result
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| AppError::internal("Coalesced upstream request did not complete"))?
+            .clone()
+            .expect("wait_for returned without a value");
+        waiter.done = true;
+        Ok(Coalesced { response, shared })
+    }
+
+    fn finish(&self, key: &str, id: u64) {
+        let mut flights = self.flights.lock();
+        if flights.get(key).is_some_and(|f| f.id == id) {
+            flights.remove(key);
+        }
+    }
+}
+
+/// Held by each caller while it waits. Dropping the last waiter of an
+/// unfinished flight aborts the upstream call.
+struct Waiter<'a> {
+    coalescer: &'a Arc<RequestCoalescer>,
+    key: String,
+    flight: Arc<Flight>,
+    done: bool,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut flights = self.coalescer.flights.lock();
+        let Some(current) = flights.get(&self.key) else {
+            return;
+        };
+        // One reference is held by the map and one by this waiter.
+        if current.id == self.flight.id && Arc::strong_count(current) == 2 {
+            debug!(key = %self.key, "Last waiter left; aborting upstream request");
+            self.flight.abort.abort();
+            flights.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    fn response(body: &'static str) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            headers: vec![],
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_callers_share_one_call() {
+        let coalescer = Arc::new(RequestCoalescer::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let run = |calls: Arc<AtomicUsize>| {
+            let coalescer = coalescer.clone();
+            async move {
+                coalescer
+                    .run("k".to_string(), move || async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        response("shared")
+                    })
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let (a, b, c) = tokio::join!(run(calls.clone()), run(calls.clone()), run(calls.clone()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!([a.shared, b.shared, c.shared], [false, true, true]);
+        assert_eq!(b.response, response("shared"));
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_call_survives_initiator_and_aborts_without_waiters() {
+        let coalescer = Arc::new(RequestCoalescer::new());
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        let start = |completed: Arc<AtomicUsize>| {
+            move || async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                completed.fetch_add(1, Ordering::SeqCst);
+                response("done")
+            }
+        };
+
+        // The initiator gives up early; the follower still gets the result.
+        let initiator = {
+            let coalescer = coalescer.clone();
+            let start = start(completed.clone());
+            tokio::spawn(async move { coalescer.run("k".to_string(), start).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let follower = {
+            let coalescer = coalescer.clone();
+            let start = start(completed.clone());
+            tokio::spawn(async move { coalescer.run("k".to_string(), start).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        initiator.abort();
+        let follower = follower.await.unwrap().unwrap();
+        assert!(follower.shared);
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+
+        // A lone caller that disconnects cancels the upstream call.
+        let lone = {
+            let coalescer = coalescer.clone();
+            let start = start(completed.clone());
+            tokio::spawn(async move { coalescer.run("other".to_string(), start).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        lone.abort();
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+}
@@ -1,6 +1,7 @@
 // src/config/app.rs
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
 pub struct KeyGroup {
@@ -15,6 +16,25 @@ pub struct KeyGroup {
     pub target_url: String,
     #[serde(default)]
     pub top_p: Option<f32>,
+    /// Rewrites of generation parameters for requests routed to this group.
+    #[serde(default)]
+    pub request_rules: Option<RequestRules>,
+    /// Body size limit in bytes for requests routed to this group, in place
+    /// of the route or server limit.
+    #[serde(default)]
+    pub max_request_size: Option<usize>,
+    /// Key tier of this group, e.g. `paid`, for clients selecting keys with
+    /// `X-Proxy-Key-Tier`.
+    #[serde(default)]
+    pub tier: Option<String>,
+    /// Upstream attempts per request for this group, in place of
+    /// `server.max_attempts`.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Overall deadline for requests routed to this group, in place of
+    /// `server.request_deadline_secs`.
+    #[serde(default)]
+    pub request_deadline_secs: Option<u64>,
 }
 
 impl Default for KeyGroup {
@@ -26,6 +46,121 @@ impl Default for KeyGroup {
             proxy_url: None,
             target_url: default_target_url(),
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
+        }
+    }
+}
+
+/// Rules for the generation parameters of a request, applied to Gemini-native
+/// and OpenAI-shaped bodies alike.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default, Serialize)]
+pub struct RequestRules {
+    #[serde(default)]
+    pub temperature: Option<ParamRule>,
+    #[serde(default)]
+    pub max_output_tokens: Option<ParamRule>,
+    #[serde(default)]
+    pub thinking_budget: Option<ParamRule>,
+    #[serde(default)]
+    pub safety_settings: Option<ParamRule>,
+    /// A string, or a Gemini `Content` object for native requests.
+    #[serde(default)]
+    pub system_instruction: Option<ParamRule>,
+}
+
+/// How a rule changes one parameter. Written as `remove` or as a map with
+/// one of `set`, `default` or `clamp`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+#[serde(try_from = "ParamRuleRepr", into = "ParamRuleRepr")]
+pub enum ParamRule {
+    /// Always use this value.
+    Set(serde_json::Value),
+    /// Use this value when the request has none.
+    Default(serde_json::Value),
+    /// Keep a numeric value within the bounds.
+    Clamp { min: Option<f64>, max: Option<f64> },
+    /// Drop the parameter from the request.
+    Remove,
+}
+
+/// Bounds of a `clamp` rule.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ClampBounds {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max: Option<f64>,
+}
+
+/// A rule as written in YAML.
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ParamRuleMap {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clamp: Option<ClampBounds>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+enum ParamRuleRepr {
+    Keyword(String),
+    Map(ParamRuleMap),
+}
+
+impl TryFrom<ParamRuleRepr> for ParamRule {
+    type Error = String;
+
+    fn try_from(repr: ParamRuleRepr) -> Result<Self, Self::Error> {
+        match repr {
+            ParamRuleRepr::Keyword(keyword) if keyword == "remove" => Ok(Self::Remove),
+            ParamRuleRepr::Keyword(other) => Err(format!(
+                "unknown rule '{other}'; use remove, set, default or clamp"
+            )),
+            ParamRuleRepr::Map(ParamRuleMap {
+                set: Some(value),
+                default: None,
+                clamp: None,
+            }) => Ok(Self::Set(value)),
+            ParamRuleRepr::Map(ParamRuleMap {
+                set: None,
+                default: Some(value),
+                clamp: None,
+            }) => Ok(Self::Default(value)),
+            ParamRuleRepr::Map(ParamRuleMap {
+                set: None,
+                default: None,
+                clamp: Some(ClampBounds { min, max }),
+            }) => Ok(Self::Clamp { min, max }),
+            ParamRuleRepr::Map(_) => {
+                Err("a rule needs exactly one of set, default or clamp".to_string())
+            }
+        }
+    }
+}
+
+impl From<ParamRule> for ParamRuleRepr {
+    fn from(rule: ParamRule) -> Self {
+        let map = |set, default, clamp| {
+            Self::Map(ParamRuleMap {
+                set,
+                default,
+                clamp,
+            })
+        };
+        match rule {
+            ParamRule::Set(value) => map(Some(value), None, None),
+            ParamRule::Default(value) => map(None, Some(value), None),
+            ParamRule::Clamp { min, max } => map(None, None, Some(ClampBounds { min, max })),
+            ParamRule::Remove => Self::Keyword("remove".to_string()),
         }
     }
 }
@@ -42,11 +177,53 @@ pub struct ServerConfig {
     pub test_mode: bool,
     #[serde(default)]
     pub admin_token: Option<String>,
+    /// `topP` for requests whose group sets none; the top-level `top_p` is
+    /// the last fallback.
     #[serde(default)]
     pub top_p: Option<f32>,
     /// Maximum allowed number of tokens per request. If None - default 250_000 is used.
     #[serde(default)]
     pub max_tokens_per_request: Option<u64>,
+    /// Translate OpenAI `chat/completions` requests to native Gemini
+    /// `generateContent` instead of forwarding to Gemini's OpenAI endpoint.
+    #[serde(default)]
+    pub openai_native_translation: bool,
+    /// How often the aggregated model catalog is refreshed from upstream.
+    /// `0` disables the catalog and `/models` is proxied as-is.
+    #[serde(default = "default_model_catalog_refresh")]
+    pub model_catalog_refresh_secs: u64,
+    /// Share one upstream call between identical non-streaming requests
+    /// that are in flight at the same time.
+    #[serde(default)]
+    pub coalesce_requests: bool,
+    /// Stream request bodies straight upstream on routes where no token
+    /// limit, body model, request rule, `topP`, cache, coalescing or hedging
+    /// needs them. Such requests make a single attempt and are not retried.
+    #[serde(default)]
+    pub stream_request_bodies: bool,
+    /// Maximum request body size in bytes, counted as the body is received.
+    #[serde(default = "default_max_request_size")]
+    pub max_request_size: usize,
+    /// Body size limits for routes, keyed by path prefix; the longest
+    /// matching prefix wins over `max_request_size`.
+    #[serde(default)]
+    pub max_request_size_by_route: HashMap<String, usize>,
+    /// Maximum upstream attempts per request, counting key rotations and
+    /// rate limit waits. Unset tries every available key.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Overall deadline per request in seconds, covering attempts, rate limit
+    /// waits and the key wait queue. Unset leaves requests unbounded.
+    #[serde(default)]
+    pub request_deadline_secs: Option<u64>,
+    /// Origin clients use to reach the proxy, e.g. `https://proxy.example`;
+    /// resumable upload URLs are rewritten to point at it.
+    #[serde(default)]
+    pub public_base_url: Option<String>,
+    /// Take the client-facing origin from `X-Forwarded-Host` and
+    /// `X-Forwarded-Proto`. Only enable behind a reverse proxy that sets them.
+    #[serde(default)]
+    pub trust_forwarded_headers: bool,
 }
 
 impl Default for ServerConfig {
@@ -59,6 +236,16 @@ impl Default for ServerConfig {
             admin_token: None,
             top_p: None,
             max_tokens_per_request: None,
+            openai_native_translation: false,
+            model_catalog_refresh_secs: default_model_catalog_refresh(),
+            coalesce_requests: false,
+            stream_request_bodies: false,
+            max_request_size: default_max_request_size(),
+            max_request_size_by_route: HashMap::new(),
+            max_attempts: None,
+            request_deadline_secs: None,
+            public_base_url: None,
+            trust_forwarded_headers: false,
         }
     }
 }
@@ -81,10 +268,33 @@ pub struct AppConfig {
     pub circuit_breaker: Option<CircuitBreakerConfig>,
     #[serde(default)]
     pub top_p: Option<f32>,
+    /// Deprecated and ignored; a warning is logged when it is set. Use
+    /// `transient_retries` and `server.max_attempts` instead.
     #[serde(default)]
     pub internal_retries: Option<u32>,
+    /// Retries of the same key after a transient upstream error, such as a
+    /// 503, before the next key is tried. Defaults to 2.
+    #[serde(default)]
+    pub transient_retries: Option<u32>,
+    /// Backoff between retries after transient upstream errors.
+    #[serde(default)]
+    pub retry_backoff: Option<RetryBackoffConfig>,
     #[serde(default)]
     pub temporary_block_minutes: Option<u32>,
+    #[serde(default)]
+    pub response_cache: Option<ResponseCacheConfig>,
+    #[serde(default)]
+    pub hedging: Option<HedgingConfig>,
+    #[serde(default)]
+    pub key_wait_queue: Option<KeyWaitQueueConfig>,
+    #[serde(default)]
+    pub priority: Option<PriorityConfig>,
+    #[serde(default)]
+    pub exact_token_count: Option<ExactTokenCountConfig>,
+    #[serde(default)]
+    pub pricing: Option<PricingConfig>,
+    #[serde(default)]
+    pub group_selection: Option<GroupSelectionConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
@@ -94,6 +304,7 @@ pub struct RateLimitConfig {
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+#[serde(default)]
 pub struct CircuitBreakerConfig {
     pub failure_threshold: u32,
     pub recovery_timeout_secs: u64,
@@ -110,6 +321,301 @@ impl Default for CircuitBreakerConfig {
     }
 }
 
+/// Where cached responses are stored.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    #[default]
+    Memory,
+    Redis,
+}
+
+/// Opt-in cache for successful non-streaming responses.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct ResponseCacheConfig {
+    #[serde(default)]
+    pub backend: CacheBackend,
+    #[serde(default = "default_cache_ttl")]
+    pub ttl_secs: u64,
+    /// Upper bound on entries kept by the in-memory backend (LRU eviction).
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+    /// Only cache embeddings and requests with `temperature: 0`.
+    #[serde(default = "default_true")]
+    pub deterministic_only: bool,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: CacheBackend::default(),
+            ttl_secs: default_cache_ttl(),
+            max_entries: default_cache_max_entries(),
+            deterministic_only: true,
+        }
+    }
+}
+
+/// Exponential backoff with jitter between same-key retries after transient
+/// upstream errors.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct RetryBackoffConfig {
+    /// Delay before the first retry; each further retry doubles it.
+    #[serde(default = "default_backoff_base")]
+    pub base_delay_ms: u64,
+    /// Upper bound for the delay before jitter.
+    #[serde(default = "default_backoff_max")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryBackoffConfig {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: default_backoff_base(),
+            max_delay_ms: default_backoff_max(),
+        }
+    }
+}
+
+/// Hedged requests: if a non-streaming request has no response after the
+/// hedge delay, a duplicate is sent with another key and the first success wins.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct HedgingConfig {
+    /// Fixed hedge delay. When unset, the observed p95 latency of the model is used.
+    #[serde(default)]
+    pub delay_ms: Option<u64>,
+    /// Delay used until a model has `min_samples` latency observations.
+    #[serde(default = "default_hedge_fallback_delay")]
+    pub fallback_delay_ms: u64,
+    #[serde(default = "default_hedge_min_samples")]
+    pub min_samples: usize,
+    /// Maximum share of requests that may be hedged (0.0..=1.0).
+    #[serde(default = "default_hedge_ratio")]
+    pub max_hedge_ratio: f64,
+    /// Models eligible for hedging. Empty means all models.
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            delay_ms: None,
+            fallback_delay_ms: default_hedge_fallback_delay(),
+            min_samples: default_hedge_min_samples(),
+            max_hedge_ratio: default_hedge_ratio(),
+            models: Vec::new(),
+        }
+    }
+}
+
+/// Lets requests wait for a rate-limited key to come back instead of failing
+/// immediately when every key of their group is temporarily blocked.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct KeyWaitQueueConfig {
+    /// Maximum number of requests waiting per group.
+    #[serde(default = "default_wait_queue_size")]
+    pub max_waiters_per_group: usize,
+    /// Upper bound on how long a request may wait. Clients can lower it per
+    /// request with the `X-Proxy-Max-Wait-Ms` header.
+    #[serde(default = "default_wait_queue_max_wait")]
+    pub max_wait_secs: u64,
+}
+
+impl Default for KeyWaitQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_waiters_per_group: default_wait_queue_size(),
+            max_wait_secs: default_wait_queue_max_wait(),
+        }
+    }
+}
+
+/// Exact token counting for requests near `server.max_tokens_per_request`.
+/// When the local estimate lands within `margin` of the limit, the request is
+/// counted with Gemini's `:countTokens` endpoint using a pool key instead.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct ExactTokenCountConfig {
+    /// Relative distance from the limit (0.0..=1.0) within which the local
+    /// estimate is verified, e.g. 0.1 for estimates between 90% and 110%.
+    #[serde(default = "default_exact_count_margin")]
+    pub margin: f64,
+    /// Timeout of the countTokens call; the estimate is used when it expires.
+    #[serde(default = "default_exact_count_timeout")]
+    pub timeout_ms: u64,
+    /// How long exact counts are cached by content hash.
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl_secs: u64,
+    #[serde(default = "default_cache_max_entries")]
+    pub cache_max_entries: usize,
+}
+
+impl Default for ExactTokenCountConfig {
+    fn default() -> Self {
+        Self {
+            margin: default_exact_count_margin(),
+            timeout_ms: default_exact_count_timeout(),
+            cache_ttl_secs: default_cache_ttl(),
+            cache_max_entries: default_cache_max_entries(),
+        }
+    }
+}
+
+/// Token prices used to turn recorded usage into cost.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct PricingConfig {
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Prices by model name. A name also matches the models it is a prefix
+    /// of, e.g. `gemini-2.5-pro` for `gemini-2.5-pro-preview-06-05`; the
+    /// longest match wins.
+    #[serde(default)]
+    pub models: HashMap<String, ModelPricing>,
+}
+
+impl PricingConfig {
+    pub fn for_model(&self, model: &str) -> Option<&ModelPricing> {
+        self.models
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, pricing)| pricing)
+    }
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            currency: default_currency(),
+            models: HashMap::new(),
+        }
+    }
+}
+
+/// Prices per million tokens.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default, Serialize)]
+pub struct TokenRates {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    /// Rate for prompt tokens served from a context cache; defaults to the input rate.
+    #[serde(default)]
+    pub cached_per_million: Option<f64>,
+    /// Rate for thinking tokens; defaults to the output rate.
+    #[serde(default)]
+    pub thinking_per_million: Option<f64>,
+}
+
+/// Rates that apply once a request's prompt exceeds `above_prompt_tokens`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct PriceTier {
+    pub above_prompt_tokens: u64,
+    #[serde(flatten)]
+    pub rates: TokenRates,
+}
+
+/// Prices of one model, with optional long-context tiers.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default, Serialize)]
+pub struct ModelPricing {
+    #[serde(flatten)]
+    pub rates: TokenRates,
+    #[serde(default)]
+    pub long_context: Vec<PriceTier>,
+}
+
+impl ModelPricing {
+    /// Returns the rates of the highest tier the prompt size falls into.
+    pub fn rates_for(&self, prompt_tokens: u64) -> &TokenRates {
+        self.long_context
+            .iter()
+            .filter(|tier| prompt_tokens > tier.above_prompt_tokens)
+            .max_by_key(|tier| tier.above_prompt_tokens)
+            .map_or(&self.rates, |tier| &tier.rates)
+    }
+}
+
+/// Traffic class of a request. Interactive traffic is served first; batch
+/// traffic is the first to wait or be shed when keys run short.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriorityClass {
+    #[default]
+    Interactive,
+    Batch,
+}
+
+impl PriorityClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Interactive => "interactive",
+            Self::Batch => "batch",
+        }
+    }
+}
+
+/// Clients allowed to choose their key group per request with
+/// `X-Proxy-Group` or `X-Proxy-Key-Tier`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default, Serialize)]
+pub struct GroupSelectionConfig {
+    /// Groups each `X-Client-Id` may select; `*` allows every group. The id is
+    /// not authenticated, so this is not an access control.
+    #[serde(default)]
+    pub clients: HashMap<String, Vec<String>>,
+}
+
+impl GroupSelectionConfig {
+    /// Returns whether `client` may send its requests to `group`.
+    pub fn allows(&self, client: &str, group: &str) -> bool {
+        self.clients
+            .get(client)
+            .is_some_and(|groups| groups.iter().any(|g| g == group || g == "*"))
+    }
+}
+
+/// Request priority classes, chosen per request from the client identity
+/// (`X-Client-Id`) or the `X-Proxy-Priority` header.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct PriorityConfig {
+    /// Class of requests that name neither a known client nor a priority.
+    #[serde(default)]
+    pub default_class: PriorityClass,
+    /// Fixed class per `X-Client-Id`. Takes precedence over `X-Proxy-Priority`.
+    #[serde(default)]
+    pub clients: HashMap<String, PriorityClass>,
+    /// Maximum concurrent upstream requests per class. Unset means unlimited.
+    #[serde(default)]
+    pub interactive_max_concurrent: Option<usize>,
+    #[serde(default)]
+    pub batch_max_concurrent: Option<usize>,
+    /// Available keys per group that batch requests may not take.
+    #[serde(default = "default_batch_reserved_keys")]
+    pub batch_reserved_keys: usize,
+    /// Share of the key wait queue open to batch requests (0.0..=1.0).
+    #[serde(default = "default_batch_queue_share")]
+    pub batch_queue_share: f64,
+}
+
+impl PriorityConfig {
+    pub fn max_concurrent(&self, class: PriorityClass) -> Option<usize> {
+        match class {
+            PriorityClass::Interactive => self.interactive_max_concurrent,
+            PriorityClass::Batch => self.batch_max_concurrent,
+        }
+    }
+}
+
+impl Default for PriorityConfig {
+    fn default() -> Self {
+        Self {
+            default_class: PriorityClass::default(),
+            clients: HashMap::new(),
+            interactive_max_concurrent: None,
+            batch_max_concurrent: None,
+            batch_reserved_keys: default_batch_reserved_keys(),
+            batch_queue_share: default_batch_queue_share(),
+        }
+    }
+}
+
 // Default value functions
 fn default_target_url() -> String {
     "https://generativelanguage.googleapis.com".to_string()
@@ -123,11 +629,129 @@ fn default_connect_timeout() -> u64 {
     10
 }
 
+fn default_model_catalog_refresh() -> u64 {
+    600
+}
+
+fn default_max_request_size() -> usize {
+    10 * 1024 * 1024
+}
+
 fn default_request_timeout() -> u64 {
     60
 }
 
+fn default_cache_ttl() -> u64 {
+    3600
+}
+
+fn default_cache_max_entries() -> usize {
+    10_000
+}
+
+fn default_hedge_fallback_delay() -> u64 {
+    2000
+}
+
+fn default_hedge_min_samples() -> usize {
+    20
+}
+
+fn default_hedge_ratio() -> f64 {
+    0.05
+}
+
+fn default_backoff_base() -> u64 {
+    250
+}
+
+fn default_backoff_max() -> u64 {
+    4000
+}
+
+fn default_wait_queue_size() -> usize {
+    100
+}
+
+fn default_wait_queue_max_wait() -> u64 {
+    30
+}
+
+fn default_batch_reserved_keys() -> usize {
+    1
+}
+
+fn default_batch_queue_share() -> f64 {
+    0.5
+}
+
+fn default_exact_count_margin() -> f64 {
+    0.1
+}
+
+fn default_exact_count_timeout() -> u64 {
+    3000
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
 impl AppConfig {
+    /// Returns the rules and `topP` for requests routed to `group_name`.
+    /// Requests without a group use the group named `default`, if there is one.
+    pub fn request_rules_for_group(
+        &self,
+        group_name: Option<&str>,
+    ) -> (Option<&RequestRules>, Option<f32>) {
+        let group_name = group_name.unwrap_or("default");
+        let group = self.groups.iter().find(|group| group.name == group_name);
+        let top_p = group
+            .and_then(|group| group.top_p)
+            .or(self.server.top_p)
+            .or(self.top_p);
+        (group.and_then(|group| group.request_rules.as_ref()), top_p)
+    }
+
+    /// Returns the attempt limit and deadline in seconds for requests routed
+    /// to `group_name`: the group's values, else the server's.
+    pub fn retry_limits_for_group(&self, group_name: Option<&str>) -> (Option<u32>, Option<u64>) {
+        let group = group_name.and_then(|name| self.groups.iter().find(|group| group.name == name));
+        (
+            group
+                .and_then(|group| group.max_attempts)
+                .or(self.server.max_attempts),
+            group
+                .and_then(|group| group.request_deadline_secs)
+                .or(self.server.request_deadline_secs),
+        )
+    }
+
+    /// Returns the body size limit for a request to `path` for `model`: the
+    /// limit of the model's group, else the route's, else the server's.
+    pub fn max_request_size_for(&self, path: &str, model: Option<&str>) -> usize {
+        model
+            .and_then(|model| self.get_group_for_model(model))
+            .and_then(|name| self.groups.iter().find(|group| group.name == name))
+            .and_then(|group| group.max_request_size)
+            .or_else(|| self.route_max_request_size(path))
+            .unwrap_or(self.server.max_request_size)
+    }
+
+    /// Returns the limit of the longest route prefix matching `path`.
+    pub fn route_max_request_size(&self, path: &str) -> Option<usize> {
+        self.server
+            .max_request_size_by_route
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, limit)| *limit)
+    }
+
     /// Get the group name for a given model
     pub fn get_group_for_model(&self, model: &str) -> Option<&str> {
         self.groups
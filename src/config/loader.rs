@@ -53,6 +53,19 @@ fn override_with_env(config: &mut AppConfig) {
         }
     }
 
+    // Override the request body size limit
+    if let Ok(size_str) = std::env::var("GEMINI_PROXY_MAX_REQUEST_SIZE") {
+        if let Ok(size) = size_str.parse::<usize>() {
+            info!("Overriding max request size from environment: {}", size);
+            config.server.max_request_size = size;
+        } else {
+            warn!(
+                "Invalid GEMINI_PROXY_MAX_REQUEST_SIZE environment variable: {}",
+                size_str
+            );
+        }
+    }
+
     // Override max failures threshold
     if let Ok(threshold_str) = std::env::var("MAX_FAILURES_THRESHOLD") {
         if let Ok(threshold) = threshold_str.parse::<u32>() {
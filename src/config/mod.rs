@@ -4,6 +4,11 @@ pub mod app;
 pub mod loader;
 pub mod validation;
 
-pub use app::{AppConfig, KeyGroup, ServerConfig};
+pub use app::{
+    AppConfig, CacheBackend, ExactTokenCountConfig, GroupSelectionConfig, HedgingConfig, KeyGroup,
+    KeyWaitQueueConfig, ModelPricing, ParamRule, PriceTier, PricingConfig, PriorityClass,
+    PriorityConfig, RequestRules, ResponseCacheConfig, RetryBackoffConfig, ServerConfig,
+    TokenRates,
+};
 pub use loader::{load_config, save_config, validate_config};
 pub use validation::ConfigValidator;
@@ -1,6 +1,6 @@
 // src/config/validation.rs
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, CacheBackend, ParamRule, PricingConfig, RequestRules};
 use crate::error::{AppError, Result};
 use std::collections::HashSet;
 use tracing::{debug, warn};
@@ -30,6 +30,130 @@ impl ConfigValidator {
         }
         debug!("Server config validation passed");
 
+        if let Err(e) = Self::validate_response_cache(config) {
+            warn!("Response cache validation failed: {}", e);
+            return Err(e);
+        }
+
+        if config.internal_retries.is_some() {
+            warn!(
+                "internal_retries is deprecated and ignored; use transient_retries for same-key \
+                 retries and server.max_attempts to limit attempts"
+            );
+        }
+
+        if let Some(hedging) = &config.hedging {
+            if !(0.0..=1.0).contains(&hedging.max_hedge_ratio) {
+                return Err(AppError::config_validation(
+                    format!(
+                        "hedging.max_hedge_ratio must be within 0.0..=1.0, got {}",
+                        hedging.max_hedge_ratio
+                    ),
+                    Some("hedging.max_hedge_ratio"),
+                ));
+            }
+        }
+
+        if let Some(priority) = &config.priority {
+            if !(0.0..=1.0).contains(&priority.batch_queue_share) {
+                return Err(AppError::config_validation(
+                    format!(
+                        "priority.batch_queue_share must be within 0.0..=1.0, got {}",
+                        priority.batch_queue_share
+                    ),
+                    Some("priority.batch_queue_share"),
+                ));
+            }
+            for (field, cap) in [
+                (
+                    "priority.interactive_max_concurrent",
+                    priority.interactive_max_concurrent,
+                ),
+                (
+                    "priority.batch_max_concurrent",
+                    priority.batch_max_concurrent,
+                ),
+            ] {
+                if cap == Some(0) {
+                    return Err(AppError::config_validation(
+                        format!("{field} cannot be 0"),
+                        Some(field),
+                    ));
+                }
+            }
+        }
+
+        if let Some(queue) = &config.key_wait_queue {
+            if queue.max_waiters_per_group == 0 {
+                return Err(AppError::config_validation(
+                    "key_wait_queue.max_waiters_per_group cannot be 0",
+                    Some("key_wait_queue.max_waiters_per_group"),
+                ));
+            }
+            if queue.max_wait_secs == 0 {
+                return Err(AppError::config_validation(
+                    "key_wait_queue.max_wait_secs cannot be 0",
+                    Some("key_wait_queue.max_wait_secs"),
+                ));
+            }
+        }
+
+        if let Some(exact) = &config.exact_token_count {
+            if !(0.0..=1.0).contains(&exact.margin) {
+                return Err(AppError::config_validation(
+                    format!(
+                        "exact_token_count.margin must be within 0.0..=1.0, got {}",
+                        exact.margin
+                    ),
+                    Some("exact_token_count.margin"),
+                ));
+            }
+            if exact.timeout_ms == 0 {
+                return Err(AppError::config_validation(
+                    "exact_token_count.timeout_ms cannot be 0",
+                    Some("exact_token_count.timeout_ms"),
+                ));
+            }
+        }
+
+        if let Some(pricing) = &config.pricing {
+            Self::validate_pricing(pricing)?;
+        }
+
+        if let Some(breaker) = &config.circuit_breaker {
+            if breaker.failure_threshold == 0 || breaker.half_open_max_calls == 0 {
+                return Err(AppError::config_validation(
+                    "Circuit breaker failure_threshold and half_open_max_calls must be greater than 0",
+                    Some("circuit_breaker"),
+                ));
+            }
+        }
+
+        if let Some(backoff) = &config.retry_backoff {
+            if backoff.base_delay_ms == 0 || backoff.max_delay_ms < backoff.base_delay_ms {
+                return Err(AppError::config_validation(
+                    "Retry backoff needs a non-zero base_delay_ms no larger than max_delay_ms",
+                    Some("retry_backoff"),
+                ));
+            }
+        }
+
+        if let Some(selection) = &config.group_selection {
+            for (client, groups) in &selection.clients {
+                let unknown = groups.iter().find(|name| {
+                    *name != "*" && !config.groups.iter().any(|group| &group.name == *name)
+                });
+                if let Some(name) = unknown {
+                    return Err(AppError::config_validation(
+                        format!(
+                            "group_selection for client '{client}' names unknown group '{name}'"
+                        ),
+                        Some("group_selection.clients"),
+                    ));
+                }
+            }
+        }
+
         // Validate token limit and warn on very high values
         if let Some(limit) = config.server.max_tokens_per_request {
             if !(1..=2_000_000).contains(&limit) {
@@ -114,6 +238,27 @@ impl ConfigValidator {
             if let Some(proxy_url) = &group.proxy_url {
                 Self::validate_proxy_url(&group.name, proxy_url)?;
             }
+
+            if group.max_request_size == Some(0) {
+                return Err(AppError::config_validation(
+                    format!("Group '{}': max_request_size cannot be 0", group.name),
+                    Some("group.max_request_size"),
+                ));
+            }
+
+            if group.max_attempts == Some(0) || group.request_deadline_secs == Some(0) {
+                return Err(AppError::config_validation(
+                    format!(
+                        "Group '{}': max_attempts and request_deadline_secs cannot be 0",
+                        group.name
+                    ),
+                    Some("group.max_attempts"),
+                ));
+            }
+
+            if let Some(rules) = &group.request_rules {
+                Self::validate_request_rules(&group.name, rules)?;
+            }
         }
 
         debug!(
@@ -131,6 +276,111 @@ impl ConfigValidator {
         Ok(())
     }
 
+    fn validate_response_cache(config: &AppConfig) -> Result<()> {
+        let Some(cache) = &config.response_cache else {
+            return Ok(());
+        };
+        if cache.ttl_secs == 0 {
+            return Err(AppError::config_validation(
+                "Response cache TTL cannot be 0",
+                Some("response_cache.ttl_secs"),
+            ));
+        }
+        if cache.backend == CacheBackend::Memory && cache.max_entries == 0 {
+            return Err(AppError::config_validation(
+                "Response cache max_entries cannot be 0 for the memory backend",
+                Some("response_cache.max_entries"),
+            ));
+        }
+        if cache.backend == CacheBackend::Redis && config.redis_url.is_none() {
+            return Err(AppError::config_validation(
+                "Response cache redis backend requires redis_url",
+                Some("response_cache.backend"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_request_rules(group_name: &str, rules: &RequestRules) -> Result<()> {
+        let invalid = |message: String| {
+            Err(AppError::config_validation(
+                format!("Group '{group_name}': {message}"),
+                Some("group.request_rules"),
+            ))
+        };
+        let numeric = [
+            ("temperature", &rules.temperature),
+            ("max_output_tokens", &rules.max_output_tokens),
+            ("thinking_budget", &rules.thinking_budget),
+        ];
+        for (name, rule) in numeric {
+            match rule {
+                Some(ParamRule::Set(value) | ParamRule::Default(value)) if !value.is_number() => {
+                    return invalid(format!("{name} must be a number"));
+                }
+                Some(ParamRule::Clamp { min, max }) => {
+                    if min.is_none() && max.is_none() {
+                        return invalid(format!("clamp for {name} needs min or max"));
+                    }
+                    if let (Some(min), Some(max)) = (min, max) {
+                        if min > max {
+                            return invalid(format!("clamp for {name} has min above max"));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        match &rules.safety_settings {
+            Some(ParamRule::Set(value) | ParamRule::Default(value)) if !value.is_array() => {
+                return invalid("safety_settings must be a list".to_string());
+            }
+            Some(ParamRule::Clamp { .. }) => {
+                return invalid("safety_settings cannot be clamped".to_string());
+            }
+            _ => {}
+        }
+        match &rules.system_instruction {
+            Some(ParamRule::Set(value) | ParamRule::Default(value))
+                if !value.is_string() && !value.is_object() =>
+            {
+                return invalid(
+                    "system_instruction must be a string or a Content object".to_string(),
+                );
+            }
+            Some(ParamRule::Clamp { .. }) => {
+                return invalid("system_instruction cannot be clamped".to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn validate_pricing(pricing: &PricingConfig) -> Result<()> {
+        for (model, prices) in &pricing.models {
+            let tiers = prices.long_context.iter().map(|tier| &tier.rates);
+            for rates in std::iter::once(&prices.rates).chain(tiers) {
+                let all_rates = [
+                    Some(rates.input_per_million),
+                    Some(rates.output_per_million),
+                    rates.cached_per_million,
+                    rates.thinking_per_million,
+                ];
+                if all_rates
+                    .into_iter()
+                    .flatten()
+                    .any(|rate| !rate.is_finite() || rate < 0.0)
+                {
+                    return Err(AppError::config_validation(
+                        format!("pricing for model '{model}' must use non-negative rates"),
+                        Some("pricing.models"),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn validate_server_config(config: &AppConfig) -> Result<()> {
         // Allow port 0 in test mode (system will assign a free port)
         if config.server.port == 0 && !config.server.test_mode {
@@ -154,6 +404,36 @@ impl ConfigValidator {
             ));
         }
 
+        if config.server.max_request_size == 0 {
+            return Err(AppError::config_validation(
+                "Max request size cannot be 0",
+                Some("server.max_request_size"),
+            ));
+        }
+
+        if config.server.max_attempts == Some(0) {
+            return Err(AppError::config_validation(
+                "Max attempts cannot be 0",
+                Some("server.max_attempts"),
+            ));
+        }
+
+        if config.server.request_deadline_secs == Some(0) {
+            return Err(AppError::config_validation(
+                "Request deadline cannot be 0",
+                Some("server.request_deadline_secs"),
+            ));
+        }
+
+        for (prefix, limit) in &config.server.max_request_size_by_route {
+            if !prefix.starts_with('/') || *limit == 0 {
+                return Err(AppError::config_validation(
+                    format!("Route size limit '{prefix}' needs a '/' prefix and a non-zero size"),
+                    Some("server.max_request_size_by_route"),
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -1,5 +1,6 @@
 // src/core/key_rotation.rs
 
+use crate::circuit_breaker::CircuitBreakerRegistry;
 use crate::error::Result;
 use crate::key_manager::FlattenedKeyInfo;
 use crate::storage::KeyStore;
@@ -15,12 +16,23 @@ pub trait KeyRotationStrategy: Send + Sync {
         &self,
         candidates: &[&FlattenedKeyInfo],
         group_id: &str,
+        model: Option<&str>,
         store: Arc<dyn KeyStore>,
     ) -> Result<Option<FlattenedKeyInfo>>;
 }
 
-/// Round-robin key selection strategy
-pub struct RoundRobinStrategy;
+/// Round-robin key selection strategy. Keys whose circuit breaker is open
+/// are skipped; once a breaker goes half-open, the key is picked again so
+/// that real requests probe it.
+pub struct RoundRobinStrategy {
+    circuit_breakers: Arc<CircuitBreakerRegistry>,
+}
+
+impl RoundRobinStrategy {
+    pub fn new(circuit_breakers: Arc<CircuitBreakerRegistry>) -> Self {
+        Self { circuit_breakers }
+    }
+}
 
 #[async_trait]
 impl KeyRotationStrategy for RoundRobinStrategy {
@@ -28,6 +40,7 @@ impl KeyRotationStrategy for RoundRobinStrategy {
         &self,
         candidates: &[&FlattenedKeyInfo],
         group_id: &str,
+        model: Option<&str>,
         store: Arc<dyn KeyStore>,
     ) -> Result<Option<FlattenedKeyInfo>> {
         if candidates.is_empty() {
@@ -39,18 +52,25 @@ impl KeyRotationStrategy for RoundRobinStrategy {
         for i in 0..candidates.len() {
             let key_info = candidates[(start_index + i) % candidates.len()];
 
-            match store.get_key_state(key_info.key.expose_secret()).await? {
-                Some(state) if state.is_available() => {
-                    self.log_key_selection(key_info, candidates.len());
-                    return Ok(Some((*key_info).clone()));
-                }
-                None => {
-                    // Key state not found, assume it's available
-                    self.log_key_selection(key_info, candidates.len());
-                    return Ok(Some((*key_info).clone()));
-                }
-                _ => continue,
+            let api_key = key_info.key.expose_secret();
+
+            let available = match store.get_key_state(api_key).await? {
+                Some(state) => state.is_available(),
+                // Key state not found, assume it's available
+                None => true,
+            };
+            if !available {
+                continue;
+            }
+            if !self.circuit_breakers.try_acquire(api_key, model).await {
+                trace!(
+                    api_key.preview = %crate::key_manager::KeyManager::preview_key(&key_info.key),
+                    "Skipping key with open circuit breaker"
+                );
+                continue;
             }
+            self.log_key_selection(key_info, candidates.len());
+            return Ok(Some((*key_info).clone()));
         }
 
         Ok(None)
@@ -80,17 +100,20 @@ impl KeySelector {
         Self { strategy }
     }
 
-    pub fn with_round_robin() -> Self {
-        Self::new(Box::new(RoundRobinStrategy))
+    pub fn with_round_robin(circuit_breakers: Arc<CircuitBreakerRegistry>) -> Self {
+        Self::new(Box::new(RoundRobinStrategy::new(circuit_breakers)))
     }
 
     pub async fn select_available_key(
         &self,
         candidates: &[&FlattenedKeyInfo],
         group_id: &str,
+        model: Option<&str>,
         store: Arc<dyn KeyStore>,
     ) -> Result<Option<FlattenedKeyInfo>> {
         trace!("Selecting key from {} candidates", candidates.len());
-        self.strategy.select_key(candidates, group_id, store).await
+        self.strategy
+            .select_key(candidates, group_id, model, store)
+            .await
     }
 }
@@ -15,14 +15,15 @@ pub mod types;
 pub use crate::with_error_context;
 pub use context::{set_error_context, ErrorContext};
 
+use crate::retry_budget::ATTEMPTS_HEADER;
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{error, error_span, warn_span};
+use tracing::{error_span, warn_span};
 use uuid::Uuid;
 
 /// Standard error response format following RFC 7807 Problem Details
@@ -94,6 +95,9 @@ pub enum AppError {
     #[error("Request timeout after {timeout_secs}s")]
     RequestTimeout { timeout_secs: u64 },
 
+    #[error("Request deadline exceeded after {attempts} upstream attempts")]
+    DeadlineExceeded { attempts: u32 },
+
     #[error("Invalid request: {message}")]
     InvalidRequest { message: String },
 
@@ -117,10 +121,19 @@ pub enum AppError {
     #[error("Circuit breaker open for service: {service}")]
     CircuitBreakerOpen { service: String },
 
+    #[error("{class} request shed under load; retry after {retry_after_secs}s")]
+    RequestShed {
+        class: String,
+        retry_after_secs: u64,
+    },
+
     // Key management
     #[error("No healthy API keys available")]
     NoHealthyKeys,
 
+    #[error("All API keys are temporarily blocked; retry after {retry_after_secs}s")]
+    KeysExhausted { retry_after_secs: u64 },
+
     #[error("Key rotation failed: {message}")]
     KeyRotation { message: String },
 
@@ -134,6 +147,9 @@ pub enum AppError {
     #[error("Request body too large: {size} tokens (max: {max_size})")]
     RequestTooLarge { size: usize, max_size: usize },
 
+    #[error("Request body exceeds the limit of {max_size} bytes")]
+    PayloadTooLarge { max_size: usize },
+
     // System errors
     #[error("Internal server error: {message}")]
     Internal { message: String },
@@ -186,6 +202,9 @@ impl AppError {
             | Self::RequestTooLarge { .. }
             | Self::Serialization { .. } => StatusCode::BAD_REQUEST,
 
+            // 413 Payload Too Large
+            Self::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+
             // 401 Unauthorized
             Self::Authentication { .. } | Self::InvalidApiKey { .. } => StatusCode::FORBIDDEN,
 
@@ -216,9 +235,12 @@ impl AppError {
             Self::ServiceUnavailable { .. }
             | Self::CircuitBreakerOpen { .. }
             | Self::NoHealthyKeys
+            | Self::KeysExhausted { .. }
+            | Self::RequestShed { .. }
             | Self::RedisConnection { .. } => StatusCode::SERVICE_UNAVAILABLE,
 
             // 504 Gateway Timeout
+            Self::DeadlineExceeded { .. } => StatusCode::GATEWAY_TIMEOUT,
             Self::RedisOperation { .. }
             | Self::StoragePersistence { .. }
             | Self::KeyRotation { .. }
@@ -237,20 +259,23 @@ impl AppError {
             | Self::StoragePersistence { .. } => "https://gemini-proxy.dev/errors/storage",
             Self::HttpClient { .. }
             | Self::UpstreamUnavailable { .. }
-            | Self::RequestTimeout { .. } => "https://gemini-proxy.dev/errors/network",
+            | Self::RequestTimeout { .. }
+            | Self::DeadlineExceeded { .. } => "https://gemini-proxy.dev/errors/network",
             Self::Authentication { .. } | Self::Authorization | Self::InvalidApiKey { .. } => {
                 "https://gemini-proxy.dev/errors/authentication"
             }
-            Self::RateLimit { .. } | Self::ApiKeyQuotaExceeded { .. } => {
-                "https://gemini-proxy.dev/errors/rate-limit"
-            }
+            Self::RateLimit { .. }
+            | Self::ApiKeyQuotaExceeded { .. }
+            | Self::RequestShed { .. } => "https://gemini-proxy.dev/errors/rate-limit",
             Self::CircuitBreakerOpen { .. } => "https://gemini-proxy.dev/errors/circuit-breaker",
-            Self::NoHealthyKeys | Self::KeyRotation { .. } | Self::KeyHealthCheck { .. } => {
-                "https://gemini-proxy.dev/errors/key-management"
-            }
+            Self::NoHealthyKeys
+            | Self::KeysExhausted { .. }
+            | Self::KeyRotation { .. }
+            | Self::KeyHealthCheck { .. } => "https://gemini-proxy.dev/errors/key-management",
             Self::Validation { .. }
             | Self::InvalidRequest { .. }
-            | Self::RequestTooLarge { .. } => "https://gemini-proxy.dev/errors/validation",
+            | Self::RequestTooLarge { .. }
+            | Self::PayloadTooLarge { .. } => "https://gemini-proxy.dev/errors/validation",
             _ => "https://gemini-proxy.dev/errors/internal",
         }
     }
@@ -266,18 +291,23 @@ impl AppError {
             | Self::StoragePersistence { .. } => "Storage Error",
             Self::HttpClient { .. }
             | Self::UpstreamUnavailable { .. }
-            | Self::RequestTimeout { .. } => "Network Error",
+            | Self::RequestTimeout { .. }
+            | Self::DeadlineExceeded { .. } => "Network Error",
             Self::Authentication { .. } | Self::Authorization | Self::InvalidApiKey { .. } => {
                 "Authentication Error"
             }
-            Self::RateLimit { .. } | Self::ApiKeyQuotaExceeded { .. } => "Rate Limit Exceeded",
+            Self::RateLimit { .. }
+            | Self::ApiKeyQuotaExceeded { .. }
+            | Self::RequestShed { .. } => "Rate Limit Exceeded",
             Self::CircuitBreakerOpen { .. } => "Circuit Breaker Open",
-            Self::NoHealthyKeys | Self::KeyRotation { .. } | Self::KeyHealthCheck { .. } => {
-                "Key Management Error"
-            }
+            Self::NoHealthyKeys
+            | Self::KeysExhausted { .. }
+            | Self::KeyRotation { .. }
+            | Self::KeyHealthCheck { .. } => "Key Management Error",
             Self::Validation { .. }
             | Self::InvalidRequest { .. }
-            | Self::RequestTooLarge { .. } => "Validation Error",
+            | Self::RequestTooLarge { .. }
+            | Self::PayloadTooLarge { .. } => "Validation Error",
             _ => "Internal Server Error",
         }
     }
@@ -340,7 +370,22 @@ impl IntoResponse for AppError {
             extensions: serde_json::Map::new(),
         };
 
-        (status, Json(error_response)).into_response()
+        let mut response = (status, Json(error_response)).into_response();
+        if let Self::KeysExhausted { retry_after_secs }
+        | Self::RequestShed {
+            retry_after_secs, ..
+        } = self
+        {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+        if let Self::DeadlineExceeded { attempts } = self {
+            response
+                .headers_mut()
+                .insert(ATTEMPTS_HEADER, HeaderValue::from(attempts));
+        }
+        response
     }
 }
 
@@ -0,0 +1,168 @@
+// src/group_selection.rs
+
+//! Client-selected key groups.
+//!
+//! A request normally uses the group its model is aliased to. A client listed
+//! in `group_selection.clients` may instead name a group with `X-Proxy-Group`,
+//! or a key tier with `X-Proxy-Key-Tier`, to run the same model against a
+//! separate key pool. Clients are identified by `X-Client-Id` and may only
+//! select the groups listed for them. The id is self-declared, so the list
+//! keeps trusted callers apart and is not an access control.
+
+use crate::config::{AppConfig, KeyGroup};
+use crate::error::{AppError, Result};
+use crate::priority::CLIENT_ID_HEADER;
+use axum::http::HeaderMap;
+use tracing::warn;
+
+/// Request header naming the key group to use.
+pub const GROUP_HEADER: &str = "x-proxy-group";
+
+/// Request header naming the key tier to use. Ignored with `X-Proxy-Group`.
+pub const TIER_HEADER: &str = "x-proxy-key-tier";
+
+/// Returns the group for a request for `model`: the group selected by the
+/// request headers, if any, else the model's group.
+///
+/// A tier resolves to the model's group if it is in the tier, else to the
+/// first group of the tier the client may use.
+pub fn resolve(
+    config: &AppConfig,
+    model: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Option<String>> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    let model_group = model.and_then(|model| config.get_group_for_model(model));
+    let (group, tier) = (header(GROUP_HEADER), header(TIER_HEADER));
+
+    let client = header(CLIENT_ID_HEADER).unwrap_or_default();
+    let allows = |group: &KeyGroup| {
+        config
+            .group_selection
+            .as_ref()
+            .is_some_and(|selection| selection.allows(client, &group.name))
+    };
+    let selected = if let Some(name) = group {
+        let group = config
+            .groups
+            .iter()
+            .find(|group| group.name == name)
+            .ok_or_else(|| AppError::InvalidRequest {
+                message: format!("Unknown key group '{name}'"),
+            })?;
+        allows(group).then_some(group)
+    } else if let Some(tier) = tier {
+        let in_tier: Vec<&KeyGroup> = config
+            .groups
+            .iter()
+            .filter(|group| group.tier.as_deref() == Some(tier))
+            .collect();
+        if in_tier.is_empty() {
+            return Err(AppError::InvalidRequest {
+                message: format!("Unknown key tier '{tier}'"),
+            });
+        }
+        let allowed: Vec<&KeyGroup> = in_tier.into_iter().filter(|g| allows(g)).collect();
+        allowed
+            .iter()
+            .find(|group| Some(group.name.as_str()) == model_group)
+            .or(allowed.first())
+            .copied()
+    } else {
+        return Ok(model_group.map(str::to_owned));
+    };
+
+    match selected {
+        Some(group) => Ok(Some(group.name.clone())),
+        None => {
+            warn!(
+                client,
+                group = group.unwrap_or_default(),
+                tier = tier.unwrap_or_default(),
+                "Client may not select the requested key group"
+            );
+            Err(AppError::Authorization)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GroupSelectionConfig;
+    use std::collections::HashMap;
+
+    fn config() -> AppConfig {
+        let group = |name: &str, tier: Option<&str>| KeyGroup {
+            name: name.to_string(),
+            model_aliases: if name == "default" {
+                vec!["gemini-2.5-flash".to_string()]
+            } else {
+                Vec::new()
+            },
+            tier: tier.map(str::to_owned),
+            ..Default::default()
+        };
+        AppConfig {
+            groups: vec![
+                group("default", Some("free")),
+                group("paid-a", Some("paid")),
+                group("paid-b", Some("paid")),
+            ],
+            group_selection: Some(GroupSelectionConfig {
+                clients: HashMap::from([
+                    ("harness".to_string(), vec!["*".to_string()]),
+                    ("team-b".to_string(), vec!["paid-b".to_string()]),
+                ]),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_headers_override_the_model_group_for_allowed_clients() {
+        let config = config();
+        let model = Some("gemini-2.5-flash");
+        let resolve = |pairs| resolve(&config, model, &headers(pairs));
+
+        assert_eq!(resolve(&[]).unwrap().as_deref(), Some("default"));
+        assert_eq!(
+            resolve(&[(CLIENT_ID_HEADER, "harness"), (GROUP_HEADER, "paid-a")])
+                .unwrap()
+                .as_deref(),
+            Some("paid-a")
+        );
+        assert_eq!(
+            resolve(&[(CLIENT_ID_HEADER, "team-b"), (TIER_HEADER, "paid")])
+                .unwrap()
+                .as_deref(),
+            Some("paid-b")
+        );
+        assert!(matches!(
+            resolve(&[(CLIENT_ID_HEADER, "team-b"), (GROUP_HEADER, "paid-a")]),
+            Err(AppError::Authorization)
+        ));
+        assert!(matches!(
+            resolve(&[(GROUP_HEADER, "paid-a")]),
+            Err(AppError::Authorization)
+        ));
+        assert!(matches!(
+            resolve(&[(CLIENT_ID_HEADER, "harness"), (TIER_HEADER, "gold")]),
+            Err(AppError::InvalidRequest { .. })
+        ));
+    }
+}
@@ -0,0 +1,418 @@
+// src/handlers/affinity.rs
+
+//! Key affinity for upstream resources.
+//!
+//! Batch jobs, files, cached contents, tuned models and long-running operations
+//! belong to the project behind the key that created them, so every later call
+//! on them must use that same key. Owners are recorded in the key store when a
+//! successful create (or get) response names such a resource, and requests that
+//! reference a known resource in their path or body are pinned to its owner.
+
+use super::{proxy_loop, RequestContext};
+use crate::{
+    error::{AppError, Result},
+    key_manager::{FlattenedKeyInfo, KeyManager},
+    state::AppState,
+};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Uri},
+    response::Response,
+};
+use secrecy::ExposeSecret;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Resource collections whose members are owned by the creating key.
+const OWNED_COLLECTIONS: [&str; 5] = [
+    "batches",
+    "files",
+    "cachedContents",
+    "tunedModels",
+    "operations",
+];
+
+/// Methods that start a long-running operation owned by the calling key.
+const CREATE_METHODS: [&str; 3] = [
+    ":batchGenerateContent",
+    ":asyncBatchEmbedContent",
+    ":predictLongRunning",
+];
+
+/// Response fields that name a resource created by the request.
+const RESOURCE_FIELDS: [&str; 3] = ["name", "responsesFile", "tunedModel"];
+
+/// Request body fields that may reference an owned resource.
+const REFERENCE_FIELDS: [&str; 6] = [
+    "cachedContent",
+    "cached_content",
+    "fileUri",
+    "file_uri",
+    "model",
+    "name",
+];
+
+/// Returns the owned resource a path or resource name refers to, e.g.
+/// `batches/123` for `/v1beta/batches/123:cancel` or `operations/abc` for
+/// `models/veo-2.0/operations/abc`.
+pub(crate) fn resource_from_path(path: &str) -> Option<String> {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    segments.windows(2).find_map(|pair| {
+        let id = pair[1].split([':', '?']).next().unwrap_or_default();
+        (OWNED_COLLECTIONS.contains(&pair[0]) && !id.is_empty())
+            .then(|| format!("{}/{id}", pair[0]))
+    })
+}
+
+/// Returns whether the request creates an owned resource. Such requests must
+/// not be coalesced, since every caller expects a resource of its own.
+pub(crate) fn creates_resource(method: &Method, path: &str) -> bool {
+    if method != Method::POST {
+        return false;
+    }
+    let path = path.trim_end_matches('/');
+    CREATE_METHODS.iter().any(|m| path.ends_with(m))
+        || OWNED_COLLECTIONS
+            .iter()
+            .any(|collection| path.ends_with(&format!("/{collection}")))
+}
+
+/// Returns whether the request lists batch jobs.
+pub(crate) fn is_batch_list(method: &Method, path: &str) -> bool {
+    method == Method::GET && path.trim_end_matches('/').ends_with("/batches")
+}
+
+/// Collects the owned resources referenced by a request body, e.g.
+/// `cachedContent: "cachedContents/abc"` or a `fileUri` pointing at `files/xyz`.
+fn referenced_resources(body: &Value, found: &mut Vec<String>) {
+    match body {
+        Value::Object(map) => {
+            for (field, value) in map {
+                if let (Some(name), true) =
+                    (value.as_str(), REFERENCE_FIELDS.contains(&field.as_str()))
+                {
+                    found.extend(resource_from_path(name));
+                }
+                referenced_resources(value, found);
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| referenced_resources(item, found)),
+        _ => {}
+    }
+}
+
+/// Returns the key that owns the resource named by the request path or, failing
+/// that, referenced in the request body, if known and still configured.
+pub(crate) async fn pinned_key(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+) -> Result<Option<FlattenedKeyInfo>> {
+    let mut resources: Vec<String> = resource_from_path(req_context.uri.path())
+        .into_iter()
+        .collect();
+    let body = req_context.body;
+    let mentions_collection = OWNED_COLLECTIONS.iter().any(|collection| {
+        let needle = format!("{collection}/");
+        body.windows(needle.len()).any(|w| w == needle.as_bytes())
+    });
+    if resources.is_empty() && mentions_collection {
+        if let Some(json) = req_context.json {
+            referenced_resources(json, &mut resources);
+        }
+    }
+    if resources.is_empty() {
+        return Ok(None);
+    }
+
+    let key_manager = state.key_manager.read().await;
+    for resource in resources {
+        let Some(owner) = key_manager.get_resource_owner(&resource).await? else {
+            continue;
+        };
+        let key_info = key_manager.get_all_key_info().await.remove(&owner);
+        if key_info.is_none() {
+            warn!(
+                resource,
+                key.preview = %KeyManager::preview_key_str(&owner),
+                "Resource owner is no longer configured; using normal key rotation"
+            );
+        }
+        return Ok(key_info);
+    }
+    Ok(None)
+}
+
+/// Collects the owned resources named in a response body: the resource itself
+/// (`name`), a created tuned model and any batch result files.
+fn owned_resources(body: &Value, found: &mut BTreeSet<String>) {
+    match body {
+        Value::Object(map) => {
+            for (field, value) in map {
+                if let (Some(name), true) =
+                    (value.as_str(), RESOURCE_FIELDS.contains(&field.as_str()))
+                {
+                    found.extend(resource_from_path(name));
+                }
+                owned_resources(value, found);
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| owned_resources(item, found)),
+        _ => {}
+    }
+}
+
+/// Records `key_info` as the owner of the resources named in a successful
+/// response to a create or get request, and forgets deleted ones.
+pub(crate) async fn record_owners(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    key_info: &FlattenedKeyInfo,
+    response: Response,
+) -> Result<Response> {
+    let path = req_context.uri.path();
+    let path_resource = resource_from_path(path);
+    let is_lookup = path_resource.is_some()
+        && (req_context.method == Method::GET || req_context.method == Method::DELETE);
+    if !response.status().is_success() || !(creates_resource(req_context.method, path) || is_lookup)
+    {
+        return Ok(response);
+    }
+
+    if req_context.method == Method::DELETE {
+        if let Some(resource) = path_resource {
+            info!(resource, "Forgetting owner of deleted resource");
+            state
+                .key_manager
+                .read()
+                .await
+                .remove_resource_owner(&resource)
+                .await?;
+        }
+        return Ok(response);
+    }
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("json"));
+    if !is_json {
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to read response: {e}")))?;
+    let mut resources = BTreeSet::new();
+    if let Ok(json) = serde_json::from_slice::<Value>(&body) {
+        owned_resources(&json, &mut resources);
+    }
+
+    if !resources.is_empty() {
+        let key_manager = state.key_manager.read().await;
+        for resource in &resources {
+            key_manager
+                .set_resource_owner(resource, key_info.key.expose_secret())
+                .await?;
+        }
+        info!(
+            ?resources,
+            key.preview = %KeyManager::preview_key(&key_info.key),
+            "Recorded resource owner"
+        );
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Lists batch jobs across every key that has created one through the proxy.
+/// Returns `None` when no batch owner is known, so the request is proxied as
+/// usual. With several owner keys the `operations` of each are merged into one
+/// page; a `pageToken` cannot name a position in that merge and is rejected.
+pub(crate) async fn list_batches(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+) -> Result<Option<Response>> {
+    let owner_keys: Vec<FlattenedKeyInfo> = {
+        let key_manager = state.key_manager.read().await;
+        let key_info = key_manager.get_all_key_info().await;
+        let owners: BTreeSet<String> = key_manager
+            .get_resource_owners()
+            .await?
+            .into_iter()
+            .filter(|(resource, _)| resource.starts_with("batches/"))
+            .map(|(_, owner)| owner)
+            .collect();
+        owners
+            .iter()
+            .filter_map(|owner| key_info.get(owner).cloned())
+            .collect()
+    };
+
+    match owner_keys.as_slice() {
+        [] => Ok(None),
+        [key_info] => proxy_loop::try_request_with_key(state, req_context, key_info)
+            .await
+            .map(Some),
+        _ if has_page_token(req_context.uri) => Err(AppError::InvalidRequest {
+            message: "pageToken is not supported when batches span several keys".to_string(),
+        }),
+        _ => merge_listings(state, req_context, &owner_keys)
+            .await
+            .map(Some),
+    }
+}
+
+fn has_page_token(uri: &Uri) -> bool {
+    uri.query().is_some_and(|query| {
+        url::form_urlencoded::parse(query.as_bytes()).any(|(name, _)| name == "pageToken")
+    })
+}
+
+async fn merge_listings(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    owner_keys: &[FlattenedKeyInfo],
+) -> Result<Response> {
+    let mut merged: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut first_failure = None;
+    let mut first_error = None;
+
+    // A key that fails (revoked, rate limited, unreachable) is skipped so the
+    // other keys' batches are still listed.
+    for key_info in owner_keys {
+        let response = match proxy_loop::try_request_with_key(state, req_context, key_info).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(
+                    error = %e,
+                    key.preview = %KeyManager::preview_key(&key_info.key),
+                    "Listing batches failed for key"
+                );
+                first_error.get_or_insert(e);
+                continue;
+            }
+        };
+        if !response.status().is_success() {
+            warn!(
+                status = response.status().as_u16(),
+                key.preview = %KeyManager::preview_key(&key_info.key),
+                "Listing batches failed for key"
+            );
+            first_failure.get_or_insert(response);
+            continue;
+        }
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .map_err(|e| AppError::internal(format!("Failed to read response: {e}")))?;
+        let Ok(Value::Object(listing)) = serde_json::from_slice::<Value>(&body) else {
+            continue;
+        };
+        for (field, value) in listing {
+            match value {
+                Value::Array(items) => merged.entry(field).or_default().extend(items),
+                _ => debug!(field, "Skipping non-array field when merging listings"),
+            }
+        }
+    }
+
+    if merged.is_empty() {
+        if let Some(response) = first_failure {
+            return Ok(response);
+        }
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+    }
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            Value::Object(
+                merged
+                    .into_iter()
+                    .map(|(field, items)| (field, Value::Array(items)))
+                    .collect(),
+            )
+            .to_string(),
+        ))
+        .map_err(|e| AppError::internal(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resource_from_path() {
+        assert_eq!(
+            resource_from_path("/v1beta/batches/abc:cancel").as_deref(),
+            Some("batches/abc")
+        );
+        assert_eq!(
+            resource_from_path("/download/v1beta/files/batch-1:download").as_deref(),
+            Some("files/batch-1")
+        );
+        assert_eq!(
+            resource_from_path("models/veo-2.0-generate-001/operations/abc").as_deref(),
+            Some("operations/abc")
+        );
+        assert_eq!(
+            resource_from_path("tunedModels/my-model/operations/op-1").as_deref(),
+            Some("tunedModels/my-model")
+        );
+        assert_eq!(resource_from_path("/v1beta/batches"), None);
+        assert_eq!(
+            resource_from_path("/v1beta/models/gemini-2.0-flash:batchGenerateContent"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_owned_resources_include_result_files() {
+        let operation = json!({
+            "name": "batches/123",
+            "metadata": {"model": "models/gemini-2.0-flash"},
+            "response": {"responsesFile": "files/batch-123"},
+        });
+        let mut found = BTreeSet::new();
+        owned_resources(&operation, &mut found);
+        assert_eq!(
+            found.into_iter().collect::<Vec<_>>(),
+            vec!["batches/123", "files/batch-123"]
+        );
+    }
+
+    #[test]
+    fn test_referenced_resources_in_request_body() {
+        let request = json!({
+            "cachedContent": "cachedContents/ctx-1",
+            "contents": [{"parts": [
+                {"text": "see files/not-a-reference"},
+                {"fileData": {"fileUri": "https://generativelanguage.googleapis.com/v1beta/files/f-9"}},
+            ]}],
+        });
+        let mut found = Vec::new();
+        referenced_resources(&request, &mut found);
+        assert_eq!(found, vec!["cachedContents/ctx-1", "files/f-9"]);
+    }
+
+    #[test]
+    fn test_creates_resource() {
+        assert!(creates_resource(&Method::POST, "/v1beta/cachedContents"));
+        assert!(creates_resource(&Method::POST, "/v1beta/tunedModels/"));
+        assert!(creates_resource(
+            &Method::POST,
+            "/v1beta/models/veo-2.0-generate-001:predictLongRunning"
+        ));
+        assert!(!creates_resource(&Method::GET, "/v1beta/cachedContents"));
+        assert!(!creates_resource(
+            &Method::POST,
+            "/v1beta/models/gemini-2.0-flash:generateContent"
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+// src/handlers/anthropic.rs
+
+//! Serves the Anthropic Messages API (`/v1/messages`) on top of Gemini
+//! `generateContent`.
+
+use super::proxy_gemini_request;
+use crate::{
+    error::{AppError, Result},
+    state::AppState,
+    translation::{anthropic, translate_event_stream},
+};
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue},
+    response::Response,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{instrument, warn};
+
+/// Client headers that only make sense to Anthropic and must not reach Google.
+const ANTHROPIC_HEADERS: [&str; 3] = ["x-api-key", "anthropic-version", "anthropic-beta"];
+
+/// Handles `POST /v1/messages`.
+#[instrument(skip_all)]
+pub async fn messages_handler(
+    State(state): State<Arc<AppState>>,
+    mut headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let request: Value = serde_json::from_slice(&body).map_err(|e| AppError::InvalidRequest {
+        message: format!("Invalid messages body: {e}"),
+    })?;
+    let gemini_request = anthropic::messages_request_to_gemini(&request)?;
+
+    for name in ANTHROPIC_HEADERS {
+        headers.remove(name);
+    }
+
+    let response = proxy_gemini_request(&state, &headers, &gemini_request).await?;
+    let (mut parts, upstream_body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_ENCODING);
+
+    if !parts.status.is_success() {
+        let bytes = to_bytes(upstream_body, usize::MAX)
+            .await
+            .map_err(|e| AppError::internal(e.to_string()))?;
+        let error = anthropic::error_to_anthropic(parts.status, &bytes);
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        return Ok(Response::from_parts(
+            parts,
+            Body::from(serde_json::to_vec(&error)?),
+        ));
+    }
+
+    if gemini_request.stream {
+        let translator = anthropic::MessagesStreamTranslator::new(gemini_request.model.clone());
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        let body = translate_event_stream(upstream_body, translator);
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    let bytes = to_bytes(upstream_body, usize::MAX)
+        .await
+        .map_err(|e| AppError::internal(e.to_string()))?;
+    let gemini_response: Value = serde_json::from_slice(&bytes).map_err(|e| {
+        warn!(error = %e, "Upstream returned a non-JSON generateContent response");
+        AppError::internal(format!("Invalid upstream response: {e}"))
+    })?;
+
+    let message = anthropic::gemini_response_to_message(&gemini_response, &gemini_request.model);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Ok(Response::from_parts(
+        parts,
+        Body::from(serde_json::to_vec(&message)?),
+    ))
+}
@@ -8,6 +8,9 @@ use std::time::Duration;
 pub enum Action {
     /// The key is valid, but the quota is exhausted. Try the next key.
     RetryNextKey,
+    /// A transient upstream error that says nothing about the key, such as an
+    /// overloaded model. Retry the same key after a backoff, then the next key.
+    RetryTransient,
     /// The key is invalid and should be permanently blocked. Then, try the next key.
     BlockKeyAndRetry,
     /// The response is final and should be returned to the client immediately.
@@ -22,6 +25,7 @@ impl PartialEq for Action {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Action::RetryNextKey, Action::RetryNextKey) => true,
+            (Action::RetryTransient, Action::RetryTransient) => true,
             (Action::BlockKeyAndRetry, Action::BlockKeyAndRetry) => true,
             (Action::WaitFor(d1), Action::WaitFor(d2)) => d1 == d2,
             // For responses, we can't directly compare them.
@@ -0,0 +1,388 @@
+// src/handlers/live.rs
+
+//! WebSocket sessions of the Gemini Live API (`BidiGenerateContent`).
+//!
+//! The client's first frame is the `setup` message naming the model. It
+//! decides the key group, the session is opened upstream with the group's
+//! next key (rotating on a rejected handshake, like a rejected request) and
+//! the setup message is replayed on it. Frames are then relayed both ways
+//! until either side closes. An upstream failure closes the client socket
+//! with `1011` instead of dropping it.
+
+use super::{base::Action, proxy_loop};
+use crate::{
+    error::{AppError, Result},
+    group_selection,
+    key_manager::{FlattenedKeyInfo, KeyManager},
+    state::AppState,
+};
+use axum::{
+    body::Body,
+    extract::{
+        ws::{self, CloseFrame, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::{HeaderMap, Uri},
+    response::Response,
+};
+use futures_util::{SinkExt, StreamExt};
+use secrecy::ExposeSecret;
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{self, protocol::frame::coding::CloseCode},
+    MaybeTlsStream, WebSocketStream,
+};
+use tracing::{info, instrument, warn};
+use url::Url;
+
+type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long a client may take to send its `setup` message.
+const SETUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Close codes sent to the client.
+const CLOSE_POLICY: u16 = 1008;
+const CLOSE_ERROR: u16 = 1011;
+const CLOSE_TRY_AGAIN: u16 = 1013;
+
+/// Upgrades the connection and runs the Live session on it.
+pub async fn live_handler(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| run_session(state, uri, headers, socket))
+}
+
+#[instrument(skip_all, fields(uri = %uri.path()))]
+async fn run_session(state: Arc<AppState>, uri: Uri, headers: HeaderMap, mut client: WebSocket) {
+    let setup = match tokio::time::timeout(SETUP_TIMEOUT, client.recv()).await {
+        Ok(Some(Ok(message))) => message,
+        Ok(_) => return,
+        Err(_) => {
+            close(&mut client, CLOSE_POLICY, "No setup message received").await;
+            return;
+        }
+    };
+    let Some(model) = setup_model(&setup) else {
+        close(
+            &mut client,
+            CLOSE_POLICY,
+            "First message must be a setup message",
+        )
+        .await;
+        return;
+    };
+
+    let (upstream, key_info) = match connect_upstream(&state, &uri, &headers, &model).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            warn!(error = %e, model, "Could not open Live session upstream");
+            let code = match e {
+                AppError::NoHealthyKeys => CLOSE_TRY_AGAIN,
+                _ => CLOSE_ERROR,
+            };
+            close(&mut client, code, &e.to_string()).await;
+            return;
+        }
+    };
+    info!(
+        model,
+        key.preview = %KeyManager::preview_key(&key_info.key),
+        "Live session opened"
+    );
+
+    let started = Instant::now();
+    let session = relay(client, upstream, setup).await;
+    let duration = started.elapsed();
+    info!(
+        model,
+        key.preview = %KeyManager::preview_key(&key_info.key),
+        outcome = session.outcome,
+        duration_ms = duration.as_millis() as u64,
+        bytes_from_client = session.bytes_from_client,
+        bytes_from_upstream = session.bytes_from_upstream,
+        "Live session closed"
+    );
+    state.metrics.record_live_session(
+        model,
+        session.outcome,
+        duration,
+        session.bytes_from_client,
+        session.bytes_from_upstream,
+    );
+}
+
+/// Returns the model of a `setup` message, without its `models/` prefix.
+fn setup_model(message: &ws::Message) -> Option<String> {
+    let setup: Value = match message {
+        ws::Message::Text(text) => serde_json::from_str(text).ok()?,
+        ws::Message::Binary(bytes) => serde_json::from_slice(bytes).ok()?,
+        _ => return None,
+    };
+    let model = setup.get("setup")?.get("model")?.as_str()?;
+    Some(model.strip_prefix("models/").unwrap_or(model).to_string())
+}
+
+/// Returns the upstream socket URL for a request to `uri` with `key_info`.
+fn upstream_url(uri: &Uri, key_info: &FlattenedKeyInfo) -> Result<Url> {
+    let mut url = super::build_target_url(uri, key_info)?;
+    let scheme = if url.scheme() == "http" { "ws" } else { "wss" };
+    url.set_scheme(scheme)
+        .map_err(|()| AppError::internal(format!("Cannot use {scheme} for the target URL")))?;
+    Ok(url)
+}
+
+/// Opens the upstream socket with the next key of the request's group, moving
+/// on to the next key when the handshake is rejected for the key. The
+/// handshake is bounded by `server.request_timeout_secs`, and its outcome is
+/// recorded on the key's breakers.
+async fn connect_upstream(
+    state: &Arc<AppState>,
+    uri: &Uri,
+    headers: &HeaderMap,
+    model: &str,
+) -> Result<(UpstreamSocket, FlattenedKeyInfo)> {
+    let (group_name, handshake_timeout) = {
+        let config = state.config.read().await;
+        (
+            group_selection::resolve(&config, Some(model), headers)?,
+            Duration::from_secs(config.server.request_timeout_secs),
+        )
+    };
+    loop {
+        let key_info = state
+            .key_manager
+            .read()
+            .await
+            .get_next_available_key_info_for_model(group_name.as_deref(), Some(model))
+            .await?
+            .ok_or(AppError::NoHealthyKeys)?;
+        if key_info.proxy_url.is_some() {
+            state
+                .circuit_breakers
+                .release(key_info.key.expose_secret(), Some(model))
+                .await;
+            return Err(AppError::internal(
+                "Live sessions cannot be opened through a group proxy_url",
+            ));
+        }
+
+        let url = upstream_url(uri, &key_info)?;
+        let key = key_info.key.expose_secret();
+        let handshake = tokio::time::timeout(
+            handshake_timeout,
+            tokio_tungstenite::connect_async(url.as_str()),
+        );
+        let response = match handshake.await {
+            Ok(Ok((socket, _))) => {
+                state
+                    .circuit_breakers
+                    .record_success(key, Some(model))
+                    .await;
+                return Ok((socket, key_info));
+            }
+            Ok(Err(tungstenite::Error::Http(response))) => response,
+            Ok(Err(e)) => {
+                state
+                    .circuit_breakers
+                    .record_failure(key, Some(model))
+                    .await;
+                return Err(AppError::UpstreamUnavailable {
+                    service: format!("Live API: {e}"),
+                });
+            }
+            Err(_) => {
+                state
+                    .circuit_breakers
+                    .record_failure(key, Some(model))
+                    .await;
+                return Err(AppError::UpstreamUnavailable {
+                    service: format!("Live API handshake timed out after {handshake_timeout:?}"),
+                });
+            }
+        };
+
+        let status = response.status();
+        let (parts, body) = response.into_parts();
+        let response = Response::from_parts(parts, Body::from(body.unwrap_or_default()));
+        let (action, _) = state
+            .response_processor
+            .process(response, &key_info)
+            .await?;
+        proxy_loop::record_breaker_outcome(state, &key_info, model, &action).await;
+        match action {
+            action @ (Action::RetryNextKey | Action::BlockKeyAndRetry | Action::WaitFor(_)) => {
+                warn!(
+                    status = status.as_u16(),
+                    key.preview = %KeyManager::preview_key(&key_info.key),
+                    "Live handshake rejected; trying next key"
+                );
+                proxy_loop::mark_key(state, &key_info, action).await?;
+            }
+            // Transient errors do not block the key, so trying the next key
+            // could come back to it; let the client retry instead.
+            Action::RetryTransient | Action::ReturnToClient(_) | Action::Terminal(_) => {
+                return Err(AppError::UpstreamUnavailable {
+                    service: format!("Live API handshake returned {status}"),
+                });
+            }
+        }
+    }
+}
+
+/// Totals of one relayed session.
+struct Session {
+    outcome: &'static str,
+    bytes_from_client: u64,
+    bytes_from_upstream: u64,
+}
+
+/// Relays frames between the two sockets, starting with the client's setup
+/// message, until one side closes or fails.
+async fn relay(client: WebSocket, upstream: UpstreamSocket, setup: ws::Message) -> Session {
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+    let mut session = Session {
+        outcome: "client_closed",
+        bytes_from_client: frame_len(&setup),
+        bytes_from_upstream: 0,
+    };
+    if let Some(setup) = to_upstream(setup) {
+        if upstream_tx.send(setup).await.is_err() {
+            session.outcome = "upstream_error";
+            let _ = client_tx
+                .send(close_message(CLOSE_ERROR, "Upstream failed"))
+                .await;
+            return session;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            message = client_rx.next() => match message {
+                Some(Ok(ws::Message::Close(frame))) => {
+                    let frame = frame.map(|frame| tungstenite::protocol::CloseFrame {
+                        code: CloseCode::from(frame.code),
+                        reason: frame.reason,
+                    });
+                    let _ = upstream_tx.send(tungstenite::Message::Close(frame)).await;
+                    break;
+                }
+                Some(Ok(message)) => {
+                    session.bytes_from_client += frame_len(&message);
+                    let Some(message) = to_upstream(message) else { continue };
+                    if upstream_tx.send(message).await.is_err() {
+                        session.outcome = "upstream_error";
+                        let _ = client_tx.send(close_message(CLOSE_ERROR, "Upstream failed")).await;
+                        break;
+                    }
+                }
+                // The client went away without a close frame.
+                Some(Err(_)) | None => {
+                    let _ = upstream_tx.close().await;
+                    break;
+                }
+            },
+            message = upstream_rx.next() => match message {
+                Some(Ok(tungstenite::Message::Close(frame))) => {
+                    session.outcome = "upstream_closed";
+                    let frame = frame.map(|frame| CloseFrame {
+                        code: frame.code.into(),
+                        reason: frame.reason,
+                    });
+                    let _ = client_tx.send(ws::Message::Close(frame)).await;
+                    break;
+                }
+                Some(Ok(message)) => {
+                    let Some(message) = to_client(message) else { continue };
+                    session.bytes_from_upstream += frame_len(&message);
+                    if client_tx.send(message).await.is_err() {
+                        let _ = upstream_tx.close().await;
+                        break;
+                    }
+                }
+                Some(Err(e)) => {
+                    warn!(error = %e, "Live session failed upstream");
+                    session.outcome = "upstream_error";
+                    let _ = client_tx.send(close_message(CLOSE_ERROR, "Upstream failed")).await;
+                    break;
+                }
+                None => {
+                    session.outcome = "upstream_error";
+                    let _ = client_tx.send(close_message(CLOSE_ERROR, "Upstream closed")).await;
+                    break;
+                }
+            },
+        }
+    }
+    session
+}
+
+/// Converts a client data frame for the upstream socket. Pings and pongs are
+/// answered on each leg by the socket itself and are not relayed.
+fn to_upstream(message: ws::Message) -> Option<tungstenite::Message> {
+    match message {
+        ws::Message::Text(text) => Some(tungstenite::Message::Text(text)),
+        ws::Message::Binary(bytes) => Some(tungstenite::Message::Binary(bytes)),
+        _ => None,
+    }
+}
+
+/// Converts an upstream data frame for the client socket.
+fn to_client(message: tungstenite::Message) -> Option<ws::Message> {
+    match message {
+        tungstenite::Message::Text(text) => Some(ws::Message::Text(text)),
+        tungstenite::Message::Binary(bytes) => Some(ws::Message::Binary(bytes)),
+        _ => None,
+    }
+}
+
+fn frame_len(message: &ws::Message) -> u64 {
+    match message {
+        ws::Message::Text(text) => text.len() as u64,
+        ws::Message::Binary(bytes) => bytes.len() as u64,
+        _ => 0,
+    }
+}
+
+fn close_message(code: u16, reason: &str) -> ws::Message {
+    // Close frames carry at most 123 bytes of reason.
+    let mut end = reason.len().min(123);
+    while !reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    ws::Message::Close(Some(CloseFrame {
+        code,
+        reason: Cow::Owned(reason[..end].to_string()),
+    }))
+}
+
+async fn close(client: &mut WebSocket, code: u16, reason: &str) {
+    let _ = client.send(close_message(code, reason)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_model_strips_models_prefix() {
+        let setup = ws::Message::Text(
+            r#"{"setup":{"model":"models/gemini-2.0-flash-live-001"}}"#.to_string(),
+        );
+        assert_eq!(
+            setup_model(&setup).as_deref(),
+            Some("gemini-2.0-flash-live-001")
+        );
+        let content = ws::Message::Text(r#"{"clientContent":{}}"#.to_string());
+        assert_eq!(setup_model(&content), None);
+    }
+}
@@ -1,7 +1,15 @@
 // src/handlers/mod.rs
 
+pub mod affinity;
+pub mod anthropic;
 pub mod base;
 pub mod invalid_api_key;
+pub mod live;
+pub mod models;
+pub mod ollama;
+pub mod openai_native;
+pub mod parsed;
+pub(crate) mod passthrough;
 pub mod processor;
 pub mod proxy_loop;
 pub mod rate_limit;
@@ -9,23 +17,30 @@ pub mod server_error;
 pub mod success;
 pub mod terminal_error;
 pub mod timeout;
+pub mod upload;
 
 // --- Код, перенесенный из src/handler.rs ---
 
 use crate::{
+    cache,
     error::{AppError, Result},
+    group_selection,
     key_manager::FlattenedKeyInfo,
+    request_rules::{self, BodyShape},
     state::AppState,
+    token_limit,
+    translation::{openai, GeminiRequest},
 };
 use axum::{
     body::{to_bytes, Body, Bytes},
     extract::{Request, State},
     http::{HeaderMap, Method, StatusCode, Uri},
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use parsed::ParsedRequest;
 use secrecy::ExposeSecret;
 use std::sync::Arc;
-use tracing::{error, info, instrument, warn};
+use tracing::{info, instrument, warn};
 use url::Url;
 
 /// Lightweight health check handler used by /health route
@@ -37,31 +52,6 @@ pub async fn health_check() -> Response {
 
 /* ---------- helpers ---------- */
 
-/// Extracts model name from request path and body
-fn extract_model_from_request(path: &str, body: &[u8]) -> Option<String> {
-    // Try to extract from path first (for generateContent endpoints)
-    if let Some(captures) = regex::Regex::new(r"/v1beta/models/([^/:]+)")
-        .ok()?
-        .captures(path)
-    {
-        return Some(captures.get(1)?.as_str().to_string());
-    }
-
-    // Try to extract from OpenAI-style path
-    if path.contains("/chat/completions") || path.contains("/embeddings") {
-        // Try to parse JSON body to get model
-        if let Ok(body_str) = std::str::from_utf8(body) {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(body_str) {
-                if let Some(model) = json.get("model").and_then(|m| m.as_str()) {
-                    return Some(model.to_string());
-                }
-            }
-        }
-    }
-
-    None
-}
-
 fn translate_path(path: &str) -> String {
     if path == "/health/detailed" {
         return "/v1beta/models".into();
@@ -91,6 +81,8 @@ pub struct RequestContext<'a> {
     pub(crate) uri: &'a Uri,
     pub(crate) headers: &'a HeaderMap,
     pub(crate) body: &'a Bytes,
+    /// The body parsed as JSON, if it is JSON.
+    pub(crate) json: Option<&'a serde_json::Value>,
 }
 
 pub fn validate_token_count_with_limit(
@@ -103,65 +95,7 @@ pub fn validate_token_count_with_limit(
         None => return Ok(()),
     };
 
-    let mut total_tokens = 0usize;
-
-    // Handle different request formats
-    if let Some(contents) = json_body.get("contents") {
-        // Gemini native format
-        let mut text_to_tokenize = String::new();
-        if let Some(contents_array) = contents.as_array() {
-            for content in contents_array {
-                if let Some(parts) = content.get("parts").and_then(|p| p.as_array()) {
-                    for part in parts {
-                        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                            text_to_tokenize.push_str(text);
-                        }
-                    }
-                }
-            }
-        } else if let Some(text) = contents.as_str() {
-            text_to_tokenize.push_str(text);
-        }
-
-        if !text_to_tokenize.is_empty() {
-            match crate::tokenizer::gemini_ml_calibrated::count_ml_calibrated_gemini_tokens(
-                &text_to_tokenize,
-            ) {
-                Ok(token_count) => {
-                    total_tokens += token_count;
-                }
-                Err(e) => {
-                    warn!("Token counting failed: {}", e);
-                    // If tokenizer fails, allow request to proceed
-                    return Ok(());
-                }
-            }
-        }
-    } else if let Some(messages) = json_body.get("messages").and_then(|m| m.as_array()) {
-        // OpenAI format
-        let mut text_to_tokenize = String::new();
-        for message in messages {
-            if let Some(content) = message.get("content").and_then(|c| c.as_str()) {
-                text_to_tokenize.push_str(content);
-                text_to_tokenize.push('\n'); // Add separator between messages
-            }
-        }
-
-        if !text_to_tokenize.is_empty() {
-            match crate::tokenizer::gemini_ml_calibrated::count_ml_calibrated_gemini_tokens(
-                &text_to_tokenize,
-            ) {
-                Ok(token_count) => {
-                    total_tokens += token_count;
-                }
-                Err(e) => {
-                    warn!("Token counting for messages failed: {}", e);
-                    // If tokenizer fails, allow request to proceed
-                    return Ok(());
-                }
-            }
-        }
-    }
+    let total_tokens = crate::tokenizer::estimate_request_tokens(json_body);
 
     // Check if token count exceeds the limit
     if total_tokens > 0 && total_tokens as u64 > max_tokens {
@@ -184,60 +118,222 @@ pub fn is_streaming_request(body_bytes: &Bytes) -> bool {
     }
 }
 
-fn process_request_body(
-    body_bytes: Bytes,
-    top_p: Option<f64>,
-    max_tokens: Option<u64>,
-) -> Result<(Bytes, HeaderMap, bool)> {
-    let mut json_body_opt: Option<serde_json::Value> = serde_json::from_slice(&body_bytes).ok();
-    let mut headers = HeaderMap::new();
-    let is_streaming = is_streaming_request(&body_bytes);
-
-    if let Some(json_body) = &json_body_opt {
-        validate_token_count_with_limit(json_body, max_tokens)?;
+/// Sends a translated native Gemini request through the regular key rotation
+/// loop, so translated dialects get the same retry and blocking behaviour.
+pub(crate) async fn proxy_gemini_request(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    request: &GeminiRequest,
+) -> Result<Response> {
+    let uri: Uri = request
+        .path_and_query()
+        .parse()
+        .map_err(|e| AppError::internal(format!("Invalid translated URI: {e}")))?;
+    let mut json_body = request.body.clone();
+    {
+        let config = state.config.read().await;
+        let group = group_selection::resolve(&config, Some(&request.model), headers)?;
+        let (rules, top_p) = config.request_rules_for_group(group.as_deref());
+        request_rules::apply(rules, top_p, BodyShape::Gemini, &mut json_body);
     }
+    let parsed = ParsedRequest::from_json(uri.path(), json_body)?;
+    let body = parsed.body();
 
-    // Conditionally inject top_p
-    let body_modified = if let Some(top_p_value) = top_p {
-        if let Some(json_body) = &mut json_body_opt {
-            if let Some(obj) = json_body.as_object_mut() {
-                obj.insert("top_p".to_string(), serde_json::json!(top_p_value));
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    } else {
-        false
+    let mut headers = headers.clone();
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    headers.insert(
+        http::header::CONTENT_LENGTH,
+        http::HeaderValue::from(body.len()),
+    );
+    // The response is parsed and re-encoded, so it must come back uncompressed.
+    headers.remove(http::header::ACCEPT_ENCODING);
+
+    let req_context = RequestContext {
+        method: &Method::POST,
+        uri: &uri,
+        headers: &headers,
+        body,
+        json: parsed.json(),
     };
 
-    // If the body was modified, serialize it back to bytes
-    if body_modified {
-        if let Some(json_body) = json_body_opt {
-            match serde_json::to_vec(&json_body) {
-                Ok(new_body_bytes) => {
-                    let new_len = new_body_bytes.len();
-                    let body_bytes = Bytes::from(new_body_bytes);
-                    headers.insert(
-                        "content-length",
-                        http::HeaderValue::from_str(&new_len.to_string()).map_err(|e| {
-                            AppError::InvalidRequest {
-                                message: format!("Invalid header value created internally: {e}"),
-                            }
-                        })?,
-                    );
-                    return Ok((body_bytes, headers, is_streaming));
-                }
-                Err(e) => {
-                    error!(error = ?e, "Failed to re-serialize JSON body after modification.");
-                }
-            }
+    info!(model = %request.model, path = %uri.path(), "Proxying translated request");
+
+    proxy_with_cache(
+        state,
+        &req_context,
+        &Some(request.model.clone()),
+        request.stream,
+    )
+    .await
+}
+
+/// Runs the proxy loop behind the response cache. Cacheable requests are
+/// answered from the cache when possible; otherwise successful responses are
+/// buffered and stored. Every cacheable response carries `X-Proxy-Cache`.
+/// Requests over the token limit are rejected first.
+pub(crate) async fn proxy_with_cache(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    model: &Option<String>,
+    is_streaming: bool,
+) -> Result<Response> {
+    let group_name = group_selection::resolve(
+        &*state.config.read().await,
+        model.as_deref(),
+        req_context.headers,
+    )?;
+    token_limit::enforce(
+        state,
+        group_name.as_deref(),
+        model.as_deref(),
+        req_context.json,
+    )
+    .await?;
+
+    let Some(lookup) = state
+        .response_cache
+        .lookup_for(req_context, model, is_streaming)
+    else {
+        return proxy_upstream(state, req_context, model, is_streaming).await;
+    };
+
+    if lookup.read {
+        if let Some(cached) = state.response_cache.get(&lookup.key).await {
+            state.metrics.record_cache_lookup(true);
+            info!(model = ?model, "Serving response from cache");
+            let mut response = cached.into_response();
+            cache::set_cache_status(response.headers_mut(), true);
+            return Ok(response);
         }
     }
+    state.metrics.record_cache_lookup(false);
+
+    let response = proxy_upstream(state, req_context, model, is_streaming).await?;
+    let (mut parts, body) = response.into_parts();
+    cache::set_cache_status(&mut parts.headers, false);
+    if !parts.status.is_success() {
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to buffer response: {e}")))?;
+    let cached = cache::CachedResponse::new(parts.status, &parts.headers, body.clone());
+    state.response_cache.put(&lookup.key, &cached).await;
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Admits the request under its priority class and sends it upstream. The
+/// class permit is held until the response body has been sent.
+async fn proxy_upstream(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    model: &Option<String>,
+    is_streaming: bool,
+) -> Result<Response> {
+    let priority = state.config.read().await.priority.clone();
+    let Some(priority) = priority else {
+        return send_upstream(state, req_context, model, is_streaming).await;
+    };
 
-    Ok((body_bytes, headers, is_streaming))
+    let class = crate::priority::classify(Some(&priority), req_context.headers);
+    let Some(permit) =
+        state
+            .priority_gate
+            .try_admit(&state.metrics, class, priority.max_concurrent(class))
+    else {
+        warn!(
+            class = class.as_str(),
+            "Concurrency cap reached; shedding request"
+        );
+        state
+            .metrics
+            .record_priority_request(class.as_str(), "shed");
+        return Err(AppError::RequestShed {
+            class: class.as_str().to_string(),
+            retry_after_secs: 1,
+        });
+    };
+    state
+        .metrics
+        .record_priority_request(class.as_str(), "admitted");
+
+    let response = send_upstream(state, req_context, model, is_streaming).await?;
+    Ok(permit.attach(response))
+}
+
+/// Sends the request upstream. With `server.coalesce_requests` enabled,
+/// identical non-streaming requests in flight at the same time share one
+/// upstream call and all receive its (possibly error) response.
+async fn send_upstream(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    model: &Option<String>,
+    is_streaming: bool,
+) -> Result<Response> {
+    let coalesce = state.config.read().await.server.coalesce_requests;
+    if !coalesce
+        || is_streaming
+        || req_context.method != Method::POST
+        || req_context.uri.path().contains(":streamGenerateContent")
+        || affinity::creates_resource(req_context.method, req_context.uri.path())
+    {
+        return proxy_loop::proxy_loop(state, req_context, model, is_streaming).await;
+    }
+    let Some(json_body) = req_context.json else {
+        return proxy_loop::proxy_loop(state, req_context, model, is_streaming).await;
+    };
+
+    let key = cache::request_key(req_context, model, json_body);
+    let task_state = state.clone();
+    let method = req_context.method.clone();
+    let uri = req_context.uri.clone();
+    let headers = req_context.headers.clone();
+    let body = req_context.body.clone();
+    let json = json_body.clone();
+    let task_model = model.clone();
+
+    let coalesced = state
+        .request_coalescer
+        .run(key, move || async move {
+            let req_context = RequestContext {
+                method: &method,
+                uri: &uri,
+                headers: &headers,
+                body: &body,
+                json: Some(&json),
+            };
+            let response =
+                match proxy_loop::proxy_loop(&task_state, &req_context, &task_model, false).await {
+                    Ok(response) => response,
+                    Err(e) => e.into_response(),
+                };
+            buffer_response(response).await
+        })
+        .await?;
+
+    if coalesced.shared {
+        state.metrics.record_coalesced_request();
+        info!(model = ?model, "Served response from a coalesced upstream call");
+    }
+    Ok(coalesced.response.into_response())
+}
+
+async fn buffer_response(response: Response) -> cache::CachedResponse {
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, usize::MAX).await {
+        Ok(body) => cache::CachedResponse::new(parts.status, &parts.headers, body),
+        Err(e) => {
+            let (parts, body) = AppError::internal(format!("Failed to buffer response: {e}"))
+                .into_response()
+                .into_parts();
+            let body = to_bytes(body, usize::MAX).await.unwrap_or_default();
+            cache::CachedResponse::new(parts.status, &parts.headers, body)
+        }
+    }
 }
 
 /* ---------- main handler ---------- */
@@ -245,33 +341,61 @@ fn process_request_body(
 #[instrument(skip_all, fields(uri = %req.uri(), method = %req.method()))]
 pub async fn proxy_handler(State(state): State<Arc<AppState>>, req: Request) -> Result<Response> {
     let (mut parts, body) = req.into_parts();
+    if passthrough::applies(&state, &parts.method, parts.uri.path(), &parts.headers).await {
+        return passthrough::forward(&state, parts, body).await;
+    }
+
     let body_bytes = to_bytes(body, usize::MAX)
         .await
         .map_err(|e| AppError::internal(e.to_string()))?;
+    let mut parsed = ParsedRequest::new(parts.uri.path(), body_bytes);
+
+    // The size limit middleware cannot see group limits for body-named models
+    if parsed::has_body_model(parts.uri.path()) {
+        let max_size = state
+            .config
+            .read()
+            .await
+            .max_request_size_for(parts.uri.path(), parsed.model());
+        if parsed.body().len() > max_size {
+            return Err(AppError::PayloadTooLarge { max_size });
+        }
+    }
 
-    // Process request body (token validation and top_p injection)
-    let (top_p, max_tokens) = {
-        let config = state.config.read().await;
-        (config.top_p, config.server.max_tokens_per_request)
-    };
-    let (processed_body, additional_headers, is_streaming) =
-        process_request_body(body_bytes, top_p.map(|v| v as f64), max_tokens)?;
+    if parts.method == Method::POST
+        && openai::is_chat_completions_path(parts.uri.path())
+        && state.config.read().await.server.openai_native_translation
+    {
+        return openai_native::handle(&state, &parts.headers, &parsed).await;
+    }
 
-    // Merge additional headers
-    for (key, value) in additional_headers {
-        if let Some(key) = key {
-            parts.headers.insert(key, value);
+    // Apply the request rules of the model's group
+    if let Some(shape) = BodyShape::of(parts.uri.path()).filter(|_| parts.method == Method::POST) {
+        let config = state.config.read().await;
+        let group = group_selection::resolve(&config, parsed.model(), &parts.headers)?;
+        let (rules, top_p) = config.request_rules_for_group(group.as_deref());
+        if parsed.apply_rules(shape, rules, top_p)? {
+            parts.headers.insert(
+                http::header::CONTENT_LENGTH,
+                http::HeaderValue::from(parsed.body().len()),
+            );
         }
     }
+    let model = parsed.model().map(str::to_owned);
 
     let req_context = RequestContext {
         method: &parts.method,
         uri: &parts.uri,
         headers: &parts.headers,
-        body: &processed_body,
+        body: parsed.body(),
+        json: parsed.json(),
     };
 
-    let model = extract_model_from_request(req_context.uri.path(), &processed_body);
+    if affinity::is_batch_list(req_context.method, req_context.uri.path()) {
+        if let Some(response) = affinity::list_batches(&state, &req_context).await? {
+            return Ok(response);
+        }
+    }
 
     info!(
         model = ?model,
@@ -279,7 +403,7 @@ pub async fn proxy_handler(State(state): State<Arc<AppState>>, req: Request) ->
         "Processing request with model-specific key management"
     );
 
-    proxy_loop::proxy_loop(&state, &req_context, &model, is_streaming).await
+    proxy_with_cache(&state, &req_context, &model, parsed.is_streaming()).await
 }
 
 #[cfg(test)]
@@ -0,0 +1,45 @@
+// src/handlers/models.rs
+
+//! Serves the aggregated model catalog on `/models`, `/v1/models` and
+//! `/v1beta/models`.
+
+use super::proxy_handler;
+use crate::{catalog, error::Result, state::AppState};
+use axum::{
+    extract::{Request, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Handles `GET /models` and `GET /v1/models` with an OpenAI model list.
+pub async fn openai_models_handler(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+) -> Result<Response> {
+    serve_catalog(state, req, catalog::to_openai_list).await
+}
+
+/// Handles `GET /v1beta/models` with a Gemini model list.
+pub async fn gemini_models_handler(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+) -> Result<Response> {
+    serve_catalog(state, req, catalog::to_gemini_list).await
+}
+
+async fn serve_catalog(
+    state: Arc<AppState>,
+    req: Request,
+    render: fn(&[catalog::CatalogModel]) -> Value,
+) -> Result<Response> {
+    if state.config.read().await.server.model_catalog_refresh_secs == 0 {
+        return proxy_handler(State(state), req).await;
+    }
+    if !state.model_catalog.is_loaded() {
+        state.model_catalog.refresh(&state).await;
+    }
+    let models = state.model_catalog.models(&state).await;
+    Ok(Json(render(&models)).into_response())
+}
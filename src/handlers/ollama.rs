@@ -0,0 +1,153 @@
+// src/handlers/ollama.rs
+
+//! Serves an Ollama-compatible API surface on top of the Gemini key pool.
+
+use super::proxy_gemini_request;
+use crate::{
+    error::{AppError, Result},
+    state::AppState,
+    translation::{
+        ollama::{self, NdjsonStreamTranslator, OllamaEndpoint},
+        translate_event_stream, GeminiRequest,
+    },
+};
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue},
+    response::Response,
+    Json,
+};
+use serde_json::Value;
+use std::{collections::BTreeSet, sync::Arc, time::Instant};
+use tracing::{instrument, warn};
+
+/// Handles `POST /api/chat`.
+#[instrument(skip_all)]
+pub async fn chat_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let request = parse_body(&body)?;
+    let gemini_request = ollama::chat_request_to_gemini(&request)?;
+    generate(&state, &headers, gemini_request, OllamaEndpoint::Chat).await
+}
+
+/// Handles `POST /api/generate`.
+#[instrument(skip_all)]
+pub async fn generate_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let request = parse_body(&body)?;
+    let gemini_request = ollama::generate_request_to_gemini(&request)?;
+    generate(&state, &headers, gemini_request, OllamaEndpoint::Generate).await
+}
+
+/// Handles `POST /api/embed`.
+#[instrument(skip_all)]
+pub async fn embed_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let request = parse_body(&body)?;
+    let gemini_request = ollama::embed_request_to_gemini(&request)?;
+
+    let response = proxy_gemini_request(&state, &headers, &gemini_request).await?;
+    let (parts, bytes) = buffer_response(response).await?;
+    if !parts.status.is_success() {
+        return Ok(json_response(parts, &ollama::error_to_ollama(&bytes)));
+    }
+
+    let embeddings =
+        ollama::embed_response_to_ollama(&parse_upstream(&bytes)?, &gemini_request.model);
+    Ok(json_response(parts, &embeddings))
+}
+
+/// Handles `GET /api/tags` by listing every model alias configured across
+/// key groups.
+#[instrument(skip_all)]
+pub async fn tags_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let models: BTreeSet<String> = state
+        .config
+        .read()
+        .await
+        .groups
+        .iter()
+        .flat_map(|group| group.model_aliases.iter().cloned())
+        .collect();
+    Json(ollama::tags_response(
+        &models.into_iter().collect::<Vec<_>>(),
+    ))
+}
+
+async fn generate(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    gemini_request: GeminiRequest,
+    endpoint: OllamaEndpoint,
+) -> Result<Response> {
+    let started = Instant::now();
+
+    let response = proxy_gemini_request(state, headers, &gemini_request).await?;
+
+    if gemini_request.stream && response.status().is_success() {
+        let (mut parts, upstream_body) = response.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.remove(header::CONTENT_ENCODING);
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/x-ndjson"),
+        );
+        let translator =
+            NdjsonStreamTranslator::new(gemini_request.model.clone(), endpoint, started);
+        let body = translate_event_stream(upstream_body, translator);
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    let (parts, bytes) = buffer_response(response).await?;
+    if !parts.status.is_success() {
+        return Ok(json_response(parts, &ollama::error_to_ollama(&bytes)));
+    }
+    let object = ollama::gemini_response_to_ollama(
+        &parse_upstream(&bytes)?,
+        &gemini_request.model,
+        endpoint,
+        started,
+    );
+    Ok(json_response(parts, &object))
+}
+
+fn parse_body(body: &Bytes) -> Result<Value> {
+    serde_json::from_slice(body).map_err(|e| AppError::InvalidRequest {
+        message: format!("Invalid request body: {e}"),
+    })
+}
+
+fn parse_upstream(bytes: &Bytes) -> Result<Value> {
+    serde_json::from_slice(bytes).map_err(|e| {
+        warn!(error = %e, "Upstream returned a non-JSON response");
+        AppError::internal(format!("Invalid upstream response: {e}"))
+    })
+}
+
+async fn buffer_response(response: Response) -> Result<(axum::http::response::Parts, Bytes)> {
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_ENCODING);
+    let bytes = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::internal(e.to_string()))?;
+    Ok((parts, bytes))
+}
+
+fn json_response(mut parts: axum::http::response::Parts, value: &Value) -> Response {
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Response::from_parts(parts, Body::from(value.to_string()))
+}
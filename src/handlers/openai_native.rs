@@ -0,0 +1,79 @@
+// src/handlers/openai_native.rs
+
+//! Serves OpenAI `chat/completions` by translating to native Gemini
+//! `generateContent` and translating the response back.
+
+use super::{parsed::ParsedRequest, proxy_gemini_request};
+use crate::{
+    error::{AppError, Result},
+    state::AppState,
+    translation::{openai, translate_event_stream},
+};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, HeaderValue},
+    response::Response,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Handles one chat completions request in native translation mode.
+pub async fn handle(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    request: &ParsedRequest,
+) -> Result<Response> {
+    let Some(request) = request.json() else {
+        // Parse again only to report why the body is invalid.
+        let error = serde_json::from_slice::<Value>(request.body())
+            .err()
+            .map_or_else(|| "not JSON".to_string(), |e| e.to_string());
+        return Err(AppError::InvalidRequest {
+            message: format!("Invalid chat completions body: {error}"),
+        });
+    };
+    let gemini_request = openai::chat_request_to_gemini(request)?;
+
+    let response = proxy_gemini_request(state, headers, &gemini_request).await?;
+
+    // Errors are returned untouched so clients still see the upstream reason.
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let (mut parts, upstream_body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_ENCODING);
+
+    if gemini_request.stream {
+        let translator = openai::ChatStreamTranslator::new(
+            gemini_request.model.clone(),
+            openai::wants_stream_usage(request),
+        );
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        let body = translate_event_stream(upstream_body, translator);
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    let bytes = to_bytes(upstream_body, usize::MAX)
+        .await
+        .map_err(|e| AppError::internal(e.to_string()))?;
+    let gemini_response: Value = serde_json::from_slice(&bytes).map_err(|e| {
+        warn!(error = %e, "Upstream returned a non-JSON generateContent response");
+        AppError::internal(format!("Invalid upstream response: {e}"))
+    })?;
+
+    let completion = openai::gemini_response_to_chat(&gemini_response, &gemini_request.model);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Ok(Response::from_parts(
+        parts,
+        Body::from(serde_json::to_vec(&completion)?),
+    ))
+}
@@ -0,0 +1,145 @@
+// src/handlers/parsed.rs
+
+//! A buffered request body parsed once and shared by every stage of the
+//! proxy: model routing, request rules, the token limit, the response cache,
+//! coalescing and resource affinity all read the same `Value`.
+
+use crate::{
+    config::RequestRules,
+    error::Result,
+    request_rules::{self, BodyShape},
+};
+use axum::body::Bytes;
+use regex::Regex;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Returns the model named in a Gemini `/v1beta/models/{model}` path.
+pub fn model_from_path(path: &str) -> Option<&str> {
+    static MODEL_PATH: OnceLock<Regex> = OnceLock::new();
+    MODEL_PATH
+        .get_or_init(|| Regex::new(r"/v1beta/models/([^/:]+)").expect("valid model path regex"))
+        .captures(path)
+        .and_then(|captures| captures.get(1))
+        .map(|model| model.as_str())
+}
+
+/// Returns whether requests to `path` name their model in the body.
+pub fn has_body_model(path: &str) -> bool {
+    path.contains("/chat/completions") || path.contains("/embeddings")
+}
+
+/// A request body with its JSON form and the facts derived from it.
+#[derive(Debug, Clone)]
+pub struct ParsedRequest {
+    body: Bytes,
+    json: Option<Value>,
+    model: Option<String>,
+    is_streaming: bool,
+}
+
+impl ParsedRequest {
+    /// Parses a request to `path`. Bodies that are not JSON are kept as-is.
+    pub fn new(path: &str, body: Bytes) -> Self {
+        let json = serde_json::from_slice::<Value>(&body).ok();
+        Self::with_json(path, body, json)
+    }
+
+    /// Builds the request for a body that is already a `Value`, such as a
+    /// translated request, serializing it once.
+    pub fn from_json(path: &str, json: Value) -> Result<Self> {
+        let body = Bytes::from(serde_json::to_vec(&json)?);
+        Ok(Self::with_json(path, body, Some(json)))
+    }
+
+    fn with_json(path: &str, body: Bytes, json: Option<Value>) -> Self {
+        let model = model_from_path(path).map(str::to_owned).or_else(|| {
+            has_body_model(path)
+                .then(|| json.as_ref()?.get("model")?.as_str().map(str::to_owned))
+                .flatten()
+        });
+        let is_streaming = json
+            .as_ref()
+            .and_then(|json| json.get("stream"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        Self {
+            body,
+            json,
+            model,
+            is_streaming,
+        }
+    }
+
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    pub fn json(&self) -> Option<&Value> {
+        self.json.as_ref()
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.is_streaming
+    }
+
+    /// Applies request rules and `topP` to the body, re-serializing it if it
+    /// changed. Returns whether it did.
+    pub fn apply_rules(
+        &mut self,
+        shape: BodyShape,
+        rules: Option<&RequestRules>,
+        top_p: Option<f32>,
+    ) -> Result<bool> {
+        let Some(json) = &mut self.json else {
+            return Ok(false);
+        };
+        if !request_rules::apply(rules, top_p, shape, json) {
+            return Ok(false);
+        }
+        self.body = Bytes::from(serde_json::to_vec(json)?);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_model_and_stream_flag_come_from_one_parse() {
+        let request = ParsedRequest::new(
+            "/v1/chat/completions",
+            Bytes::from(r#"{"model":"gemini-2.5-flash","stream":true,"messages":[]}"#),
+        );
+        assert_eq!(request.model(), Some("gemini-2.5-flash"));
+        assert!(request.is_streaming());
+
+        let request = ParsedRequest::new(
+            "/v1beta/models/gemini-2.0-flash:generateContent",
+            Bytes::from_static(b"not json"),
+        );
+        assert_eq!(request.model(), Some("gemini-2.0-flash"));
+        assert!(request.json().is_none());
+        assert!(!request.is_streaming());
+    }
+
+    #[test]
+    fn test_rules_reserialize_the_body() {
+        let mut request = ParsedRequest::new(
+            "/v1/chat/completions",
+            Bytes::from(r#"{"model":"m","messages":[]}"#),
+        );
+        assert!(request
+            .apply_rules(BodyShape::OpenAi, None, Some(0.5))
+            .unwrap());
+        let body: Value = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(body, json!({"model": "m", "messages": [], "top_p": 0.5}));
+        assert_eq!(request.json(), Some(&body));
+    }
+}
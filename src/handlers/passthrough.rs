@@ -0,0 +1,136 @@
+// src/handlers/passthrough.rs
+
+//! Fast path for request bodies the proxy has no reason to read.
+//!
+//! With `server.stream_request_bodies` enabled, requests to routes whose
+//! bodies never name a model or a key-owned resource are streamed straight
+//! upstream instead of being buffered and parsed, as long as no token limit,
+//! response cache, coalescing, priority admission, request rule or `topP`
+//! needs the body. The body cannot be replayed, so a streamed request makes a
+//! single attempt: no retry with another key, no same-key retry and no hedge.
+//! The key's failure is recorded and the upstream response returned.
+
+use super::{affinity, base::Action, parsed::model_from_path, proxy_loop, RequestContext};
+use crate::{
+    error::{AppError, Result},
+    group_selection, proxy,
+    state::AppState,
+    usage,
+};
+use axum::{
+    body::{Body, Bytes},
+    http::{request::Parts, HeaderMap, Method},
+    response::Response,
+};
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use tracing::{debug, instrument};
+
+/// Model actions whose bodies carry neither a model nor resource references.
+const STREAMABLE_ACTIONS: [&str; 4] = [
+    ":embedContent",
+    ":batchEmbedContents",
+    ":predict",
+    ":predictLongRunning",
+];
+
+/// Returns whether a request may take the fast path. Requests whose group
+/// rewrites bodies with request rules or `topP`, and setups with hedging,
+/// which needs a second attempt, keep the buffered path.
+pub(crate) async fn applies(
+    state: &Arc<AppState>,
+    method: &Method,
+    path: &str,
+    headers: &HeaderMap,
+) -> bool {
+    let Some(model) = model_from_path(path) else {
+        return false;
+    };
+    if *method != Method::POST
+        || !STREAMABLE_ACTIONS
+            .iter()
+            .any(|action| path.ends_with(action))
+    {
+        return false;
+    }
+    let config = state.config.read().await;
+    if !config.server.stream_request_bodies
+        || config.server.max_tokens_per_request.is_some()
+        || config.server.coalesce_requests
+        || config.priority.is_some()
+        || config.hedging.is_some()
+        || state.response_cache.is_enabled()
+    {
+        return false;
+    }
+    // Invalid group selections are rejected on the buffered path.
+    let Ok(group_name) = group_selection::resolve(&config, Some(model), headers) else {
+        return false;
+    };
+    let (rules, top_p) = config.request_rules_for_group(group_name.as_deref());
+    rules.is_none() && top_p.is_none()
+}
+
+/// Streams the request to the next key of the model's group.
+#[instrument(skip_all, fields(uri = %parts.uri))]
+pub(crate) async fn forward(state: &Arc<AppState>, parts: Parts, body: Body) -> Result<Response> {
+    let model = model_from_path(parts.uri.path()).unwrap_or_default();
+    let group_name =
+        group_selection::resolve(&*state.config.read().await, Some(model), &parts.headers)?;
+    let key_info = state
+        .key_manager
+        .read()
+        .await
+        .get_next_available_key_info_for_model(group_name.as_deref(), Some(model))
+        .await?
+        .ok_or(AppError::NoHealthyKeys)?;
+    debug!(model, "Streaming request body upstream");
+
+    let url = super::build_target_url(&parts.uri, &key_info)?;
+    let client = state.get_client(key_info.proxy_url.as_deref()).await?;
+    let response = match proxy::forward_streaming_request(
+        &client,
+        &key_info,
+        parts.method.clone(),
+        url,
+        parts.headers.clone(),
+        body,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            state
+                .circuit_breakers
+                .record_failure(key_info.key.expose_secret(), Some(model))
+                .await;
+            return Err(e);
+        }
+    };
+
+    let (action, final_response) = state
+        .response_processor
+        .process(response, &key_info)
+        .await?;
+    proxy_loop::record_breaker_outcome(state, &key_info, model, &action).await;
+    match action {
+        Action::ReturnToClient(resp) => {
+            // Long-running predictions return an operation owned by this key.
+            let empty_body = Bytes::new();
+            let req_context = RequestContext {
+                method: &parts.method,
+                uri: &parts.uri,
+                headers: &parts.headers,
+                body: &empty_body,
+                json: None,
+            };
+            let resp = affinity::record_owners(state, &req_context, &key_info, resp).await?;
+            Ok(usage::track_response(state, &key_info, model, &parts.headers, resp).await)
+        }
+        Action::Terminal(resp) => Ok(resp),
+        action => {
+            proxy_loop::mark_key(state, &key_info, action).await?;
+            Ok(final_response)
+        }
+    }
+}
@@ -1,27 +1,42 @@
 // src/handlers/proxy_loop.rs
 
 use crate::{
+    config::{HedgingConfig, PriorityClass},
     error::{AppError, Result},
-    handlers::{base::Action, RequestContext},
+    group_selection,
+    handlers::{affinity, base::Action, RequestContext},
+    hedging,
     key_manager::FlattenedKeyInfo,
-    proxy,
+    priority, proxy,
+    retry_budget::{self, RetryBudget},
     state::AppState,
-    tokenizer::gemini_ml_calibrated::count_ml_calibrated_gemini_tokens,
+    usage,
+    wait_queue::{self, WaitSlot},
+};
+use axum::{
+    body::Body,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
-use axum::{body::Body, http::StatusCode, response::Response};
 use secrecy::ExposeSecret;
+use std::collections::HashSet;
+use std::future::Future;
 use std::sync::Arc;
-use tracing::{error, info, trace, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
+
+/// Same-key retries after a transient upstream error when `transient_retries`
+/// is not set.
+const DEFAULT_TRANSIENT_RETRIES: u32 = 2;
 
 /// Tries a single request with a given key.
-async fn try_request_with_key(
+pub(crate) async fn try_request_with_key(
     state: &Arc<AppState>,
     req_context: &RequestContext<'_>,
     key_info: &FlattenedKeyInfo,
 ) -> Result<Response> {
     let url = super::build_target_url(req_context.uri, key_info)?;
     let client = state.get_client(key_info.proxy_url.as_deref()).await?;
-    let circuit_breaker = state.get_circuit_breaker(&key_info.target_url).await;
 
     proxy::forward_request(
         &client,
@@ -30,116 +45,397 @@ async fn try_request_with_key(
         url,
         req_context.headers.clone(),
         req_context.body.clone(),
-        circuit_breaker,
     )
     .await
 }
 
-/// Main loop for handling proxy requests, iterating through available keys.
+fn is_success(result: &Result<Response>) -> bool {
+    matches!(result, Ok(response) if response.status().is_success())
+}
+
+/// Sends the request with `key_info` and, if nothing has come back after
+/// `delay`, races a duplicate on another key of the same group. The first
+/// success wins and the other request is dropped, which cancels it. Returns
+/// the result to process together with the key that produced it.
+async fn hedged_request(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    key_info: &FlattenedKeyInfo,
+    group_name: Option<&str>,
+    model: &str,
+    hedging: &HedgingConfig,
+    delay: Duration,
+) -> (Result<Response>, FlattenedKeyInfo) {
+    let primary = try_request_with_key(state, req_context, key_info);
+    tokio::pin!(primary);
+
+    tokio::select! {
+        result = &mut primary => return (result, key_info.clone()),
+        () = tokio::time::sleep(delay) => {}
+    }
+
+    let hedge_key = match state
+        .key_manager
+        .read()
+        .await
+        .get_next_available_key_info_for_model(group_name, Some(model))
+        .await
+    {
+        Ok(Some(info)) if info.key.expose_secret() != key_info.key.expose_secret() => info,
+        _ => {
+            debug!("No alternative key available for hedging");
+            return (primary.await, key_info.clone());
+        }
+    };
+    if !state.hedge_controller.try_acquire(hedging) {
+        debug!("Hedge budget exhausted; waiting for primary request");
+        return (primary.await, key_info.clone());
+    }
+
+    info!(
+        delay_ms = delay.as_millis() as u64,
+        key.preview = %crate::key_manager::KeyManager::preview_key(&hedge_key.key),
+        "No response within hedge delay; sending hedged request"
+    );
+    let hedge = try_request_with_key(state, req_context, &hedge_key);
+    tokio::pin!(hedge);
+
+    let (hedge_first, first) = tokio::select! {
+        result = &mut primary => (false, result),
+        result = &mut hedge => (true, result),
+    };
+    if is_success(&first) {
+        state.metrics.record_hedge(model.to_string(), hedge_first);
+        let key = if hedge_first {
+            hedge_key.clone()
+        } else {
+            key_info.clone()
+        };
+        return (first, key);
+    }
+
+    // The first request to finish failed; give the other one a chance.
+    let second = if hedge_first {
+        (&mut primary).await
+    } else {
+        (&mut hedge).await
+    };
+    let (primary_result, hedge_result) = if hedge_first {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    // If neither succeeded, the primary's failure is reported against its key.
+    let hedge_won = is_success(&hedge_result);
+    state.metrics.record_hedge(model.to_string(), hedge_won);
+    if hedge_won {
+        record_dropped_outcome(state, key_info, model, primary_result).await;
+        (hedge_result, hedge_key.clone())
+    } else {
+        record_dropped_outcome(state, &hedge_key, model, hedge_result).await;
+        (primary_result, key_info.clone())
+    }
+}
+
+/// Holds the failed result of a hedged request that is not returned against
+/// its key, so a rate-limited or failing key is not handed out again at once.
+async fn record_dropped_outcome(
+    state: &Arc<AppState>,
+    key_info: &FlattenedKeyInfo,
+    model: &str,
+    result: Result<Response>,
+) {
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            debug!(error = ?e, "Dropped hedged request failed");
+            state
+                .circuit_breakers
+                .record_failure(key_info.key.expose_secret(), Some(model))
+                .await;
+            return;
+        }
+    };
+    let action = match state.response_processor.process(response, key_info).await {
+        Ok((action, _)) => action,
+        Err(e) => {
+            warn!(error = ?e, "Failed to process dropped hedged response");
+            return;
+        }
+    };
+    record_breaker_outcome(state, key_info, model, &action).await;
+    if let Err(e) = mark_key(state, key_info, action).await {
+        warn!(error = ?e, "Failed to mark key of dropped hedged request");
+    }
+}
+
+/// Records the failure behind a retry `action` against the key that caused it:
+/// a failure, a permanent block, or a rate limit lasting the wait period.
+/// Transient upstream errors are not held against the key.
+pub(crate) async fn mark_key(
+    state: &Arc<AppState>,
+    key_info: &FlattenedKeyInfo,
+    action: Action,
+) -> Result<()> {
+    let key_manager = state.key_manager.write().await;
+    let key = key_info.key.expose_secret();
+    match action {
+        Action::RetryNextKey => key_manager.handle_api_failure(key, false).await,
+        Action::BlockKeyAndRetry => key_manager.handle_api_failure(key, true).await,
+        Action::WaitFor(duration) => key_manager.handle_rate_limit(key, duration).await,
+        Action::RetryTransient | Action::ReturnToClient(_) | Action::Terminal(_) => Ok(()),
+    }
+}
+
+/// Records the outcome behind `action` on the circuit breakers of the key and
+/// of the key and `model`. Answers the key got through count as successes,
+/// failures caused by the key as failures; rate limits and transient upstream
+/// errors say nothing about the key's health, so only the half-open probes
+/// taken for the request are given back.
+pub(crate) fn record_breaker_outcome<'a>(
+    state: &'a Arc<AppState>,
+    key_info: &'a FlattenedKeyInfo,
+    model: &'a str,
+    action: &Action,
+) -> impl Future<Output = ()> + Send + 'a {
+    // Decided up front: the action holds a response, which is not `Sync`.
+    let succeeded = match action {
+        Action::ReturnToClient(_) | Action::Terminal(_) => Some(true),
+        Action::RetryNextKey | Action::BlockKeyAndRetry => Some(false),
+        Action::RetryTransient | Action::WaitFor(_) => None,
+    };
+    async move {
+        let breakers = &state.circuit_breakers;
+        let key = key_info.key.expose_secret();
+        match succeeded {
+            Some(true) => breakers.record_success(key, Some(model)).await,
+            Some(false) => breakers.record_failure(key, Some(model)).await,
+            None => breakers.release(key, Some(model)).await,
+        }
+    }
+}
+
+/// Main loop for handling proxy requests, iterating through available keys
+/// within the request's retry budget.
 pub async fn proxy_loop(
     state: &Arc<AppState>,
     req_context: &RequestContext<'_>,
     model: &Option<String>,
     is_streaming: bool,
 ) -> Result<Response> {
-    let max_tokens = {
+    let mut budget = {
+        let config = state.config.read().await;
+        let group_name = group_selection::resolve(&config, model.as_deref(), req_context.headers)?;
+        RetryBudget::new(&config, group_name.as_deref(), req_context.headers)
+    };
+    match try_keys(state, req_context, model, is_streaming, &mut budget).await {
+        Ok(response) => Ok(budget.stamp(response)),
+        // Errors such as exhausted keys report the attempts made as well.
+        Err(e) => Ok(budget.stamp(e.into_response())),
+    }
+}
+
+async fn try_keys(
+    state: &Arc<AppState>,
+    req_context: &RequestContext<'_>,
+    model: &Option<String>,
+    is_streaming: bool,
+    budget: &mut RetryBudget,
+) -> Result<Response> {
+    let mut last_response: Option<Response> = None;
+
+    // Requests on a resource owned by one key can only use that key.
+    let mut pinned_key = affinity::pinned_key(state, req_context).await?;
+    let is_pinned = pinned_key.is_some();
+
+    // Only idempotent calls are hedged; a hedged create would make a second,
+    // orphaned resource on another key.
+    let hedgeable = hedging::is_hedgeable(req_context.method, req_context.uri.path());
+    let hedging = if is_streaming || is_pinned || !hedgeable {
+        None
+    } else {
+        state.config.read().await.hedging.clone()
+    };
+    let model_name = model.as_deref().unwrap_or_default();
+    let hedge_delay = hedging
+        .as_ref()
+        .and_then(|h| state.hedge_controller.hedge_delay(h, model_name));
+    let (wait_queue_enabled, priority_config, max_transient_retries, backoff) = {
         let config_guard = state.config.read().await;
-        config_guard.server.max_tokens_per_request
+        (
+            config_guard.key_wait_queue.is_some(),
+            config_guard.priority.clone(),
+            config_guard
+                .transient_retries
+                .unwrap_or(DEFAULT_TRANSIENT_RETRIES),
+            config_guard.retry_backoff.clone().unwrap_or_default(),
+        )
     };
+    let class = priority::classify(priority_config.as_ref(), req_context.headers);
+    let mut wait_slot: Option<WaitSlot> = None;
+    // The key to retry after a transient error, and its retries so far.
+    let mut retry_key: Option<FlattenedKeyInfo> = None;
+    let mut transient_retries = 0;
+    // Keys tried until their transient retries ran out. They are not marked
+    // as failed, so the rotation still hands them out; they are skipped until
+    // it has offered more keys in a row than the pool holds.
+    let mut tried_keys: HashSet<String> = HashSet::new();
+    let mut skipped_in_a_row = 0;
 
-    if let Some(max_tokens) = max_tokens {
-        if let Ok(body_str) = std::str::from_utf8(req_context.body) {
-            if let Ok(json_body) = serde_json::from_str::<serde_json::Value>(body_str) {
-                let mut total_tokens = 0;
-                if let Some(contents) = json_body.get("contents") {
-                    let mut text_to_tokenize = String::new();
-                    if let Some(contents_array) = contents.as_array() {
-                        for content in contents_array {
-                            if let Some(parts) = content.get("parts").and_then(|p| p.as_array()) {
-                                for part in parts {
-                                    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                        text_to_tokenize.push_str(text);
-                                    }
-                                }
-                            }
-                        }
-                    } else if let Some(text) = contents.as_str() {
-                        text_to_tokenize.push_str(text);
-                    }
+    loop {
+        if budget.exhausted() {
+            warn!(attempts = budget.attempts(), "Retry budget exhausted");
+            if last_response.is_none() {
+                return Err(AppError::DeadlineExceeded {
+                    attempts: budget.attempts(),
+                });
+            }
+            break;
+        }
+        let group_name = group_selection::resolve(
+            &*state.config.read().await,
+            model.as_deref(),
+            req_context.headers,
+        )?;
 
-                    if !text_to_tokenize.is_empty() {
-                        match count_ml_calibrated_gemini_tokens(&text_to_tokenize) {
-                            Ok(token_count) => {
-                                total_tokens += token_count;
-                            }
-                            Err(e) => {
-                                warn!("Token counting failed: {}", e);
-                            }
-                        }
-                    }
-                } else if let Some(messages) = json_body.get("messages").and_then(|m| m.as_array())
-                {
-                    let mut text_to_tokenize = String::new();
-                    for message in messages {
-                        if let Some(content) = message.get("content").and_then(|c| c.as_str()) {
-                            text_to_tokenize.push_str(content);
-                            text_to_tokenize.push('\n'); // Add separator between messages
-                        }
-                    }
+        // Batch requests leave the last keys of a group under pressure to
+        // interactive traffic.
+        let held_back = match (&priority_config, class) {
+            (Some(config), PriorityClass::Batch) => {
+                !priority::batch_may_take_key(state, config, group_name.as_deref()).await?
+            }
+            _ => false,
+        };
+        let next_key = if let Some(key) = retry_key.take() {
+            Some(key)
+        } else if is_pinned {
+            transient_retries = 0;
+            pinned_key.take()
+        } else if held_back {
+            None
+        } else {
+            transient_retries = 0;
+            state
+                .key_manager
+                .read()
+                .await
+                .get_next_available_key_info_for_model(group_name.as_deref(), model.as_deref())
+                .await?
+        };
 
-                    if !text_to_tokenize.is_empty() {
-                        match count_ml_calibrated_gemini_tokens(&text_to_tokenize) {
-                            Ok(token_count) => {
-                                total_tokens += token_count;
-                            }
-                            Err(e) => {
-                                warn!("Token counting for messages failed: {}", e);
-                            }
+        let key_info = match next_key {
+            Some(info) if tried_keys.contains(info.key.expose_secret()) => {
+                state
+                    .circuit_breakers
+                    .release(info.key.expose_secret(), model.as_deref())
+                    .await;
+                skipped_in_a_row += 1;
+                let pool_size = state
+                    .key_manager
+                    .read()
+                    .await
+                    .get_all_key_info()
+                    .await
+                    .len();
+                if skipped_in_a_row > pool_size {
+                    debug!("Every available key hit transient errors");
+                    break;
+                }
+                continue;
+            }
+            Some(info) => {
+                skipped_in_a_row = 0;
+                info
+            }
+            None if is_pinned => break,
+            None => {
+                let waited = budget
+                    .within(wait_queue::wait_for_key(
+                        state,
+                        req_context.headers,
+                        group_name.as_deref(),
+                        class,
+                        &mut wait_slot,
+                    ))
+                    .await;
+                match waited {
+                    // Out of time while queued; the budget check ends the loop.
+                    None => continue,
+                    Some(waited) => {
+                        if waited? {
+                            continue;
                         }
                     }
                 }
-
-                if total_tokens > 0 && total_tokens as u64 > max_tokens {
-                    return Err(AppError::RequestTooLarge {
-                        size: total_tokens,
-                        max_size: max_tokens as usize,
+                if held_back {
+                    warn!("Keys reserved for interactive traffic; shedding batch request");
+                    state
+                        .metrics
+                        .record_priority_request(class.as_str(), "shed");
+                    return Err(AppError::RequestShed {
+                        class: class.as_str().to_string(),
+                        retry_after_secs: 1,
                     });
                 }
+                break;
             }
+        };
+        if let Some(slot) = wait_slot.take() {
+            slot.finish();
         }
-    }
-    let mut last_response: Option<Response> = None;
 
-    loop {
-        let group_name = {
-            let config_guard = state.config.read().await;
-            model
-                .as_deref()
-                .and_then(|m| config_guard.get_group_for_model(m))
-                .map(|g| g.to_string())
-        };
+        info!(key.preview = %crate::key_manager::KeyManager::preview_key(&key_info.key), "Attempting to use key");
 
-        let key_info = match state
-            .key_manager
-            .read()
-            .await
-            .get_next_available_key_info(group_name.as_deref())
-            .await?
-        {
-            Some(info) => info,
-            None => break,
+        budget.start_attempt();
+        let attempt_started = Instant::now();
+        let attempt = async {
+            match (&hedging, hedge_delay) {
+                (Some(hedging), Some(delay)) => {
+                    hedged_request(
+                        state,
+                        req_context,
+                        &key_info,
+                        group_name.as_deref(),
+                        model_name,
+                        hedging,
+                        delay,
+                    )
+                    .await
+                }
+                _ => (
+                    try_request_with_key(state, req_context, &key_info).await,
+                    key_info.clone(),
+                ),
+            }
+        };
+        let Some((result, key_info)) = budget.within(attempt).await else {
+            warn!(
+                attempts = budget.attempts(),
+                "Request deadline passed during an upstream attempt"
+            );
+            return Err(AppError::DeadlineExceeded {
+                attempts: budget.attempts(),
+            });
         };
 
-        info!(key.preview = %crate::key_manager::KeyManager::preview_key(&key_info.key), "Attempting to use key");
-
-        let response = match try_request_with_key(state, req_context, &key_info).await {
+        let response = match result {
             Ok(r) => r,
             Err(e) => {
                 error!(error = ?e, key.preview = %crate::key_manager::KeyManager::preview_key(&key_info.key), "Request failed");
+                state
+                    .circuit_breakers
+                    .record_failure(key_info.key.expose_secret(), model.as_deref())
+                    .await;
                 return Err(e);
             }
         };
 
+        if hedging.is_some() && response.status().is_success() {
+            state
+                .hedge_controller
+                .record_latency(model_name, attempt_started.elapsed());
+        }
+
         if is_streaming && response.status().is_success() {
             if let Some(content_type) = response.headers().get("content-type") {
                 if content_type
@@ -149,7 +445,18 @@ pub async fn proxy_loop(
                     || content_type.to_str().unwrap_or("").contains("text/plain")
                 {
                     info!("Returning streaming response directly to client");
-                    return Ok(response);
+                    state
+                        .circuit_breakers
+                        .record_success(key_info.key.expose_secret(), model.as_deref())
+                        .await;
+                    return Ok(usage::track_response(
+                        state,
+                        &key_info,
+                        model_name,
+                        req_context.headers,
+                        response,
+                    )
+                    .await);
                 }
             }
         }
@@ -158,41 +465,59 @@ pub async fn proxy_loop(
             .response_processor
             .process(response, &key_info)
             .await?;
+        record_breaker_outcome(state, &key_info, model_name, &action).await;
 
         match action {
-            Action::ReturnToClient(resp) => return Ok(resp),
+            Action::ReturnToClient(resp) => {
+                let resp = affinity::record_owners(state, req_context, &key_info, resp).await?;
+                return Ok(usage::track_response(
+                    state,
+                    &key_info,
+                    model_name,
+                    req_context.headers,
+                    resp,
+                )
+                .await);
+            }
             Action::Terminal(resp) => return Ok(resp),
-            Action::RetryNextKey => {
-                trace!("Retrying with next key");
-                state
-                    .key_manager
-                    .write()
-                    .await
-                    .handle_api_failure(key_info.key.expose_secret(), false)
-                    .await?;
+            Action::WaitFor(duration) => {
+                trace!("Rate limit with wait period received. Marking key and waiting.");
+                mark_key(state, &key_info, Action::WaitFor(duration)).await?;
+
+                // With a wait queue the key stays blocked until `duration`
+                // passes and other keys are tried first.
+                if !wait_queue_enabled {
+                    info!(?duration, "Rate limit hit. Waiting before retrying.");
+                    tokio::time::sleep(budget.cap_wait(duration)).await;
+                }
                 last_response = Some(final_response);
             }
-            Action::BlockKeyAndRetry => {
-                trace!("Blocking key and retrying");
+            Action::RetryTransient if transient_retries < max_transient_retries => {
+                transient_retries += 1;
+                let delay =
+                    budget.cap_wait(retry_budget::backoff_delay(&backoff, transient_retries));
+                let status = final_response.status().as_u16();
+                info!(
+                    status,
+                    retry = transient_retries,
+                    delay_ms = delay.as_millis() as u64,
+                    "Transient upstream error; retrying the same key"
+                );
                 state
-                    .key_manager
-                    .write()
-                    .await
-                    .handle_api_failure(key_info.key.expose_secret(), true)
-                    .await?;
+                    .metrics
+                    .record_transient_retry(model_name.to_string(), status);
+                tokio::time::sleep(delay).await;
+                retry_key = Some(key_info);
                 last_response = Some(final_response);
             }
-            Action::WaitFor(duration) => {
-                trace!("Rate limit with wait period received. Marking key and waiting.");
-                state
-                    .key_manager
-                    .write()
-                    .await
-                    .handle_rate_limit(key_info.key.expose_secret(), duration)
-                    .await?;
-
-                info!(?duration, "Rate limit hit. Waiting before retrying.");
-                tokio::time::sleep(duration).await;
+            Action::RetryTransient => {
+                debug!("Transient retries exhausted; trying the next key");
+                tried_keys.insert(key_info.key.expose_secret().clone());
+                last_response = Some(final_response);
+            }
+            action @ (Action::RetryNextKey | Action::BlockKeyAndRetry) => {
+                trace!(?action, "Retrying with next key");
+                mark_key(state, &key_info, action).await?;
                 last_response = Some(final_response);
             }
         }
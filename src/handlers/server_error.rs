@@ -4,7 +4,7 @@ use super::base::{Action, ResponseHandler};
 use axum::{body::Bytes, http::StatusCode, response::Response};
 use tracing::warn;
 
-/// Handler for server errors that should trigger key rotation
+/// Handler for transient server errors, retried without blaming the key
 pub struct ServerErrorHandler;
 
 impl ResponseHandler for ServerErrorHandler {
@@ -12,7 +12,7 @@ impl ResponseHandler for ServerErrorHandler {
         let status = response.status();
 
         // Handle specific server errors that indicate temporary issues
-        // and should trigger key rotation instead of returning error to client
+        // and should be retried instead of returning error to client
         if matches!(
             status,
             StatusCode::INTERNAL_SERVER_ERROR |  // 500
@@ -25,12 +25,12 @@ impl ResponseHandler for ServerErrorHandler {
             warn!(
                 status = status.as_u16(),
                 response_body = %body_text,
-                "Server error detected, will retry with next key"
+                "Server error detected, will retry"
             );
 
-            // For server errors, we want to try a different key
-            // These are typically temporary issues on the provider side
-            return Some(Action::RetryNextKey);
+            // These are typically temporary issues on the provider side,
+            // such as an overloaded model, not problems with the key
+            return Some(Action::RetryTransient);
         }
 
         None
@@ -60,7 +60,7 @@ mod tests {
         );
 
         let action = handler.handle(&response, &body, "test_key");
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
@@ -69,7 +69,7 @@ mod tests {
         let (response, body) = create_test_response(StatusCode::BAD_GATEWAY, "Bad Gateway");
 
         let action = handler.handle(&response, &body, "test_key");
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
@@ -79,7 +79,7 @@ mod tests {
             create_test_response(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable");
 
         let action = handler.handle(&response, &body, "test_key");
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
@@ -88,7 +88,7 @@ mod tests {
         let (response, body) = create_test_response(StatusCode::GATEWAY_TIMEOUT, "Gateway Timeout");
 
         let action = handler.handle(&response, &body, "test_key");
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
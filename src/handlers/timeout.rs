@@ -18,12 +18,12 @@ impl ResponseHandler for TimeoutHandler {
         ) {
             warn!(
                 status = status.as_u16(),
-                "Timeout error detected, will retry with next key"
+                "Timeout error detected, will retry"
             );
 
-            // For timeout errors, we want to try a different key
-            // but not block the current key permanently
-            return Some(Action::RetryNextKey);
+            // A timeout says nothing about the key, so it is not counted
+            // as a key failure
+            return Some(Action::RetryTransient);
         }
 
         // Check for timeout indications in server error responses
@@ -32,10 +32,10 @@ impl ResponseHandler for TimeoutHandler {
             if body_text.contains("timeout") || body_text.contains("timed out") {
                 info!(
                     status = status.as_u16(),
-                    "Server error with timeout indication, retrying with next key"
+                    "Server error with timeout indication, will retry"
                 );
 
-                return Some(Action::RetryNextKey);
+                return Some(Action::RetryTransient);
             }
         }
 
@@ -64,7 +64,7 @@ mod tests {
 
         let action = handler.handle(&response, &body, "test_key");
 
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
@@ -74,7 +74,7 @@ mod tests {
 
         let action = handler.handle(&response, &body, "test_key");
 
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
@@ -87,7 +87,7 @@ mod tests {
 
         let action = handler.handle(&response, &body, "test_key");
 
-        assert!(matches!(action, Some(Action::RetryNextKey)));
+        assert!(matches!(action, Some(Action::RetryTransient)));
     }
 
     #[test]
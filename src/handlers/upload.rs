@@ -0,0 +1,272 @@
+// src/handlers/upload.rs
+
+//! Resumable uploads to the Files API (`/upload/v1beta/files`).
+//!
+//! The start request opens an upload session and Google answers with an
+//! `X-Goog-Upload-URL` carrying an `upload_id`. The chunk and finalize requests
+//! sent to that URL only work with the key that started the session, so the
+//! session is recorded as resource `uploads/{upload_id}` owned by that key and
+//! the URL is rewritten to point back at the proxy. Bodies are streamed to the
+//! target as they arrive instead of being buffered.
+
+use super::{affinity, base::Action, proxy_loop, RequestContext};
+use crate::{
+    config::ServerConfig,
+    error::{AppError, Result},
+    group_selection,
+    key_manager::{FlattenedKeyInfo, KeyManager},
+    proxy,
+    state::AppState,
+};
+use axum::{
+    body::Bytes,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Uri},
+    response::Response,
+};
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use tracing::{info, instrument, warn};
+use url::Url;
+
+/// Response header with the session URL for the following upload requests.
+const UPLOAD_URL_HEADER: &str = "x-goog-upload-url";
+/// Response header whose value is `final` once the upload has completed.
+const UPLOAD_STATUS_HEADER: &str = "x-goog-upload-status";
+const UPLOAD_ID_PARAM: &str = "upload_id";
+
+/// Returns the upload session a request belongs to, if any.
+fn upload_id(uri: &Uri) -> Option<String> {
+    url::form_urlencoded::parse(uri.query()?.as_bytes())
+        .find(|(name, _)| name == UPLOAD_ID_PARAM)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn session_resource(upload_id: &str) -> String {
+    format!("uploads/{upload_id}")
+}
+
+/// Returns the origin the client used to reach the proxy: the configured
+/// public base URL, or the `Host` header. `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` are only honored when the reverse proxy is trusted.
+fn proxy_origin(server: &ServerConfig, headers: &HeaderMap) -> Option<String> {
+    if let Some(base_url) = &server.public_base_url {
+        return Some(base_url.trim_end_matches('/').to_string());
+    }
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let forwarded = |name: &str| header(name).filter(|_| server.trust_forwarded_headers);
+    let host = forwarded("x-forwarded-host").or_else(|| header("host"))?;
+    let scheme = forwarded("x-forwarded-proto").unwrap_or("http");
+    Some(format!("{scheme}://{host}"))
+}
+
+/// Points an upstream session URL at `origin` and drops the API key from it.
+fn rewrite_upload_url(upstream: &str, origin: &str) -> Option<HeaderValue> {
+    let upstream = Url::parse(upstream).ok()?;
+    let mut rewritten = Url::parse(origin).ok()?.join(upstream.path()).ok()?;
+    let pairs: Vec<(String, String)> = upstream
+        .query_pairs()
+        .filter(|(name, _)| name != "key")
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if !pairs.is_empty() {
+        rewritten.query_pairs_mut().extend_pairs(pairs);
+    }
+    HeaderValue::from_str(rewritten.as_str()).ok()
+}
+
+/// Returns the key that started the upload session `upload_id`.
+async fn session_key(state: &Arc<AppState>, upload_id: &str) -> Result<FlattenedKeyInfo> {
+    let key_manager = state.key_manager.read().await;
+    let owner = key_manager
+        .get_resource_owner(&session_resource(upload_id))
+        .await?;
+    let key_info = match owner {
+        Some(owner) => key_manager.get_all_key_info().await.remove(&owner),
+        None => None,
+    };
+    key_info.ok_or_else(|| AppError::InvalidRequest {
+        message: format!("Unknown or expired upload session '{upload_id}'"),
+    })
+}
+
+/// Records the key of a newly started session and rewrites its upload URL.
+async fn start_session(
+    state: &Arc<AppState>,
+    request_headers: &HeaderMap,
+    key_info: &FlattenedKeyInfo,
+    mut response: Response,
+) -> Result<Response> {
+    let Some(upstream_url) = response
+        .headers()
+        .get(UPLOAD_URL_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+    else {
+        return Ok(response);
+    };
+    let Some(upload_id) = Url::parse(&upstream_url).ok().and_then(|url| {
+        url.query_pairs()
+            .find(|(name, _)| name == UPLOAD_ID_PARAM)
+            .map(|(_, value)| value.into_owned())
+    }) else {
+        warn!("Upload URL without upload_id; returning it unchanged");
+        return Ok(response);
+    };
+
+    state
+        .key_manager
+        .read()
+        .await
+        .set_resource_owner(&session_resource(&upload_id), key_info.key.expose_secret())
+        .await?;
+    info!(
+        upload_id,
+        key.preview = %KeyManager::preview_key(&key_info.key),
+        "Started resumable upload session"
+    );
+
+    let origin = proxy_origin(&state.config.read().await.server, request_headers);
+    match origin.and_then(|o| rewrite_upload_url(&upstream_url, &o)) {
+        Some(rewritten) => {
+            response.headers_mut().insert(UPLOAD_URL_HEADER, rewritten);
+        }
+        None => warn!("Could not determine proxy origin; upload URL left unchanged"),
+    }
+    Ok(response)
+}
+
+/// Proxies the start, chunk and finalize requests of a resumable upload. The
+/// body cannot be replayed, so a failed request is not retried with another key.
+#[instrument(skip_all, fields(uri = %req.uri(), method = %req.method()))]
+pub async fn upload_handler(State(state): State<Arc<AppState>>, req: Request) -> Result<Response> {
+    let (parts, body) = req.into_parts();
+    let session = upload_id(&parts.uri);
+    let key_info = match &session {
+        Some(upload_id) => session_key(&state, upload_id).await?,
+        // Files are not tied to a model; only a client-selected group applies.
+        None => {
+            let group_name =
+                group_selection::resolve(&*state.config.read().await, None, &parts.headers)?;
+            state
+                .key_manager
+                .read()
+                .await
+                .get_next_available_key_info_for_model(group_name.as_deref(), None)
+                .await?
+                .ok_or(AppError::NoHealthyKeys)?
+        }
+    };
+
+    let url = super::build_target_url(&parts.uri, &key_info)?;
+    let client = state.get_client(key_info.proxy_url.as_deref()).await?;
+    let response = match proxy::forward_streaming_request(
+        &client,
+        &key_info,
+        parts.method.clone(),
+        url,
+        parts.headers.clone(),
+        body,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            state
+                .circuit_breakers
+                .record_failure(key_info.key.expose_secret(), None)
+                .await;
+            return Err(e);
+        }
+    };
+    // Uploads are not tied to a model, so only the key's breaker applies.
+    if !response.status().is_success() {
+        let (action, final_response) = state
+            .response_processor
+            .process(response, &key_info)
+            .await?;
+        proxy_loop::record_breaker_outcome(&state, &key_info, "", &action).await;
+        return match action {
+            Action::ReturnToClient(resp) | Action::Terminal(resp) => Ok(resp),
+            action => {
+                proxy_loop::mark_key(&state, &key_info, action).await?;
+                Ok(final_response)
+            }
+        };
+    }
+    state
+        .circuit_breakers
+        .record_success(key_info.key.expose_secret(), None)
+        .await;
+
+    let Some(upload_id) = session else {
+        return start_session(&state, &parts.headers, &key_info, response).await;
+    };
+    let finished = response
+        .headers()
+        .get(UPLOAD_STATUS_HEADER)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"final"));
+    if !finished {
+        return Ok(response);
+    }
+
+    state
+        .key_manager
+        .read()
+        .await
+        .remove_resource_owner(&session_resource(&upload_id))
+        .await?;
+    // The finalize response describes the new file; record it for its key.
+    let empty_body = Bytes::new();
+    let req_context = RequestContext {
+        method: &parts.method,
+        uri: &parts.uri,
+        headers: &parts.headers,
+        body: &empty_body,
+        json: None,
+    };
+    affinity::record_owners(&state, &req_context, &key_info, response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_url_points_back_at_proxy_without_key() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("internal:8080"));
+        headers.insert(
+            "x-forwarded-host",
+            HeaderValue::from_static("proxy.example"),
+        );
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        let mut server = ServerConfig::default();
+        assert_eq!(
+            proxy_origin(&server, &headers).as_deref(),
+            Some("http://internal:8080"),
+            "forwarded headers are ignored unless trusted"
+        );
+        server.trust_forwarded_headers = true;
+        let origin = proxy_origin(&server, &headers).unwrap();
+        server.public_base_url = Some("https://public.example/".to_string());
+        assert_eq!(
+            proxy_origin(&server, &headers).as_deref(),
+            Some("https://public.example")
+        );
+
+        let rewritten = rewrite_upload_url(
+            "https://generativelanguage.googleapis.com/upload/v1beta/files?key=secret&upload_id=abc&upload_protocol=resumable",
+            &origin,
+        )
+        .unwrap();
+        assert_eq!(
+            rewritten,
+            "https://proxy.example/upload/v1beta/files?upload_id=abc&upload_protocol=resumable"
+        );
+        assert_eq!(
+            upload_id(&"/upload/v1beta/files?upload_id=abc".parse().unwrap()).as_deref(),
+            Some("abc")
+        );
+    }
+}
@@ -0,0 +1,182 @@
+// src/hedging.rs
+
+//! Bookkeeping for hedged requests.
+//!
+//! Tracks recent successful latencies per model to derive the hedge delay
+//! (p95 by default) and enforces the configured share of hedged traffic.
+//! The request race itself lives in `handlers::proxy_loop`.
+
+use crate::config::HedgingConfig;
+use axum::http::Method;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Number of recent latency samples kept per model.
+const LATENCY_WINDOW: usize = 256;
+
+const HEDGE_PERCENTILE: f64 = 0.95;
+
+/// Gemini methods that are safe to send twice: they create nothing upstream.
+const HEDGEABLE_METHODS: [&str; 4] = [
+    ":generateContent",
+    ":embedContent",
+    ":batchEmbedContents",
+    ":countTokens",
+];
+
+/// OpenAI-compatible endpoints that are safe to send twice.
+const HEDGEABLE_OPENAI_PATHS: [&str; 2] = ["/chat/completions", "/embeddings"];
+
+/// Returns whether a request may be hedged. Only idempotent generate, embed
+/// and count calls qualify; anything else could create a duplicate resource.
+pub fn is_hedgeable(method: &Method, path: &str) -> bool {
+    if method != Method::POST {
+        return false;
+    }
+    let path = path.trim_end_matches('/');
+    HEDGEABLE_METHODS.iter().any(|m| path.ends_with(m))
+        || HEDGEABLE_OPENAI_PATHS.iter().any(|p| path.ends_with(p))
+}
+
+/// Shared hedging state, one per `AppState`.
+#[derive(Debug, Default)]
+pub struct HedgeController {
+    latencies: Mutex<HashMap<String, VecDeque<Duration>>>,
+    requests: AtomicU64,
+    hedges: AtomicU64,
+}
+
+impl HedgeController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the hedge delay for `model`, or `None` if the model is not
+    /// eligible for hedging. Also counts the request towards the hedge budget.
+    pub fn hedge_delay(&self, config: &HedgingConfig, model: &str) -> Option<Duration> {
+        if !config.models.is_empty() && !config.models.iter().any(|m| m == model) {
+            return None;
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(delay_ms) = config.delay_ms {
+            return Some(Duration::from_millis(delay_ms));
+        }
+        Some(
+            self.percentile(model, HEDGE_PERCENTILE, config.min_samples)
+                .unwrap_or(Duration::from_millis(config.fallback_delay_ms)),
+        )
+    }
+
+    /// Reserves a hedge if doing so keeps hedged traffic within
+    /// `max_hedge_ratio` of eligible requests.
+    pub fn try_acquire(&self, config: &HedgingConfig) -> bool {
+        let requests = self.requests.load(Ordering::Relaxed) as f64;
+        self.hedges
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |hedges| {
+                ((hedges + 1) as f64 <= requests * config.max_hedge_ratio).then_some(hedges + 1)
+            })
+            .is_ok()
+    }
+
+    /// Records the latency of a successful upstream response.
+    pub fn record_latency(&self, model: &str, latency: Duration) {
+        let mut latencies = self.latencies.lock();
+        let samples = latencies.entry(model.to_string()).or_default();
+        if samples.len() == LATENCY_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    /// Returns the `quantile` latency for `model` once `min_samples` exist.
+    pub fn percentile(&self, model: &str, quantile: f64, min_samples: usize) -> Option<Duration> {
+        let latencies = self.latencies.lock();
+        let samples = latencies.get(model)?;
+        if samples.is_empty() || samples.len() < min_samples {
+            return None;
+        }
+        let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 * quantile).ceil() as usize).clamp(1, sorted.len()) - 1;
+        Some(sorted[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_uses_p95_after_min_samples() {
+        let controller = HedgeController::new();
+        let config = HedgingConfig {
+            min_samples: 20,
+            fallback_delay_ms: 1500,
+            ..Default::default()
+        };
+        for ms in 1..=19 {
+            controller.record_latency("m", Duration::from_millis(ms * 10));
+        }
+        assert_eq!(
+            controller.hedge_delay(&config, "m"),
+            Some(Duration::from_millis(1500))
+        );
+
+        controller.record_latency("m", Duration::from_millis(200));
+        assert_eq!(
+            controller.hedge_delay(&config, "m"),
+            Some(Duration::from_millis(190))
+        );
+    }
+
+    #[test]
+    fn test_only_idempotent_calls_are_hedgeable() {
+        let post = Method::POST;
+        assert!(is_hedgeable(
+            &post,
+            "/v1beta/models/gemini-2.0-flash:generateContent"
+        ));
+        assert!(is_hedgeable(&post, "/v1beta/models/m:batchEmbedContents"));
+        assert!(is_hedgeable(&post, "/v1beta/models/m:countTokens"));
+        assert!(is_hedgeable(&post, "/v1/chat/completions"));
+        assert!(is_hedgeable(&post, "/v1beta/openai/embeddings"));
+
+        assert!(!is_hedgeable(
+            &Method::GET,
+            "/v1beta/models/m:generateContent"
+        ));
+        assert!(!is_hedgeable(
+            &post,
+            "/v1beta/models/m:streamGenerateContent"
+        ));
+        assert!(!is_hedgeable(
+            &post,
+            "/v1beta/models/m:batchGenerateContent"
+        ));
+        assert!(!is_hedgeable(&post, "/v1beta/models/m:predictLongRunning"));
+        assert!(!is_hedgeable(&post, "/v1beta/files"));
+        assert!(!is_hedgeable(&post, "/v1beta/cachedContents"));
+        assert!(!is_hedgeable(&post, "/v1beta/batches/123:cancel"));
+    }
+
+    #[test]
+    fn test_model_filter_and_budget() {
+        let controller = HedgeController::new();
+        let config = HedgingConfig {
+            delay_ms: Some(50),
+            max_hedge_ratio: 0.5,
+            models: vec!["fast".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(controller.hedge_delay(&config, "slow"), None);
+        assert!(!controller.try_acquire(&config), "no eligible traffic yet");
+
+        controller.hedge_delay(&config, "fast");
+        controller.hedge_delay(&config, "fast");
+        assert!(controller.try_acquire(&config));
+        assert!(!controller.try_acquire(&config), "budget is 1 of 2");
+    }
+}
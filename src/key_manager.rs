@@ -1,6 +1,7 @@
 // src/key_manager.rs
 // Refactored key manager with clear separation of concerns
 
+use crate::circuit_breaker::{CircuitBreakerConfig, CircuitBreakerRegistry};
 use crate::config::AppConfig;
 use crate::core::KeySelector;
 use crate::error::Result;
@@ -59,6 +60,16 @@ pub trait KeyManagerTrait: Send + Sync {
         group_name: Option<&str>,
     ) -> Result<Option<FlattenedKeyInfo>>;
 
+    /// Like `get_next_available_key_info`, but also skips keys whose circuit
+    /// breaker for `model` is open.
+    async fn get_next_available_key_info_for_model(
+        &self,
+        group_name: Option<&str>,
+        _model: Option<&str>,
+    ) -> Result<Option<FlattenedKeyInfo>> {
+        self.get_next_available_key_info(group_name).await
+    }
+
     async fn handle_api_failure(&self, api_key: &str, is_terminal: bool) -> Result<()>;
 
     async fn handle_rate_limit(&self, api_key: &str, duration: Duration) -> Result<()>;
@@ -67,6 +78,14 @@ pub trait KeyManagerTrait: Send + Sync {
 
     async fn get_all_key_info(&self) -> HashMap<String, FlattenedKeyInfo>;
 
+    async fn set_resource_owner(&self, resource: &str, api_key: &str) -> Result<()>;
+
+    async fn get_resource_owner(&self, resource: &str) -> Result<Option<String>>;
+
+    async fn remove_resource_owner(&self, resource: &str) -> Result<()>;
+
+    async fn get_resource_owners(&self) -> Result<HashMap<String, String>>;
+
     async fn reload(&mut self, config: &AppConfig, redis_pool: Option<Pool>) -> Result<()>;
 }
 
@@ -75,6 +94,7 @@ pub struct KeyManager {
     store: Arc<dyn KeyStore>,
     key_info_map: Arc<HashMap<String, FlattenedKeyInfo>>,
     selector: KeySelector,
+    circuit_breakers: Arc<CircuitBreakerRegistry>,
     max_failures_threshold: u32,
 }
 
@@ -97,17 +117,34 @@ impl KeyManager {
             }
         };
 
-        let selector = KeySelector::with_round_robin();
+        let circuit_breakers = Arc::new(CircuitBreakerRegistry::new(Self::circuit_breaker_config(
+            config,
+        )));
+        let selector = KeySelector::with_round_robin(circuit_breakers.clone());
 
         trace!("KeyManager::new finished");
         Ok(Self {
             store,
             key_info_map: Arc::new(key_info_map),
             selector,
+            circuit_breakers,
             max_failures_threshold: config.max_failures_threshold.unwrap_or(3),
         })
     }
 
+    /// Returns the per-key circuit breakers consulted during key selection.
+    pub fn circuit_breakers(&self) -> Arc<CircuitBreakerRegistry> {
+        self.circuit_breakers.clone()
+    }
+
+    fn circuit_breaker_config(config: &AppConfig) -> CircuitBreakerConfig {
+        config
+            .circuit_breaker
+            .as_ref()
+            .map(CircuitBreakerConfig::from)
+            .unwrap_or_default()
+    }
+
     fn build_key_info_map(config: &AppConfig) -> HashMap<String, FlattenedKeyInfo> {
         config
             .groups
@@ -152,6 +189,15 @@ impl KeyManagerTrait for KeyManager {
     async fn get_next_available_key_info(
         &self,
         group_name: Option<&str>,
+    ) -> Result<Option<FlattenedKeyInfo>> {
+        self.get_next_available_key_info_for_model(group_name, None)
+            .await
+    }
+
+    async fn get_next_available_key_info_for_model(
+        &self,
+        group_name: Option<&str>,
+        model: Option<&str>,
     ) -> Result<Option<FlattenedKeyInfo>> {
         trace!("get_next_available_key_info: start");
 
@@ -174,7 +220,12 @@ impl KeyManagerTrait for KeyManager {
 
         match self
             .selector
-            .select_available_key(candidate_keys.as_slice(), group_id, self.store.clone())
+            .select_available_key(
+                candidate_keys.as_slice(),
+                group_id,
+                model,
+                self.store.clone(),
+            )
             .await?
         {
             Some(key_info) => Ok(Some(key_info)),
@@ -210,15 +261,46 @@ impl KeyManagerTrait for KeyManager {
         self.key_info_map.as_ref().clone()
     }
 
+    async fn set_resource_owner(&self, resource: &str, api_key: &str) -> Result<()> {
+        self.store.set_resource_owner(resource, api_key).await
+    }
+
+    async fn get_resource_owner(&self, resource: &str) -> Result<Option<String>> {
+        self.store.get_resource_owner(resource).await
+    }
+
+    async fn remove_resource_owner(&self, resource: &str) -> Result<()> {
+        self.store.remove_resource_owner(resource).await
+    }
+
+    async fn get_resource_owners(&self) -> Result<HashMap<String, String>> {
+        self.store.get_resource_owners().await
+    }
+
     async fn reload(&mut self, config: &AppConfig, redis_pool: Option<Pool>) -> Result<()> {
         info!("Reloading KeyManager state from new configuration...");
         let new_key_info_map = Self::build_key_info_map(config);
 
         let new_store: Arc<dyn KeyStore> = match redis_pool {
             Some(pool) => Arc::new(RedisStore::new(pool, config, &new_key_info_map).await?),
-            None => Arc::new(InMemoryStore::new(&new_key_info_map)),
+            None => {
+                // Resource owners only live in memory here; carry over those
+                // whose key is still configured. Their retention restarts.
+                let store = InMemoryStore::new(&new_key_info_map);
+                for (resource, api_key) in self.store.get_resource_owners().await? {
+                    if new_key_info_map.contains_key(&api_key) {
+                        store.set_resource_owner(&resource, &api_key).await?;
+                    }
+                }
+                Arc::new(store)
+            }
         };
 
+        self.circuit_breakers
+            .reconfigure(Self::circuit_breaker_config(config), |api_key| {
+                new_key_info_map.contains_key(api_key)
+            });
+
         self.store = new_store;
         self.key_info_map = Arc::new(new_key_info_map);
         self.max_failures_threshold = config.max_failures_threshold.unwrap_or(3);
@@ -7,27 +7,43 @@ pub mod storage;
 
 // --- Application Modules ---
 pub mod admin;
+pub mod batch_files;
+pub mod cache;
+pub mod catalog;
 pub mod circuit_breaker;
+pub mod coalesce;
 pub mod config;
 pub mod error;
+pub mod group_selection;
 pub mod handlers;
+pub mod hedging;
 pub mod key_manager;
 pub mod metrics;
 pub mod middleware;
 pub mod monitoring;
+pub mod priority;
 pub mod proxy;
+pub mod request_rules;
+pub mod retry_budget;
 pub mod security;
 pub mod state;
+pub mod token_limit;
 pub mod tokenizer;
+pub mod translation;
+pub mod usage;
 pub mod utils;
+pub mod wait_queue;
 
 // --- Dependencies and Re-exports ---
-use crate::handlers::{health_check, proxy_handler};
+use crate::handlers::{
+    anthropic::messages_handler, health_check, live, models, ollama, proxy_handler, upload,
+};
 use axum::{
     body::Body,
+    extract::DefaultBodyLimit,
     http::{HeaderValue, Request as AxumRequest},
     response::IntoResponse,
-    routing::{any, get},
+    routing::{any, get, post},
     Router,
 };
 use std::{
@@ -50,21 +66,49 @@ pub fn create_router(state: Arc<AppState>) -> Router {
     let proxy_routes = [
         "/v1/*path",
         "/v1beta/*path",
+        "/download/*path",
         "/chat/*path",
         "/embeddings",
-        "/models",
     ];
 
     let mut router = Router::new()
         .route("/health", get(health_check))
         .route("/metrics", get(metrics::metrics_handler))
+        // Anthropic Messages API; the static route takes precedence over `/v1/*path`.
+        .route("/v1/messages", post(messages_handler))
+        // Ollama-compatible API
+        .route("/api/chat", post(ollama::chat_handler))
+        .route("/api/generate", post(ollama::generate_handler))
+        .route("/api/embed", post(ollama::embed_handler))
+        .route("/api/tags", get(ollama::tags_handler))
+        // Live API WebSocket sessions
+        .route("/ws/*path", get(live::live_handler))
+        // Resumable Files API uploads; bodies are streamed, not buffered.
+        .route("/upload/*path", any(upload::upload_handler))
+        // Aggregated model catalog; other methods fall through to the proxy.
+        .route(
+            "/models",
+            get(models::openai_models_handler).fallback(proxy_handler),
+        )
+        .route(
+            "/v1/models",
+            get(models::openai_models_handler).fallback(proxy_handler),
+        )
+        .route(
+            "/v1beta/models",
+            get(models::gemini_models_handler).fallback(proxy_handler),
+        )
         .merge(admin::admin_routes(state.clone()));
 
     for path in proxy_routes {
         router = router.route(path, any(proxy_handler));
     }
 
-    router.layer(CookieManagerLayer::new()).with_state(state)
+    // Body sizes are limited by `request_size_limit_middleware` instead.
+    router
+        .layer(DefaultBodyLimit::disable())
+        .layer(CookieManagerLayer::new())
+        .with_state(state)
 }
 
 /// Middleware for adding Request ID and request tracing.
@@ -132,6 +176,7 @@ pub async fn run(
     // 3. Application state initialization
     let (app_state, mut config_update_rx) =
         build_application_state(&app_config, &config_path).await?;
+    catalog::spawn_refresh_task(app_state.clone());
 
     // 3. Start background handler for configuration updates
     let state_for_worker = app_state.clone();
@@ -156,8 +201,9 @@ pub async fn run(
     });
 
     // 4. Router and middleware setup
-    let app = create_router(app_state)
-        .layer(axum::middleware::from_fn(
+    let app = create_router(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            app_state,
             crate::middleware::request_size_limit_middleware,
         ))
         .layer(axum::middleware::from_fn(trace_requests))
@@ -1,10 +1,17 @@
 use anyhow::Result;
 use gemini_proxy::{
-    cli::{Cli, Commands, GenerateCommands, KeyCommands},
+    batch_files,
+    cli::{BatchFileCommands, Cli, Commands, GenerateCommands, KeyCommands},
     error::{context::ErrorContext, AppError},
     run,
 };
-use std::{io::ErrorKind, net::SocketAddr, path::PathBuf, process};
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind},
+    net::SocketAddr,
+    path::PathBuf,
+    process,
+};
 use tokio::{net::TcpListener, signal};
 use tracing::{error, info};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
@@ -297,6 +304,57 @@ async fn generate_command(template: GenerateCommands) -> Result<()> {
     Ok(())
 }
 
+/// Split batch input files or merge result files; nothing is submitted
+async fn batch_files_command(config: Option<PathBuf>, action: BatchFileCommands) -> Result<()> {
+    match action {
+        BatchFileCommands::Split {
+            input,
+            parts,
+            output_dir,
+        } => {
+            let parts = match parts {
+                Some(parts) => parts,
+                None => {
+                    let config_path = config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+                    let config = gemini_proxy::config::load_config(&config_path)?;
+                    config.groups.iter().map(|g| g.api_keys.len()).sum()
+                }
+            };
+            let chunks = batch_files::split_jsonl(BufReader::new(File::open(&input)?), parts)?;
+
+            let output_dir = output_dir
+                .or_else(|| input.parent().map(PathBuf::from))
+                .unwrap_or_default();
+            let stem = input
+                .file_stem()
+                .map_or_else(|| "batch".into(), |s| s.to_string_lossy());
+            for (i, lines) in chunks.iter().enumerate() {
+                let path = output_dir.join(format!("{stem}.part-{}.jsonl", i + 1));
+                std::fs::write(&path, lines.join("\n") + "\n")?;
+                println!("{} ({} requests)", path.display(), lines.len());
+            }
+        }
+        BatchFileCommands::Merge {
+            inputs,
+            output,
+            order_by,
+        } => {
+            let order = match order_by {
+                Some(path) => Some(batch_files::input_keys(BufReader::new(File::open(path)?))?),
+                None => None,
+            };
+            let readers = inputs
+                .iter()
+                .map(|path| File::open(path).map(BufReader::new))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let lines = batch_files::merge_jsonl(readers, order.as_deref())?;
+            std::fs::write(&output, lines.join("\n") + "\n")?;
+            println!("{} ({} results)", output.display(), lines.len());
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse_args();
@@ -331,6 +389,7 @@ async fn main() -> Result<()> {
         }) => health_command(url, detailed, timeout).await,
         Some(Commands::Keys { action }) => keys_command(action).await,
         Some(Commands::Generate { template }) => generate_command(template).await,
+        Some(Commands::BatchFiles { action }) => batch_files_command(cli.config, action).await,
         None => {
             // Default to serve command
             serve_command(cli.config, cli.host, cli.port, false, None).await
@@ -8,6 +8,7 @@ pub mod exporters;
 pub use exporters::metrics_handler;
 pub mod middleware;
 
+use crate::usage::{UsageCounts, UsageLabels};
 use metrics::{counter, gauge, histogram, Counter, Gauge, Histogram};
 use once_cell::sync::Lazy;
 use std::time::{Duration, Instant};
@@ -174,6 +175,97 @@ impl MetricsRegistry {
         }
     }
 
+    /// Record response cache lookup
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        counter!("gemini_proxy_response_cache_total", "result" => result).increment(1);
+    }
+
+    /// Record a request that joined an identical in-flight upstream call
+    pub fn record_coalesced_request(&self) {
+        counter!("gemini_proxy_coalesced_requests_total").increment(1);
+    }
+
+    /// Record the outcome of a hedged request
+    pub fn record_hedge(&self, model: String, won: bool) {
+        let result = if won { "won" } else { "lost" };
+        counter!("gemini_proxy_hedged_requests_total", "model" => model, "result" => result)
+            .increment(1);
+    }
+
+    /// Record a same-key retry after a transient upstream error
+    pub fn record_transient_retry(&self, model: String, status: u16) {
+        counter!("gemini_proxy_transient_retries_total", "model" => model, "status" => status.to_string())
+            .increment(1);
+    }
+
+    /// Set the number of requests waiting for a key in a group
+    pub fn set_key_wait_queue_depth(&self, group: String, depth: usize) {
+        gauge!("gemini_proxy_key_wait_queue_depth", "group" => group).set(depth as f64);
+    }
+
+    /// Record how long a request waited for a key and how the wait ended
+    pub fn record_key_wait(&self, group: String, waited: Duration, outcome: &'static str) {
+        histogram!("gemini_proxy_key_wait_seconds", "group" => group, "outcome" => outcome)
+            .record(waited.as_secs_f64());
+    }
+
+    /// Record a request admitted or shed for a priority class
+    pub fn record_priority_request(&self, class: &'static str, outcome: &'static str) {
+        counter!("gemini_proxy_priority_requests_total", "class" => class, "outcome" => outcome)
+            .increment(1);
+    }
+
+    /// Set the number of upstream requests in flight for a priority class
+    pub fn set_priority_in_flight(&self, class: &'static str, count: usize) {
+        gauge!("gemini_proxy_priority_in_flight", "class" => class).set(count as f64);
+    }
+
+    /// Record a finished Live API session and the bytes relayed each way
+    pub fn record_live_session(
+        &self,
+        model: String,
+        outcome: &'static str,
+        duration: Duration,
+        bytes_from_client: u64,
+        bytes_from_upstream: u64,
+    ) {
+        counter!("gemini_proxy_live_sessions_total", "model" => model.clone(), "outcome" => outcome)
+            .increment(1);
+        histogram!("gemini_proxy_live_session_duration_seconds", "model" => model.clone())
+            .record(duration.as_secs_f64());
+        counter!("gemini_proxy_live_bytes_total", "model" => model.clone(), "direction" => "client")
+            .increment(bytes_from_client);
+        counter!("gemini_proxy_live_bytes_total", "model" => model, "direction" => "upstream")
+            .increment(bytes_from_upstream);
+    }
+
+    /// Record the token usage reported by an upstream response
+    pub fn record_usage(&self, labels: &UsageLabels, usage: &UsageCounts) {
+        let label_set = [
+            ("key", labels.key.clone()),
+            ("group", labels.group.clone()),
+            ("model", labels.model.clone()),
+            ("client", labels.client.clone()),
+        ];
+        counter!("gemini_proxy_usage_requests_total", &label_set).increment(usage.requests);
+        for (kind, tokens) in [
+            ("prompt", usage.prompt_tokens),
+            ("candidates", usage.candidates_tokens),
+            ("cached", usage.cached_tokens),
+            ("thoughts", usage.thoughts_tokens),
+        ] {
+            let mut kind_labels = label_set.to_vec();
+            kind_labels.push(("kind", kind.to_string()));
+            counter!("gemini_proxy_usage_tokens_total", &kind_labels).increment(tokens);
+        }
+        // Counters only hold integers, so cost is counted in millionths.
+        let cost_micros = (usage.cost * 1_000_000.0).round() as u64;
+        if cost_micros > 0 {
+            counter!("gemini_proxy_usage_cost_micros_total", &label_set).increment(cost_micros);
+        }
+    }
+
     /// Record Redis operation
     pub fn record_redis_operation(&self, operation: String, success: bool) {
         self.redis_operations_total.increment(1);
@@ -1,63 +1,148 @@
 // src/middleware/request_size_limit.rs
 
-use axum::{body::Body, extract::Request, http::StatusCode, middleware::Next, response::Response};
+use crate::{error::AppError, handlers::parsed::model_from_path, state::AppState};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 use tracing::warn;
 
-const MAX_REQUEST_SIZE: usize = 10 * 1024 * 1024; // 10MB limit
+/// Path prefix of Files API uploads, whose bodies are streamed to the target.
+const UPLOAD_PATH_PREFIX: &str = "/upload/";
 
-/// Middleware to limit request body size to prevent DoS attacks
+/// Middleware to limit request body size to prevent DoS attacks.
+///
+/// A declared `Content-Length` above the limit is rejected up front. Bodies
+/// without one, such as chunked uploads, are counted as they stream in and
+/// the request fails once the limit is crossed. Uploads are only limited by
+/// a matching entry in `server.max_request_size_by_route`.
+///
+/// Compressed bodies are forwarded as they are, so the limit applies to
+/// their compressed bytes.
 pub async fn request_size_limit_middleware(
+    State(state): State<Arc<AppState>>,
     request: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
-    // Only check Content-Length for methods that typically have bodies
-    let method = request.method();
-    if matches!(
-        method,
-        &axum::http::Method::POST | &axum::http::Method::PUT | &axum::http::Method::PATCH
+) -> Response {
+    if !matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH
     ) {
-        // Check Content-Length header if present
-        if let Some(content_length) = request.headers().get("content-length") {
-            if let Ok(length_str) = content_length.to_str() {
-                if let Ok(length) = length_str.parse::<usize>() {
-                    if length > MAX_REQUEST_SIZE {
-                        warn!(
-                            content_length = length,
-                            max_size = MAX_REQUEST_SIZE,
-                            method = %method,
-                            "Request rejected: body size exceeds limit"
-                        );
-                        return Err(StatusCode::PAYLOAD_TOO_LARGE);
-                    }
-                }
+        return next.run(request).await;
+    }
+
+    let path = request.uri().path();
+    let max_size = {
+        let config = state.config.read().await;
+        if path.starts_with(UPLOAD_PATH_PREFIX) {
+            match config.route_max_request_size(path) {
+                Some(limit) => limit,
+                None => return next.run(request).await,
             }
+        } else {
+            config.max_request_size_for(path, model_from_path(path))
+        }
+    };
+
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    match content_length {
+        Some(length) if length > max_size => {
+            warn!(
+                content_length = length,
+                max_size,
+                method = %request.method(),
+                "Request rejected: body size exceeds limit"
+            );
+            AppError::PayloadTooLarge { max_size }.into_response()
+        }
+        // The server reads no more than the declared length.
+        Some(_) => next.run(request).await,
+        None => {
+            let exceeded = Arc::new(AtomicBool::new(false));
+            let request = request.map(|body| limit_body(body, max_size, exceeded.clone()));
+            let response = next.run(request).await;
+            if exceeded.load(Ordering::Relaxed) {
+                warn!(max_size, "Request rejected: streamed body exceeds limit");
+                return AppError::PayloadTooLarge { max_size }.into_response();
+            }
+            response
         }
     }
+}
 
-    Ok(next.run(request).await)
+/// Wraps `body` so that reading past `max_size` bytes fails and sets
+/// `exceeded`, whichever handler or client is consuming it.
+fn limit_body(body: Body, max_size: usize, exceeded: Arc<AtomicBool>) -> Body {
+    let mut received = 0usize;
+    Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let chunk = chunk?;
+        received = received.saturating_add(chunk.len());
+        if received > max_size {
+            exceeded.store(true, Ordering::Relaxed);
+            return Err(axum::Error::new(AppError::PayloadTooLarge { max_size }));
+        }
+        Ok(chunk)
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::{body::Body, http::Method, middleware::from_fn, routing::post, Router};
+    use crate::config::AppConfig;
+    use axum::{
+        body::Bytes, extract::DefaultBodyLimit, http::StatusCode, middleware::from_fn_with_state,
+        routing::post, Router,
+    };
+    use std::convert::Infallible;
+    use tempfile::tempdir;
     use tower::ServiceExt;
 
-    async fn dummy_handler() -> &'static str {
+    async fn dummy_handler(_body: Bytes) -> &'static str {
         "OK"
     }
 
+    async fn create_test_app(config: AppConfig, route: &str) -> Router {
+        let temp_dir = tempdir().unwrap();
+        let (state, _) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+            .await
+            .unwrap();
+        Router::new()
+            .route(route, post(dummy_handler))
+            .layer(DefaultBodyLimit::disable())
+            .layer(from_fn_with_state(
+                Arc::new(state),
+                request_size_limit_middleware,
+            ))
+    }
+
+    fn chunked(chunks: usize, chunk_size: usize) -> Body {
+        let chunk = Bytes::from(vec![b'x'; chunk_size]);
+        Body::from_stream(futures_util::stream::iter(
+            (0..chunks).map(move |_| Ok::<_, Infallible>(chunk.clone())),
+        ))
+    }
+
     #[tokio::test]
     async fn test_request_size_limit_allows_small_requests() {
-        let app = Router::new()
-            .route("/test", post(dummy_handler))
-            .layer(from_fn(request_size_limit_middleware));
+        let app = create_test_app(AppConfig::default(), "/test").await;
 
         let request = Request::builder()
             .method(Method::POST)
             .uri("/test")
             .header("content-length", "1000")
-            .body(Body::empty())
+            .body(Body::from(vec![b'x'; 1000]))
             .unwrap();
 
         let response = app.oneshot(request).await.unwrap();
@@ -66,9 +151,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_request_size_limit_blocks_large_requests() {
-        let app = Router::new()
-            .route("/test", post(dummy_handler))
-            .layer(from_fn(request_size_limit_middleware));
+        let app = create_test_app(AppConfig::default(), "/test").await;
 
         let request = Request::builder()
             .method(Method::POST)
@@ -80,4 +163,86 @@ mod tests {
         let response = app.oneshot(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
     }
+
+    #[tokio::test]
+    async fn test_request_size_limit_counts_chunked_bodies() {
+        let mut config = AppConfig::default();
+        config.server.max_request_size = 4096;
+        let app = create_test_app(config, "/test").await;
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/test")
+            .body(chunked(8, 1024))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/test")
+            .body(chunked(4, 1024))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_request_size_limit_counts_compressed_bodies() {
+        let mut config = AppConfig::default();
+        config.server.max_request_size = 4096;
+        let app = create_test_app(config, "/test").await;
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/test")
+            .header("content-encoding", "gzip")
+            .header("content-length", "100")
+            .body(Body::from(vec![0x1f; 100]))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/test")
+            .header("content-encoding", "gzip")
+            .body(chunked(8, 1024))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_request_size_limit_skips_uploads() {
+        let app = create_test_app(AppConfig::default(), "/upload/v1beta/files").await;
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload/v1beta/files?upload_id=abc")
+            .body(chunked(21, 1024 * 1024))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_request_size_limit_applies_route_limit_to_uploads() {
+        let mut config = AppConfig::default();
+        config
+            .server
+            .max_request_size_by_route
+            .insert("/upload/".to_string(), 1024);
+        let app = create_test_app(config, "/upload/v1beta/files").await;
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload/v1beta/files?upload_id=abc")
+            .body(chunked(2, 1024))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
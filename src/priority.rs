@@ -0,0 +1,192 @@
+// src/priority.rs
+
+//! Request priority classes.
+//!
+//! Every request is either interactive or batch. Each class can be capped at a
+//! number of concurrent upstream requests, and while a group is short of keys
+//! batch requests leave the last `batch_reserved_keys` to interactive traffic,
+//! waiting in the key wait queue or being shed instead.
+//!
+//! Classes are chosen from request headers any caller can set, so they order
+//! traffic between trusted callers and are not an access control.
+
+use crate::config::{PriorityClass, PriorityConfig};
+use crate::error::Result;
+use crate::metrics::MetricsRegistry;
+use crate::state::AppState;
+use axum::{body::Body, http::HeaderMap, response::Response};
+use futures_util::StreamExt;
+use parking_lot::Mutex;
+use secrecy::ExposeSecret;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Request header naming the priority class (`interactive` or `batch`).
+pub const PRIORITY_HEADER: &str = "x-proxy-priority";
+
+/// Request header identifying the calling client, mapped via `priority.clients`.
+/// The id is self-declared and not authenticated.
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+
+/// Determines the class of a request. A client listed in `priority.clients`
+/// always gets its configured class; otherwise `X-Proxy-Priority` decides.
+pub fn classify(config: Option<&PriorityConfig>, headers: &HeaderMap) -> PriorityClass {
+    let Some(config) = config else {
+        return PriorityClass::default();
+    };
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(class) = header(CLIENT_ID_HEADER).and_then(|id| config.clients.get(id.trim())) {
+        return *class;
+    }
+    match header(PRIORITY_HEADER).map(|v| v.trim().to_ascii_lowercase()) {
+        Some(v) if v == "interactive" => PriorityClass::Interactive,
+        Some(v) if v == "batch" => PriorityClass::Batch,
+        _ => config.default_class,
+    }
+}
+
+/// Counts upstream requests in flight per class.
+#[derive(Debug, Default)]
+pub struct PriorityGate {
+    in_flight: Mutex<HashMap<PriorityClass, usize>>,
+}
+
+/// An admitted request, released when dropped.
+pub struct PriorityPermit {
+    gate: Arc<PriorityGate>,
+    metrics: Arc<MetricsRegistry>,
+    class: PriorityClass,
+}
+
+impl PriorityGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of admitted requests of `class` still in flight.
+    pub fn in_flight(&self, class: PriorityClass) -> usize {
+        self.in_flight.lock().get(&class).copied().unwrap_or(0)
+    }
+
+    /// Admits a request of `class` unless `limit` requests are already in flight.
+    pub fn try_admit(
+        self: &Arc<Self>,
+        metrics: &Arc<MetricsRegistry>,
+        class: PriorityClass,
+        limit: Option<usize>,
+    ) -> Option<PriorityPermit> {
+        let mut in_flight = self.in_flight.lock();
+        let count = in_flight.entry(class).or_insert(0);
+        if limit.is_some_and(|limit| *count >= limit) {
+            return None;
+        }
+        *count += 1;
+        metrics.set_priority_in_flight(class.as_str(), *count);
+        Some(PriorityPermit {
+            gate: self.clone(),
+            metrics: metrics.clone(),
+            class,
+        })
+    }
+}
+
+impl PriorityPermit {
+    /// Keeps the permit until the response body has been sent, so streaming
+    /// responses count against their class for as long as they run.
+    pub fn attach(self, response: Response) -> Response {
+        let (parts, body) = response.into_parts();
+        let stream = body.into_data_stream().map(move |chunk| {
+            let _permit = &self;
+            chunk
+        });
+        Response::from_parts(parts, Body::from_stream(stream))
+    }
+}
+
+impl Drop for PriorityPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.gate.in_flight.lock();
+        if let Some(count) = in_flight.get_mut(&self.class) {
+            *count = count.saturating_sub(1);
+            self.metrics
+                .set_priority_in_flight(self.class.as_str(), *count);
+        }
+    }
+}
+
+/// Returns whether a batch request may take a key of `group`. While some keys
+/// of the group are unavailable, the last `batch_reserved_keys` available keys
+/// are kept for interactive traffic.
+pub async fn batch_may_take_key(
+    state: &Arc<AppState>,
+    config: &PriorityConfig,
+    group: Option<&str>,
+) -> Result<bool> {
+    if config.batch_reserved_keys == 0 {
+        return Ok(true);
+    }
+    let key_manager = state.key_manager.read().await;
+    let key_info = key_manager.get_all_key_info().await;
+    let key_states = key_manager.get_key_states().await?;
+
+    let (mut total, mut available) = (0, 0);
+    for info in key_info
+        .values()
+        .filter(|info| group.map_or(true, |g| info.group_name == g))
+    {
+        total += 1;
+        if key_states
+            .get(info.key.expose_secret())
+            .map_or(true, |s| s.is_available())
+        {
+            available += 1;
+        }
+    }
+    Ok(available == total || available > config.batch_reserved_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_client_mapping_overrides_header() {
+        let config = PriorityConfig {
+            clients: HashMap::from([("nightly".to_string(), PriorityClass::Batch)]),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            classify(Some(&config), &headers),
+            PriorityClass::Interactive
+        );
+
+        headers.insert(PRIORITY_HEADER, HeaderValue::from_static("Batch"));
+        assert_eq!(classify(Some(&config), &headers), PriorityClass::Batch);
+        assert_eq!(classify(None, &headers), PriorityClass::Interactive);
+
+        headers.insert(PRIORITY_HEADER, HeaderValue::from_static("interactive"));
+        headers.insert(CLIENT_ID_HEADER, HeaderValue::from_static("nightly"));
+        assert_eq!(classify(Some(&config), &headers), PriorityClass::Batch);
+    }
+
+    #[test]
+    fn test_gate_caps_each_class() {
+        let gate = Arc::new(PriorityGate::new());
+        let metrics = Arc::new(MetricsRegistry::new());
+
+        let first = gate.try_admit(&metrics, PriorityClass::Batch, Some(1));
+        assert!(first.is_some());
+        assert!(gate
+            .try_admit(&metrics, PriorityClass::Batch, Some(1))
+            .is_none());
+        assert!(gate
+            .try_admit(&metrics, PriorityClass::Interactive, None)
+            .is_some());
+
+        drop(first);
+        assert_eq!(gate.in_flight(PriorityClass::Batch), 0);
+    }
+}
@@ -1,7 +1,6 @@
 // src/proxy.rs
 
 use crate::{
-    circuit_breaker::{CircuitBreaker, CircuitBreakerError},
     error::{AppError, Result},
     key_manager::FlattenedKeyInfo,
 };
@@ -15,7 +14,6 @@ use once_cell::sync::Lazy; // Added for efficient static HashSet
 use secrecy::ExposeSecret;
 use std::collections::HashSet; // Added for HashSet
 use std::error::Error;
-use std::sync::Arc;
 use std::time::Instant;
 use tracing::{debug, error, info, trace, warn};
 use url::Url;
@@ -35,6 +33,14 @@ static HOP_BY_HOP_HEADERS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
         "host",
         "authorization",
         "x-goog-api-key",
+        // Proxy control headers
+        "x-proxy-max-wait-ms",
+        "x-proxy-max-attempts",
+        "x-proxy-deadline-ms",
+        "x-proxy-priority",
+        "x-client-id",
+        "x-proxy-group",
+        "x-proxy-key-tier",
     ]
     .into_iter()
     .collect()
@@ -60,7 +66,6 @@ pub async fn forward_request(
     target_url: Url,
     headers: HeaderMap,
     body_bytes: Bytes,
-    circuit_breaker: Option<Arc<CircuitBreaker>>,
 ) -> Result<Response> {
     debug!(
         "Full request body: {:?}",
@@ -82,41 +87,13 @@ pub async fn forward_request(
 
     let start_time = Instant::now();
 
-    // Execute request through circuit breaker if available
-    let target_response_result = if let Some(cb) = circuit_breaker {
-        match cb
-            .call(|| async {
-                client
-                    .request(method.clone(), target_url.clone())
-                    .headers(outgoing_headers.clone())
-                    .body(outgoing_reqwest_body)
-                    .send()
-                    .await
-            })
-            .await
-        {
-            Ok(response) => Ok(response),
-            Err(cb_error) => match cb_error {
-                CircuitBreakerError::CircuitOpen => {
-                    warn!(target.url = %target_url, "Circuit breaker is open, failing fast");
-                    Err(AppError::CircuitBreakerOpen {
-                        service: target_url.to_string(),
-                    })
-                }
-                CircuitBreakerError::OperationFailed(req_error) => {
-                    Err(AppError::internal(req_error.to_string()))
-                }
-            },
-        }
-    } else {
-        client
-            .request(method, target_url.clone())
-            .headers(outgoing_headers)
-            .body(outgoing_reqwest_body)
-            .send()
-            .await
-            .map_err(|e| AppError::internal(e.to_string()))
-    };
+    let target_response_result = client
+        .request(method, target_url.clone())
+        .headers(outgoing_headers)
+        .body(outgoing_reqwest_body)
+        .send()
+        .await
+        .map_err(|e| AppError::internal(e.to_string()));
 
     let elapsed_time = start_time.elapsed();
 
@@ -126,6 +103,55 @@ pub async fn forward_request(
         Err(app_error) => return Err(app_error),
     };
 
+    into_client_response(target_response, key_info).await
+}
+
+/// Forwards a request whose body is streamed to the target instead of being
+/// buffered, such as a file upload. The body cannot be replayed, so the
+/// response is returned as-is and not retried with another key.
+#[tracing::instrument(
+    level = "info",
+    skip_all,
+    fields(
+        http.method = %method,
+        target.url = %target_url,
+        group.name = %key_info.group_name
+    )
+)]
+pub async fn forward_streaming_request(
+    client: &reqwest::Client,
+    key_info: &FlattenedKeyInfo,
+    method: Method,
+    target_url: Url,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response> {
+    let outgoing_headers = build_forward_headers(&headers, key_info.key.expose_secret())?;
+    let outgoing_body = reqwest::Body::wrap_stream(body.into_data_stream());
+
+    info!(
+        proxy.url = ?key_info.proxy_url.as_deref(),
+        "Streaming request to target"
+    );
+
+    let start_time = Instant::now();
+    let response_result = client
+        .request(method, target_url.clone())
+        .headers(outgoing_headers)
+        .body(outgoing_body)
+        .send()
+        .await;
+    let target_response =
+        handle_target_response(response_result, start_time.elapsed(), &target_url, key_info)?;
+
+    into_client_response(target_response, key_info).await
+}
+
+/// Converts the target's response into the response for the original client.
+async fn into_client_response(
+    target_response: reqwest::Response,
+    key_info: &FlattenedKeyInfo,
+) -> Result<Response> {
     let response_status = target_response.status();
     let response_headers = build_response_headers(target_response.headers());
 
@@ -0,0 +1,428 @@
+// src/request_rules.rs
+
+//! Per-group rewrites of request generation parameters.
+//!
+//! A group's `request_rules` set, default, clamp or remove `temperature`,
+//! `maxOutputTokens`, `thinkingBudget`, `safetySettings` and
+//! `systemInstruction`. Its `top_p`, or the server-wide one, is used when the
+//! client sends none.
+//!
+//! Gemini `generateContent` bodies and OpenAI `chat/completions` bodies are
+//! rewritten in their own shape:
+//!
+//! | parameter         | Gemini                                    | OpenAI                                               |
+//! |-------------------|-------------------------------------------|------------------------------------------------------|
+//! | temperature       | `generationConfig.temperature`            | `temperature`                                        |
+//! | maxOutputTokens   | `generationConfig.maxOutputTokens`        | `max_completion_tokens`, else `max_tokens`           |
+//! | thinkingBudget    | `generationConfig.thinkingConfig`         | `extra_body.google.thinking_config`                  |
+//! | safetySettings    | `safetySettings`                          | `extra_body.google.safety_settings`                  |
+//! | systemInstruction | `systemInstruction`                       | the `system` message                                 |
+//! | topP              | `generationConfig.topP`                   | `top_p`                                              |
+
+use crate::{
+    config::{ParamRule, RequestRules},
+    translation::openai,
+};
+use serde_json::{json, Map, Value};
+
+/// Body formats the rules understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyShape {
+    Gemini,
+    OpenAi,
+}
+
+impl BodyShape {
+    /// Returns the shape of a request to `path`, or `None` for requests that
+    /// carry no generation parameters.
+    pub fn of(path: &str) -> Option<Self> {
+        if path.ends_with(":generateContent") || path.ends_with(":streamGenerateContent") {
+            Some(Self::Gemini)
+        } else if openai::is_chat_completions_path(path) {
+            Some(Self::OpenAi)
+        } else {
+            None
+        }
+    }
+}
+
+/// A parameter location: each step is the field name written by the proxy and
+/// the alternative spelling clients may use instead.
+type FieldPath = &'static [(&'static str, &'static str)];
+
+const GEMINI_TEMPERATURE: FieldPath = &[
+    ("generationConfig", "generation_config"),
+    ("temperature", "temperature"),
+];
+const GEMINI_MAX_OUTPUT_TOKENS: FieldPath = &[
+    ("generationConfig", "generation_config"),
+    ("maxOutputTokens", "max_output_tokens"),
+];
+const GEMINI_THINKING_BUDGET: FieldPath = &[
+    ("generationConfig", "generation_config"),
+    ("thinkingConfig", "thinking_config"),
+    ("thinkingBudget", "thinking_budget"),
+];
+const GEMINI_SAFETY_SETTINGS: FieldPath = &[("safetySettings", "safety_settings")];
+const GEMINI_SYSTEM_INSTRUCTION: FieldPath = &[("systemInstruction", "system_instruction")];
+const GEMINI_TOP_P: FieldPath = &[("generationConfig", "generation_config"), ("topP", "top_p")];
+
+const OPENAI_TEMPERATURE: FieldPath = &[("temperature", "temperature")];
+const OPENAI_THINKING_BUDGET: FieldPath = &[
+    ("extra_body", "extra_body"),
+    ("google", "google"),
+    ("thinking_config", "thinkingConfig"),
+    ("thinking_budget", "thinkingBudget"),
+];
+const OPENAI_SAFETY_SETTINGS: FieldPath = &[
+    ("extra_body", "extra_body"),
+    ("google", "google"),
+    ("safety_settings", "safetySettings"),
+];
+const OPENAI_TOP_P: FieldPath = &[("top_p", "top_p")];
+
+/// Applies `rules` and `top_p` to a request body of the given shape. Returns
+/// whether the body was changed.
+pub fn apply(
+    rules: Option<&RequestRules>,
+    top_p: Option<f32>,
+    shape: BodyShape,
+    body: &mut Value,
+) -> bool {
+    let Some(root) = body.as_object_mut() else {
+        return false;
+    };
+    let mut changed = false;
+    if let Some(rules) = rules {
+        changed |= match shape {
+            BodyShape::Gemini => apply_gemini(rules, root),
+            BodyShape::OpenAi => apply_openai(rules, root),
+        };
+    }
+    if let Some(top_p) = top_p {
+        let path = match shape {
+            BodyShape::Gemini => GEMINI_TOP_P,
+            BodyShape::OpenAi => OPENAI_TOP_P,
+        };
+        changed |= apply_at(root, path, &ParamRule::Default(json!(top_p)));
+    }
+    changed
+}
+
+fn apply_gemini(rules: &RequestRules, root: &mut Map<String, Value>) -> bool {
+    let mut changed = false;
+    if let Some(rule) = &rules.temperature {
+        changed |= apply_at(root, GEMINI_TEMPERATURE, rule);
+    }
+    if let Some(rule) = &rules.max_output_tokens {
+        changed |= apply_at(root, GEMINI_MAX_OUTPUT_TOKENS, rule);
+    }
+    if let Some(rule) = &rules.thinking_budget {
+        changed |= apply_at(root, GEMINI_THINKING_BUDGET, rule);
+    }
+    if let Some(rule) = &rules.safety_settings {
+        changed |= apply_at(root, GEMINI_SAFETY_SETTINGS, rule);
+    }
+    if let Some(rule) = &rules.system_instruction {
+        let rule = match rule {
+            ParamRule::Set(value) => ParamRule::Set(gemini_instruction(value)),
+            ParamRule::Default(value) => ParamRule::Default(gemini_instruction(value)),
+            other => other.clone(),
+        };
+        changed |= apply_at(root, GEMINI_SYSTEM_INSTRUCTION, &rule);
+    }
+    changed
+}
+
+fn apply_openai(rules: &RequestRules, root: &mut Map<String, Value>) -> bool {
+    let mut changed = false;
+    if let Some(rule) = &rules.temperature {
+        changed |= apply_at(root, OPENAI_TEMPERATURE, rule);
+    }
+    if let Some(rule) = &rules.max_output_tokens {
+        let path: FieldPath = if root.contains_key("max_completion_tokens") {
+            &[("max_completion_tokens", "max_completion_tokens")]
+        } else {
+            &[("max_tokens", "max_tokens")]
+        };
+        changed |= apply_at(root, path, rule);
+    }
+    if let Some(rule) = &rules.thinking_budget {
+        // `reasoning_effort` is the other way to ask for a thinking budget.
+        match rule {
+            ParamRule::Set(_) | ParamRule::Remove => {
+                changed |= root.remove("reasoning_effort").is_some();
+                changed |= apply_at(root, OPENAI_THINKING_BUDGET, rule);
+            }
+            ParamRule::Default(_) if root.contains_key("reasoning_effort") => {}
+            _ => changed |= apply_at(root, OPENAI_THINKING_BUDGET, rule),
+        }
+    }
+    if let Some(rule) = &rules.safety_settings {
+        changed |= apply_at(root, OPENAI_SAFETY_SETTINGS, rule);
+    }
+    if let Some(rule) = &rules.system_instruction {
+        changed |= apply_system_message(root, rule);
+    }
+    changed
+}
+
+/// Applies `rule` to the field at `path`, creating missing parents only when
+/// a value is written.
+fn apply_at(root: &mut Map<String, Value>, path: FieldPath, rule: &ParamRule) -> bool {
+    let create = matches!(rule, ParamRule::Set(_) | ParamRule::Default(_));
+    let Some(((field, alias), parents)) = path.split_last() else {
+        return false;
+    };
+    let mut changed = false;
+    let mut object = root;
+    for (name, parent_alias) in parents {
+        changed |= canonicalize(object, name, parent_alias);
+        if !object.contains_key(*name) {
+            if !create {
+                return changed;
+            }
+            object.insert(name.to_string(), json!({}));
+            changed = true;
+        }
+        let Some(next) = object.get_mut(*name).and_then(Value::as_object_mut) else {
+            return changed;
+        };
+        object = next;
+    }
+    changed |= canonicalize(object, field, alias);
+
+    match rule {
+        ParamRule::Set(value) => {
+            if object.get(*field) != Some(value) {
+                object.insert(field.to_string(), value.clone());
+                changed = true;
+            }
+        }
+        ParamRule::Default(value) => {
+            if !object.contains_key(*field) {
+                object.insert(field.to_string(), value.clone());
+                changed = true;
+            }
+        }
+        ParamRule::Clamp { min, max } => {
+            let Some(current) = object.get(*field) else {
+                return changed;
+            };
+            if let Some(clamped) = clamp(current, *min, *max) {
+                object.insert(field.to_string(), clamped);
+                changed = true;
+            }
+        }
+        ParamRule::Remove => changed |= object.remove(*field).is_some(),
+    }
+    changed
+}
+
+/// Renames `alias` to `name` so rules see one spelling of a field.
+fn canonicalize(object: &mut Map<String, Value>, name: &str, alias: &str) -> bool {
+    if name == alias || object.contains_key(name) {
+        return false;
+    }
+    match object.remove(alias) {
+        Some(value) => {
+            object.insert(name.to_string(), value);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the clamped value if it differs from `value`. Integers stay
+/// integers; `-1`, Gemini's "dynamic" thinking budget, is left alone.
+fn clamp(value: &Value, min: Option<f64>, max: Option<f64>) -> Option<Value> {
+    let number = value.as_f64()?;
+    if value.as_i64() == Some(-1) {
+        return None;
+    }
+    let clamped = number
+        .max(min.unwrap_or(f64::NEG_INFINITY))
+        .min(max.unwrap_or(f64::INFINITY));
+    if clamped == number {
+        return None;
+    }
+    if value.is_i64() || value.is_u64() {
+        Some(json!(clamped.round() as i64))
+    } else {
+        Some(json!(clamped))
+    }
+}
+
+/// A configured instruction as a Gemini `Content`.
+fn gemini_instruction(value: &Value) -> Value {
+    match value {
+        Value::String(text) => json!({ "parts": [{ "text": text }] }),
+        other => other.clone(),
+    }
+}
+
+/// The text of a configured instruction, joining the parts of a `Content`.
+fn instruction_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other
+            .get("parts")
+            .and_then(Value::as_array)
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(Value::as_str))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn is_system_message(message: &Value) -> bool {
+    matches!(
+        message.get("role").and_then(Value::as_str),
+        Some("system" | "developer")
+    )
+}
+
+/// Applies a `system_instruction` rule to the `system` and `developer`
+/// messages of an OpenAI request.
+fn apply_system_message(root: &mut Map<String, Value>, rule: &ParamRule) -> bool {
+    let Some(messages) = root.get_mut("messages").and_then(Value::as_array_mut) else {
+        return false;
+    };
+    let before = messages.len();
+    match rule {
+        ParamRule::Set(value) => {
+            messages.retain(|message| !is_system_message(message));
+            messages.insert(
+                0,
+                json!({ "role": "system", "content": instruction_text(value) }),
+            );
+            true
+        }
+        ParamRule::Default(value) => {
+            if messages.iter().any(is_system_message) {
+                return false;
+            }
+            messages.insert(
+                0,
+                json!({ "role": "system", "content": instruction_text(value) }),
+            );
+            true
+        }
+        ParamRule::Remove => {
+            messages.retain(|message| !is_system_message(message));
+            messages.len() != before
+        }
+        ParamRule::Clamp { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> RequestRules {
+        RequestRules {
+            temperature: Some(ParamRule::Clamp {
+                min: None,
+                max: Some(1.0),
+            }),
+            max_output_tokens: Some(ParamRule::Default(json!(1024))),
+            thinking_budget: Some(ParamRule::Set(json!(0))),
+            safety_settings: Some(ParamRule::Remove),
+            system_instruction: Some(ParamRule::Default(json!("Be brief."))),
+        }
+    }
+
+    #[test]
+    fn test_rules_rewrite_gemini_body() {
+        let mut body = json!({
+            "contents": [],
+            "generation_config": {"temperature": 1.7},
+            "safetySettings": [{"category": "HARM_CATEGORY_HARASSMENT"}]
+        });
+        assert!(apply(
+            Some(&rules()),
+            Some(0.9),
+            BodyShape::Gemini,
+            &mut body
+        ));
+        assert_eq!(
+            body,
+            json!({
+                "contents": [],
+                "generationConfig": {
+                    "temperature": 1.0,
+                    "maxOutputTokens": 1024,
+                    "thinkingConfig": {"thinkingBudget": 0},
+                    "topP": 0.9f32
+                },
+                "systemInstruction": {"parts": [{"text": "Be brief."}]}
+            })
+        );
+        assert!(!apply(
+            Some(&rules()),
+            Some(0.9),
+            BodyShape::Gemini,
+            &mut body
+        ));
+    }
+
+    #[test]
+    fn test_rules_rewrite_openai_body() {
+        let mut body = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "system", "content": "Be verbose."}, {"role": "user", "content": "Hi"}],
+            "temperature": 0.5,
+            "max_completion_tokens": 8192,
+            "reasoning_effort": "high",
+            "extra_body": {"google": {"safety_settings": []}}
+        });
+        assert!(apply(Some(&rules()), None, BodyShape::OpenAi, &mut body));
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["max_completion_tokens"], 8192);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("reasoning_effort").is_none());
+        assert_eq!(
+            body["extra_body"],
+            json!({"google": {"thinking_config": {"thinking_budget": 0}}})
+        );
+        assert_eq!(body["messages"][0]["content"], "Be verbose.");
+
+        let mut rules = rules();
+        rules.system_instruction = Some(ParamRule::Remove);
+        rules.max_output_tokens = Some(ParamRule::Clamp {
+            min: None,
+            max: Some(4096.0),
+        });
+        assert!(apply(Some(&rules), None, BodyShape::OpenAi, &mut body));
+        assert_eq!(body["max_completion_tokens"], 4096);
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_clamp_keeps_dynamic_thinking_budget() {
+        let rules = RequestRules {
+            thinking_budget: Some(ParamRule::Clamp {
+                min: Some(0.0),
+                max: Some(2048.0),
+            }),
+            ..Default::default()
+        };
+        let mut body = json!({"generationConfig": {"thinkingConfig": {"thinkingBudget": -1}}});
+        assert!(!apply(Some(&rules), None, BodyShape::Gemini, &mut body));
+        assert_eq!(BodyShape::of("/v1beta/models/x:countTokens"), None);
+    }
+
+    #[test]
+    fn test_rules_parse_from_yaml() {
+        let yaml = "temperature:\n  clamp: { max: 1.0 }\nsafety_settings: remove\n";
+        let rules: RequestRules = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(rules.safety_settings, Some(ParamRule::Remove));
+        let saved = serde_yaml::to_string(&rules).unwrap();
+        assert_eq!(serde_yaml::from_str::<RequestRules>(&saved).unwrap(), rules);
+    }
+}
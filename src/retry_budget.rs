@@ -0,0 +1,199 @@
+// src/retry_budget.rs
+
+//! Per-request limits on upstream attempts and total time.
+//!
+//! Each attempt of `proxy_loop`, whichever key it uses, counts against
+//! `max_attempts`; the deadline covers attempts, `Retry-After` waits and the
+//! key wait queue. Both come from the request's group, else the server, and
+//! clients may lower them with headers. Responses report the attempts made in
+//! `X-Proxy-Attempts`.
+//!
+//! Same-key retries after transient upstream errors wait for an exponential
+//! backoff with jitter, see [`backoff_delay`].
+
+use crate::config::{AppConfig, RetryBackoffConfig};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::Response;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Request header with which clients lower their maximum attempts.
+pub const MAX_ATTEMPTS_HEADER: &str = "x-proxy-max-attempts";
+
+/// Request header with which clients lower their deadline, in milliseconds.
+pub const DEADLINE_HEADER: &str = "x-proxy-deadline-ms";
+
+/// Response header reporting the upstream attempts made for the request.
+pub const ATTEMPTS_HEADER: &str = "x-proxy-attempts";
+
+/// Attempts made and left for one request.
+#[derive(Debug)]
+pub struct RetryBudget {
+    max_attempts: Option<u32>,
+    deadline: Option<Instant>,
+    attempts: u32,
+}
+
+impl RetryBudget {
+    /// Builds the budget of a request routed to `group_name`. A client header
+    /// only lowers a configured limit, but sets one where none is configured.
+    pub fn new(config: &AppConfig, group_name: Option<&str>, headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|v| *v > 0)
+        };
+        let (max_attempts, deadline_secs) = config.retry_limits_for_group(group_name);
+
+        let requested_attempts = header(MAX_ATTEMPTS_HEADER).map(|v| v.min(u32::MAX as u64) as u32);
+        let max_attempts = match (max_attempts, requested_attempts) {
+            (Some(configured), Some(requested)) => Some(configured.min(requested)),
+            (configured, requested) => configured.or(requested),
+        };
+        let configured = deadline_secs.map(Duration::from_secs);
+        let requested = header(DEADLINE_HEADER).map(Duration::from_millis);
+        let deadline = match (configured, requested) {
+            (Some(configured), Some(requested)) => Some(configured.min(requested)),
+            (configured, requested) => configured.or(requested),
+        };
+
+        Self {
+            max_attempts,
+            deadline: deadline.map(|deadline| Instant::now() + deadline),
+            attempts: 0,
+        }
+    }
+
+    /// Returns whether no attempt is left, by count or by time.
+    pub fn exhausted(&self) -> bool {
+        self.max_attempts.is_some_and(|max| self.attempts >= max) || self.expired()
+    }
+
+    pub fn start_attempt(&mut self) {
+        self.attempts += 1;
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Time left before the deadline, if the request has one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_some_and(|left| left.is_zero())
+    }
+
+    /// Caps a wait at the time left before the deadline.
+    pub fn cap_wait(&self, wait: Duration) -> Duration {
+        self.remaining().map_or(wait, |left| wait.min(left))
+    }
+
+    /// Runs `future` until it completes or the deadline passes, in which
+    /// case it is dropped and `None` is returned.
+    pub async fn within<F: std::future::Future>(&self, future: F) -> Option<F::Output> {
+        match self.remaining() {
+            Some(left) => tokio::time::timeout(left, future).await.ok(),
+            None => Some(future.await),
+        }
+    }
+
+    /// Adds `X-Proxy-Attempts` to `response`.
+    pub fn stamp(&self, mut response: Response) -> Response {
+        response
+            .headers_mut()
+            .insert(ATTEMPTS_HEADER, HeaderValue::from(self.attempts));
+        response
+    }
+}
+
+/// Returns the delay before the `retry`-th same-key retry, counting from 1:
+/// the base delay doubled per earlier retry and capped at the maximum, of
+/// which a random half is kept so that retries of concurrent requests spread.
+pub fn backoff_delay(config: &RetryBackoffConfig, retry: u32) -> Duration {
+    let exponent = retry.saturating_sub(1).min(16);
+    let delay = config
+        .base_delay_ms
+        .saturating_mul(1 << exponent)
+        .min(config.max_delay_ms);
+    let half = delay / 2;
+    Duration::from_millis(half + rand::thread_rng().gen_range(0..=delay - half))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeyGroup;
+
+    #[test]
+    fn test_clients_only_lower_configured_limits() {
+        let mut config = AppConfig {
+            groups: vec![KeyGroup {
+                name: "eval".to_string(),
+                max_attempts: Some(5),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        config.server.max_attempts = Some(2);
+        config.server.request_deadline_secs = Some(10);
+        let mut headers = HeaderMap::new();
+
+        let budget = RetryBudget::new(&config, Some("eval"), &headers);
+        assert_eq!(budget.max_attempts, Some(5));
+        let budget = RetryBudget::new(&config, None, &headers);
+        assert_eq!(budget.max_attempts, Some(2));
+        assert!(budget.remaining().unwrap() <= Duration::from_secs(10));
+
+        headers.insert(MAX_ATTEMPTS_HEADER, HeaderValue::from_static("3"));
+        headers.insert(DEADLINE_HEADER, HeaderValue::from_static("500"));
+        let budget = RetryBudget::new(&config, Some("eval"), &headers);
+        assert_eq!(budget.max_attempts, Some(3));
+        assert!(budget.remaining().unwrap() <= Duration::from_millis(500));
+        let budget = RetryBudget::new(&config, None, &headers);
+        assert_eq!(budget.max_attempts, Some(2));
+
+        config.server.request_deadline_secs = None;
+        headers.insert(DEADLINE_HEADER, HeaderValue::from_static("60000"));
+        let budget = RetryBudget::new(&config, None, &headers);
+        assert!(budget.remaining().unwrap() > Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_attempts_stop_at_the_limit() {
+        let mut config = AppConfig::default();
+        config.server.max_attempts = Some(2);
+        let mut budget = RetryBudget::new(&config, None, &HeaderMap::new());
+
+        budget.start_attempt();
+        assert!(!budget.exhausted());
+        budget.start_attempt();
+        assert!(budget.exhausted());
+        assert_eq!(budget.attempts(), 2);
+        assert_eq!(
+            budget.cap_wait(Duration::from_secs(600)),
+            Duration::from_secs(600)
+        );
+    }
+
+    #[test]
+    fn test_backoff_grows_to_the_cap_with_jitter() {
+        let config = RetryBackoffConfig {
+            base_delay_ms: 100,
+            max_delay_ms: 300,
+        };
+        for _ in 0..20 {
+            let first = backoff_delay(&config, 1);
+            assert!((50..=100).contains(&(first.as_millis() as u64)));
+            let second = backoff_delay(&config, 2);
+            assert!((100..=200).contains(&(second.as_millis() as u64)));
+            let capped = backoff_delay(&config, 10);
+            assert!((150..=300).contains(&(capped.as_millis() as u64)));
+        }
+    }
+}
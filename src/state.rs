@@ -1,7 +1,10 @@
 // src/state.rs
 
 use crate::admin::SystemInfoCollector;
-use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+use crate::cache::ResponseCache;
+use crate::catalog::ModelCatalog;
+use crate::circuit_breaker::CircuitBreakerRegistry;
+use crate::coalesce::RequestCoalescer;
 use crate::config::AppConfig;
 use crate::error::{AppError, Result};
 use crate::handlers::processor::ResponseProcessor;
@@ -9,9 +12,14 @@ use crate::handlers::{
     invalid_api_key::InvalidApiKeyHandler, rate_limit::RateLimitHandler, success::SuccessHandler,
     terminal_error::TerminalErrorHandler,
 };
+use crate::hedging::HedgeController;
 use crate::key_manager::{KeyManager, KeyManagerTrait};
 use crate::metrics::MetricsRegistry;
 use crate::middleware::rate_limit::RateLimitStore;
+use crate::priority::PriorityGate;
+use crate::token_limit::TokenCountCache;
+use crate::usage::UsageTracker;
+use crate::wait_queue::KeyWaitQueue;
 use deadpool_redis::{Config, Pool, Runtime};
 use reqwest::{Client, ClientBuilder, Proxy};
 use std::collections::{HashMap, HashSet};
@@ -37,7 +45,15 @@ pub struct AppState {
     pub metrics: Arc<MetricsRegistry>,
     pub rate_limit_store: RateLimitStore,
     pub config_update_tx: broadcast::Sender<AppConfig>,
-    pub circuit_breakers: Arc<RwLock<HashMap<String, Arc<CircuitBreaker>>>>,
+    pub circuit_breakers: Arc<CircuitBreakerRegistry>,
+    pub model_catalog: Arc<ModelCatalog>,
+    pub response_cache: Arc<ResponseCache>,
+    pub request_coalescer: Arc<RequestCoalescer>,
+    pub hedge_controller: Arc<HedgeController>,
+    pub key_wait_queue: Arc<KeyWaitQueue>,
+    pub priority_gate: Arc<PriorityGate>,
+    pub token_count_cache: Arc<TokenCountCache>,
+    pub usage: Arc<UsageTracker>,
 }
 
 impl fmt::Debug for AppState {
@@ -312,10 +328,12 @@ impl AppState {
         info!("Creating shared AppState...");
 
         let redis_pool = Self::create_redis_pool(config).await?;
-        let key_manager = Arc::new(RwLock::new(
-            KeyManager::new(config, redis_pool.clone()).await?,
-        )) as Arc<RwLock<dyn KeyManagerTrait>>;
+        let key_manager = KeyManager::new(config, redis_pool.clone()).await?;
+        // Per-key circuit breakers, shared with key selection
+        let circuit_breakers = key_manager.circuit_breakers();
+        let key_manager = Arc::new(RwLock::new(key_manager)) as Arc<RwLock<dyn KeyManagerTrait>>;
         let http_clients = build_http_clients(config).await?;
+        let response_cache = Arc::new(ResponseCache::new(config, redis_pool.as_ref()));
 
         let response_processor = ResponseProcessor::new(vec![
             Box::new(SuccessHandler),
@@ -328,9 +346,6 @@ impl AppState {
 
         let (tx, rx) = broadcast::channel(16);
 
-        // Initialize circuit breakers for each unique target URL
-        let circuit_breakers = Self::create_circuit_breakers(config).await;
-
         Ok((
             Self {
                 redis_pool,
@@ -344,46 +359,25 @@ impl AppState {
                 metrics: Arc::new(crate::metrics::MetricsRegistry::new()),
                 rate_limit_store: crate::middleware::rate_limit::create_rate_limit_store(),
                 config_update_tx: tx,
-                circuit_breakers: Arc::new(RwLock::new(circuit_breakers)),
+                circuit_breakers,
+                model_catalog: Arc::new(ModelCatalog::new()),
+                response_cache,
+                request_coalescer: Arc::new(RequestCoalescer::new()),
+                hedge_controller: Arc::new(HedgeController::new()),
+                key_wait_queue: Arc::new(KeyWaitQueue::new()),
+                priority_gate: Arc::new(PriorityGate::new()),
+                token_count_cache: Arc::new(TokenCountCache::new(
+                    config
+                        .exact_token_count
+                        .as_ref()
+                        .map_or(0, |exact| exact.cache_max_entries),
+                )),
+                usage: Arc::new(UsageTracker::new()),
             },
             rx,
         ))
     }
 
-    /// Creates circuit breakers for each unique target URL.
-    async fn create_circuit_breakers(config: &AppConfig) -> HashMap<String, Arc<CircuitBreaker>> {
-        let mut circuit_breakers = HashMap::new();
-        let mut unique_targets = HashSet::new();
-
-        // Collect unique target URLs
-        for group in &config.groups {
-            unique_targets.insert(group.target_url.clone());
-        }
-
-        // Create circuit breaker for each unique target
-        for target_url in unique_targets {
-            let cb_config = CircuitBreakerConfig {
-                failure_threshold: 5,
-                recovery_timeout: Duration::from_secs(60),
-                success_threshold: 3,
-            };
-
-            let circuit_breaker = Arc::new(CircuitBreaker::new(
-                format!("target_{target_url}"),
-                cb_config,
-            ));
-
-            circuit_breakers.insert(target_url, circuit_breaker);
-        }
-
-        info!(
-            circuit_breakers_count = circuit_breakers.len(),
-            "Circuit breakers initialized for target URLs"
-        );
-
-        circuit_breakers
-    }
-
     /// Creates a Redis connection pool if configured.
     async fn create_redis_pool(config: &AppConfig) -> Result<Option<Pool>> {
         if let Some(redis_url) = &config.redis_url {
@@ -436,12 +430,6 @@ impl AppState {
             AppError::Internal { message: msg }
         })
     }
-
-    /// Returns a reference to the circuit breaker for the given target URL.
-    pub async fn get_circuit_breaker(&self, target_url: &str) -> Option<Arc<CircuitBreaker>> {
-        let breakers_guard = self.circuit_breakers.read().await;
-        breakers_guard.get(target_url).cloned()
-    }
 }
 
 #[cfg(test)]
@@ -488,6 +476,11 @@ mod tests {
             proxy_url: None,
             target_url: DEFAULT_TARGET_URL_STR.to_string(),
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         }];
         let config = create_test_config(groups, false);
         let state_result = AppState::new(&config, &dummy_path).await;
@@ -525,6 +518,11 @@ mod tests {
                 proxy_url: Some(http_proxy_url.to_string()),
                 target_url: DEFAULT_TARGET_URL_STR.to_string(),
                 top_p: None,
+                request_rules: None,
+                max_request_size: None,
+                tier: None,
+                max_attempts: None,
+                request_deadline_secs: None,
             },
             KeyGroup {
                 name: "g_socks".to_string(),
@@ -533,6 +531,11 @@ mod tests {
                 proxy_url: Some(socks_proxy_url.to_string()),
                 target_url: DEFAULT_TARGET_URL_STR.to_string(),
                 top_p: None,
+                request_rules: None,
+                max_request_size: None,
+                tier: None,
+                max_attempts: None,
+                request_deadline_secs: None,
             },
             KeyGroup {
                 name: "g_http_dup".to_string(),
@@ -541,6 +544,11 @@ mod tests {
                 proxy_url: Some(http_proxy_url.to_string()),
                 target_url: DEFAULT_TARGET_URL_STR.to_string(),
                 top_p: None,
+                request_rules: None,
+                max_request_size: None,
+                tier: None,
+                max_attempts: None,
+                request_deadline_secs: None,
             },
             KeyGroup {
                 name: "g_no_proxy".to_string(),
@@ -549,6 +557,11 @@ mod tests {
                 proxy_url: None,
                 target_url: DEFAULT_TARGET_URL_STR.to_string(),
                 top_p: None,
+                request_rules: None,
+                max_request_size: None,
+                tier: None,
+                max_attempts: None,
+                request_deadline_secs: None,
             },
         ];
         let config = create_test_config(groups, false);
@@ -602,6 +615,11 @@ mod tests {
             proxy_url: Some("::not a proxy url::".to_string()),
             target_url: DEFAULT_TARGET_URL_STR.to_string(),
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         }];
         let config = create_test_config(groups, false);
         let state_result = AppState::new(&config, &dummy_path).await;
@@ -628,6 +646,11 @@ mod tests {
             proxy_url: Some("ftp://unsupported.proxy".to_string()),
             target_url: DEFAULT_TARGET_URL_STR.to_string(),
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         }];
         let config = create_test_config(groups, false);
         let state_result = AppState::new(&config, &dummy_path).await;
@@ -655,6 +678,11 @@ mod tests {
                 proxy_url: Some("http://127.0.0.1:34569".to_string()),
                 target_url: DEFAULT_TARGET_URL_STR.to_string(),
                 top_p: None,
+                request_rules: None,
+                max_request_size: None,
+                tier: None,
+                max_attempts: None,
+                request_deadline_secs: None,
             },
             KeyGroup {
                 name: "g_build_error".to_string(),
@@ -663,6 +691,11 @@ mod tests {
                 proxy_url: Some("socks5://nonexistent-proxy-host.invalid:1080".to_string()),
                 target_url: DEFAULT_TARGET_URL_STR.to_string(),
                 top_p: None,
+                request_rules: None,
+                max_request_size: None,
+                tier: None,
+                max_attempts: None,
+                request_deadline_secs: None,
             },
         ];
         let config = create_test_config(groups, false);
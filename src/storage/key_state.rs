@@ -1,6 +1,23 @@
 // src/storage/key_state.rs
 
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Longest temporary block a key can receive. Upstream `Retry-After` values
+/// beyond this are treated as this long.
+pub const MAX_TEMPORARY_BLOCK: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Caps `duration` at [`MAX_TEMPORARY_BLOCK`] and returns it together with the
+/// moment the block ends.
+pub fn temporary_block_deadline(duration: Duration) -> (Duration, chrono::DateTime<chrono::Utc>) {
+    let duration = duration.min(MAX_TEMPORARY_BLOCK);
+    let now = chrono::Utc::now();
+    let until = chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|delta| now.checked_add_signed(delta))
+        .unwrap_or_else(|| now + chrono::Duration::hours(24));
+    (duration, until)
+}
 
 /// Represents the state of a single API key
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -10,6 +27,10 @@ pub struct KeyState {
     pub is_blocked: bool,
     pub consecutive_failures: u32,
     pub last_failure: Option<chrono::DateTime<chrono::Utc>>,
+    /// End of a temporary block (rate limit). `None` while blocked means the
+    /// key stays blocked until it is reset.
+    #[serde(default)]
+    pub blocked_until: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl KeyState {
@@ -21,6 +42,7 @@ impl KeyState {
             is_blocked: false,
             consecutive_failures: 0,
             last_failure: None,
+            blocked_until: None,
         }
     }
 
@@ -31,11 +53,13 @@ impl KeyState {
 
     /// Record a failure and update state
     pub fn record_failure(&mut self, is_terminal: bool, max_failures: u32) {
+        self.clear_expired_block();
         self.consecutive_failures += 1;
         self.last_failure = Some(chrono::Utc::now());
 
         if self.should_block(max_failures, is_terminal) {
             self.is_blocked = true;
+            self.blocked_until = None;
         }
     }
 
@@ -44,10 +68,38 @@ impl KeyState {
         self.is_blocked = false;
         self.consecutive_failures = 0;
         self.last_failure = None;
+        self.blocked_until = None;
+    }
+
+    /// Block the key until `until`.
+    pub fn block_until(&mut self, until: chrono::DateTime<chrono::Utc>) {
+        self.is_blocked = true;
+        self.blocked_until = Some(until);
+    }
+
+    /// Lifts a temporary block whose end has passed, so `is_blocked` agrees
+    /// with `is_available`. Stores call this on every state they hand out.
+    pub fn clear_expired_block(&mut self) {
+        if self
+            .blocked_until
+            .is_some_and(|until| until <= chrono::Utc::now())
+        {
+            self.is_blocked = false;
+            self.blocked_until = None;
+        }
+    }
+
+    /// Returns the end of a temporary block that has not yet passed.
+    pub fn temporarily_blocked_until(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.blocked_until
+            .filter(|until| self.is_blocked && *until > chrono::Utc::now())
     }
 
     /// Check if the key is available for use
     pub fn is_available(&self) -> bool {
         !self.is_blocked
+            || self
+                .blocked_until
+                .is_some_and(|until| until <= chrono::Utc::now())
     }
 }
@@ -2,12 +2,14 @@
 
 use crate::error::{AppError, Result};
 use crate::key_manager::FlattenedKeyInfo;
+use crate::storage::key_state::temporary_block_deadline;
+use crate::storage::resource_owner::owner_ttl;
 use crate::storage::{KeyState, KeyStateStore, KeyStore};
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{trace, warn};
 
@@ -15,6 +17,13 @@ use tracing::{trace, warn};
 pub struct InMemoryStore {
     key_states: Arc<RwLock<HashMap<String, KeyState>>>,
     counters: Arc<RwLock<HashMap<String, AtomicUsize>>>,
+    resource_owners: Arc<RwLock<HashMap<String, ResourceOwner>>>,
+}
+
+/// A recorded resource owner and when the record lapses.
+struct ResourceOwner {
+    api_key: String,
+    expires_at: Instant,
 }
 
 impl InMemoryStore {
@@ -30,6 +39,7 @@ impl InMemoryStore {
         Self {
             key_states: Arc::new(RwLock::new(key_states)),
             counters: Arc::new(RwLock::new(HashMap::new())),
+            resource_owners: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
@@ -78,29 +88,86 @@ impl KeyStore for InMemoryStore {
         trace!("InMemoryStore::get_key_state: waiting for read lock");
         let states_guard = self.key_states.read().await;
         trace!("InMemoryStore::get_key_state: got read lock");
-        Ok(states_guard.get(key).cloned())
+        Ok(states_guard.get(key).cloned().map(|mut state| {
+            state.clear_expired_block();
+            state
+        }))
     }
 
     async fn get_all_key_states(&self) -> Result<HashMap<String, KeyState>> {
         trace!("InMemoryStore::get_all_key_states: waiting for read lock");
         let states_guard = self.key_states.read().await;
         trace!("InMemoryStore::get_all_key_states: got read lock");
-        Ok(states_guard.clone())
+        Ok(states_guard
+            .iter()
+            .map(|(key, state)| {
+                let mut state = state.clone();
+                state.clear_expired_block();
+                (key.clone(), state)
+            })
+            .collect())
     }
 
-    async fn set_key_rate_limited(&self, api_key: &str, _duration: Duration) -> Result<()> {
+    async fn set_key_rate_limited(&self, api_key: &str, duration: Duration) -> Result<()> {
         let mut states_guard = self.key_states.write().await;
         if let Some(state) = states_guard.get_mut(api_key) {
-            state.is_blocked = true;
-            // NOTE: In-memory store does not currently support TTLs for rate limiting.
-            // The key will be blocked until the next restart.
+            let (duration, until) = temporary_block_deadline(duration);
+            state.block_until(until);
             warn!(
                 api_key.preview = %crate::key_manager::KeyManager::preview_key_str(api_key),
-                "API key has been temporarily rate-limited (in-memory, blocked until restart)."
+                duration = ?duration,
+                "API key has been temporarily rate-limited."
             );
         }
         Ok(())
     }
+
+    async fn set_resource_owner(&self, resource: &str, api_key: &str) -> Result<()> {
+        let now = Instant::now();
+        let mut owners = self.resource_owners.write().await;
+        owners.retain(|_, owner| owner.expires_at > now);
+        owners.insert(
+            resource.to_string(),
+            ResourceOwner {
+                api_key: api_key.to_string(),
+                expires_at: now + owner_ttl(resource),
+            },
+        );
+        Ok(())
+    }
+
+    async fn get_resource_owner(&self, resource: &str) -> Result<Option<String>> {
+        let now = Instant::now();
+        let mut owners = self.resource_owners.write().await;
+        match owners.get_mut(resource) {
+            Some(owner) if owner.expires_at > now => {
+                owner.expires_at = now + owner_ttl(resource);
+                Ok(Some(owner.api_key.clone()))
+            }
+            Some(_) => {
+                owners.remove(resource);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn remove_resource_owner(&self, resource: &str) -> Result<()> {
+        self.resource_owners.write().await.remove(resource);
+        Ok(())
+    }
+
+    async fn get_resource_owners(&self) -> Result<HashMap<String, String>> {
+        let now = Instant::now();
+        Ok(self
+            .resource_owners
+            .read()
+            .await
+            .iter()
+            .filter(|(_, owner)| owner.expires_at > now)
+            .map(|(resource, owner)| (resource.clone(), owner.api_key.clone()))
+            .collect())
+    }
 }
 
 #[async_trait]
@@ -3,6 +3,7 @@
 pub mod key_state;
 pub mod memory;
 pub mod redis;
+pub mod resource_owner;
 pub mod traits;
 
 pub use key_state::KeyState;
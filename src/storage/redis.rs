@@ -3,6 +3,8 @@
 use crate::config::AppConfig;
 use crate::error::Result;
 use crate::key_manager::FlattenedKeyInfo;
+use crate::storage::key_state::temporary_block_deadline;
+use crate::storage::resource_owner::owner_ttl;
 use crate::storage::{KeyState, KeyStateStore, KeyStore};
 use async_trait::async_trait;
 use deadpool_redis::{Connection as RedisConnection, Pool};
@@ -14,6 +16,7 @@ use tracing::{info, trace, warn};
 
 const ROTATION_SET_KEY: &str = "rotation_keys";
 const ROTATION_COUNTER_KEY: &str = "rotation_counter";
+const RESOURCE_OWNER_KEY: &str = "resource_owner";
 
 /// Redis implementation of key storage
 pub struct RedisStore {
@@ -69,6 +72,11 @@ impl RedisStore {
         format!("{}{}", self.key_prefix, key)
     }
 
+    /// Each owner record is its own key so it can expire on its own.
+    fn resource_owner_key(&self, resource: &str) -> String {
+        self.prefix_key(&format!("{RESOURCE_OWNER_KEY}:{resource}"))
+    }
+
     async fn initialize_redis_from_config(
         conn: &mut RedisConnection,
         key_prefix: &str,
@@ -138,7 +146,7 @@ impl RedisStore {
     }
 
     fn parse_key_state(&self, key: &str, redis_state: HashMap<String, String>) -> KeyState {
-        KeyState {
+        let mut state = KeyState {
             key: key.to_string(),
             group_name: redis_state
                 .get("group_name")
@@ -156,7 +164,13 @@ impl RedisStore {
                 .get("last_failure")
                 .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
                 .map(|dt| dt.with_timezone(&chrono::Utc)),
-        }
+            blocked_until: redis_state
+                .get("blocked_until")
+                .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&chrono::Utc)),
+        };
+        state.clear_expired_block();
+        state
     }
 }
 
@@ -208,6 +222,7 @@ impl KeyStore for RedisStore {
         let should_block = is_terminal || new_failure_count >= max_failures;
         if should_block {
             let _: () = conn.hset(&state_key, "is_blocked", true).await?;
+            let _: () = conn.hdel(&state_key, "blocked_until").await?;
         }
 
         trace!(
@@ -223,6 +238,7 @@ impl KeyStore for RedisStore {
             is_blocked: should_block,
             consecutive_failures: new_failure_count,
             last_failure: Some(chrono::Utc::now()),
+            blocked_until: None,
         })
     }
 
@@ -259,7 +275,9 @@ impl KeyStore for RedisStore {
 
         let mut pipe = redis::pipe();
         pipe.atomic();
+        let (duration, until) = temporary_block_deadline(duration);
         pipe.hset(&state_key, "is_blocked", true);
+        pipe.hset(&state_key, "blocked_until", until.to_rfc3339());
         pipe.expire(&state_key, duration.as_secs() as i64);
 
         let _: () = pipe.query_async(&mut conn).await?;
@@ -271,6 +289,59 @@ impl KeyStore for RedisStore {
         );
         Ok(())
     }
+
+    async fn set_resource_owner(&self, resource: &str, api_key: &str) -> Result<()> {
+        let mut conn = self.get_connection().await?;
+        let _: () = conn
+            .set_ex(
+                self.resource_owner_key(resource),
+                api_key,
+                owner_ttl(resource).as_secs(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_resource_owner(&self, resource: &str) -> Result<Option<String>> {
+        let mut conn = self.get_connection().await?;
+        let owner_key = self.resource_owner_key(resource);
+        // Every lookup extends the record's lifetime.
+        let (owner, _): (Option<String>, bool) = redis::pipe()
+            .get(&owner_key)
+            .expire(&owner_key, owner_ttl(resource).as_secs() as i64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(owner)
+    }
+
+    async fn remove_resource_owner(&self, resource: &str) -> Result<()> {
+        let mut conn = self.get_connection().await?;
+        let _: () = conn.del(self.resource_owner_key(resource)).await?;
+        Ok(())
+    }
+
+    async fn get_resource_owners(&self) -> Result<HashMap<String, String>> {
+        let mut conn = self.get_connection().await?;
+        let prefix = self.resource_owner_key("");
+        let owner_keys: Vec<String> = {
+            let mut iter: redis::AsyncIter<String> = conn.scan_match(format!("{prefix}*")).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if owner_keys.is_empty() {
+            return Ok(HashMap::new());
+        }
+        // Records can expire between the scan and the read.
+        let owners: Vec<Option<String>> = conn.mget(&owner_keys).await?;
+        Ok(owner_keys
+            .iter()
+            .zip(owners)
+            .filter_map(|(key, owner)| Some((key.strip_prefix(&prefix)?.to_string(), owner?)))
+            .collect())
+    }
 }
 
 #[async_trait]
@@ -308,6 +379,7 @@ impl KeyStateStore for RedisStore {
                 &[("is_blocked", "false"), ("consecutive_failures", "0")],
             )
             .await?;
+        let _: () = conn.hdel(&state_key, "blocked_until").await?;
 
         Ok(())
     }
@@ -0,0 +1,24 @@
+// src/storage/resource_owner.rs
+
+//! Retention of resource owner records.
+//!
+//! Owners are kept for a while after their last use instead of forever, so
+//! the records of resources that expired or were deleted upstream without
+//! going through the proxy are eventually dropped.
+
+use std::time::Duration;
+
+/// Files (and the uploads that create them) expire upstream after 48 hours.
+pub const FILE_OWNER_TTL: Duration = Duration::from_secs(48 * 60 * 60);
+
+/// Retention for batches, cached contents, tuned models and operations.
+pub const DEFAULT_OWNER_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Returns how long the owner of `resource` (e.g. `files/abc`) is kept after
+/// it was last recorded or looked up.
+pub fn owner_ttl(resource: &str) -> Duration {
+    match resource.split('/').next() {
+        Some("files" | "uploads") => FILE_OWNER_TTL,
+        _ => DEFAULT_OWNER_TTL,
+    }
+}
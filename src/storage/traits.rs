@@ -31,6 +31,18 @@ pub trait KeyStore: Send + Sync {
 
     /// Temporarily block a key due to rate limiting
     async fn set_key_rate_limited(&self, api_key: &str, duration: Duration) -> Result<()>;
+
+    /// Record the key that created an upstream resource (e.g. `batches/123`)
+    async fn set_resource_owner(&self, resource: &str, api_key: &str) -> Result<()>;
+
+    /// Get the key that created an upstream resource
+    async fn get_resource_owner(&self, resource: &str) -> Result<Option<String>>;
+
+    /// Forget the owner of a deleted resource
+    async fn remove_resource_owner(&self, resource: &str) -> Result<()>;
+
+    /// Get all recorded resource owners, keyed by resource name
+    async fn get_resource_owners(&self) -> Result<HashMap<String, String>>;
 }
 
 /// Trait for key state management operations
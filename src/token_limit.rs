@@ -0,0 +1,334 @@
+// src/token_limit.rs
+
+//! Enforcement of `server.max_tokens_per_request`.
+//!
+//! Requests are measured with the local multimodal estimate. With
+//! `exact_token_count` configured, estimates close to the limit are replaced by
+//! the result of Gemini's `:countTokens` endpoint, called with a key of the
+//! request's group and cached by a hash of the counted content so repeated
+//! prompts cost one call. OpenAI chat bodies are counted in their Gemini form.
+
+use crate::{
+    config::ExactTokenCountConfig,
+    error::{AppError, Result},
+    handlers::{base::Action, proxy_loop},
+    proxy,
+    state::AppState,
+    tokenizer::estimate_request_tokens,
+    translation::openai,
+};
+use axum::{
+    body::{to_bytes, Bytes},
+    http::{header, HeaderMap, HeaderValue, Method, Uri},
+};
+use lru::LruCache;
+use parking_lot::Mutex;
+use secrecy::ExposeSecret;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Request fields that contribute to the prompt token count.
+const COUNTED_FIELDS: [&str; 7] = [
+    "contents",
+    "cachedContent",
+    "systemInstruction",
+    "system_instruction",
+    "tools",
+    "toolConfig",
+    "tool_config",
+];
+
+/// Exact token counts keyed by content hash, bounded with LRU eviction.
+pub struct TokenCountCache {
+    entries: Mutex<LruCache<String, (Instant, usize)>>,
+}
+
+impl TokenCountCache {
+    pub fn new(max_entries: usize) -> Self {
+        let capacity = NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<usize> {
+        let mut entries = self.entries.lock();
+        match entries.get(key) {
+            Some((expires_at, count)) if *expires_at > Instant::now() => Some(*count),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn put(&self, key: &str, count: usize, ttl: Duration) {
+        self.entries
+            .lock()
+            .put(key.to_string(), (Instant::now() + ttl, count));
+    }
+}
+
+/// Returns whether `estimate` lies within `margin` (relative) of `limit`.
+fn near_limit(estimate: usize, limit: u64, margin: f64) -> bool {
+    (estimate as f64 - limit as f64).abs() <= limit as f64 * margin
+}
+
+/// Builds the `:countTokens` request for a Gemini-format body or an OpenAI
+/// chat body. Other formats are left to the estimate.
+fn count_request(model: &str, body: &Value) -> Option<Value> {
+    let object = body.as_object()?;
+    if !object.contains_key("contents") {
+        let translated = openai::chat_request_to_gemini(body).ok()?;
+        return count_request(model, &translated.body);
+    }
+    let mut request: Map<String, Value> = COUNTED_FIELDS
+        .iter()
+        .filter_map(|field| Some((field.to_string(), object.get(*field)?.clone())))
+        .collect();
+    request.insert("model".to_string(), json!(format!("models/{model}")));
+    Some(json!({ "generateContentRequest": request }))
+}
+
+fn cache_key(request: &Value) -> String {
+    hex::encode(Sha256::digest(request.to_string()))
+}
+
+/// Calls `:countTokens` for `model` with the next available key of
+/// `group_name`. The answer goes through the response processor like any
+/// proxied call, so a rate-limited or invalid key is marked and its breakers
+/// record the outcome.
+async fn count_upstream(
+    state: &Arc<AppState>,
+    group_name: Option<&str>,
+    model: &str,
+    request: &Value,
+    timeout: Duration,
+) -> Result<usize> {
+    let key_info = state
+        .key_manager
+        .read()
+        .await
+        .get_next_available_key_info_for_model(group_name, Some(model))
+        .await?
+        .ok_or(AppError::NoHealthyKeys)?;
+    let key = key_info.key.expose_secret();
+
+    let uri: Uri = format!("/v1beta/models/{model}:countTokens")
+        .parse()
+        .map_err(|e| AppError::internal(format!("Invalid countTokens URI: {e}")))?;
+    let url = crate::handlers::build_target_url(&uri, &key_info)?;
+    let client = state.get_client(key_info.proxy_url.as_deref()).await?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    let forwarded = proxy::forward_request(
+        &client,
+        &key_info,
+        Method::POST,
+        url,
+        headers,
+        Bytes::from(request.to_string()),
+    );
+    let response = match tokio::time::timeout(timeout, forwarded).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            state
+                .circuit_breakers
+                .record_failure(key, Some(model))
+                .await;
+            return Err(e);
+        }
+        Err(_) => {
+            state.circuit_breakers.release(key, Some(model)).await;
+            return Err(AppError::internal("countTokens timed out"));
+        }
+    };
+
+    let status = response.status();
+    let (action, _) = state
+        .response_processor
+        .process(response, &key_info)
+        .await?;
+    proxy_loop::record_breaker_outcome(state, &key_info, model, &action).await;
+    let response = match action {
+        Action::ReturnToClient(response) => response,
+        Action::Terminal(_) => {
+            return Err(AppError::internal(format!(
+                "countTokens returned status {status}"
+            )))
+        }
+        action => {
+            proxy_loop::mark_key(state, &key_info, action).await?;
+            return Err(AppError::internal(format!(
+                "countTokens returned status {status}"
+            )));
+        }
+    };
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to read countTokens response: {e}")))?;
+    serde_json::from_slice::<Value>(&body)?
+        .get("totalTokens")
+        .and_then(Value::as_u64)
+        .map(|tokens| tokens as usize)
+        .ok_or_else(|| AppError::internal("countTokens response has no totalTokens"))
+}
+
+/// Returns the exact token count of a request and the method that produced
+/// it, or `None` if the request cannot be counted upstream.
+async fn exact_count(
+    state: &Arc<AppState>,
+    config: &ExactTokenCountConfig,
+    group_name: Option<&str>,
+    model: &str,
+    body: &Value,
+) -> Option<(usize, &'static str)> {
+    let Some(request) = count_request(model, body) else {
+        debug!(
+            model,
+            "Request format cannot be counted upstream; using the estimate"
+        );
+        return None;
+    };
+    let key = cache_key(&request);
+    if let Some(tokens) = state.token_count_cache.get(&key) {
+        return Some((tokens, "exact_cached"));
+    }
+    match count_upstream(
+        state,
+        group_name,
+        model,
+        &request,
+        Duration::from_millis(config.timeout_ms),
+    )
+    .await
+    {
+        Ok(tokens) => {
+            state
+                .token_count_cache
+                .put(&key, tokens, Duration::from_secs(config.cache_ttl_secs));
+            Some((tokens, "exact"))
+        }
+        Err(e) => {
+            warn!(error = %e, model, "Exact token count failed; falling back to the estimate");
+            None
+        }
+    }
+}
+
+/// Rejects requests over `server.max_tokens_per_request` with
+/// `AppError::RequestTooLarge`. Exact counts use a key of `group_name`, the
+/// group resolved for the request. The decision is logged with the counting
+/// method: `estimate`, `exact` or `exact_cached`.
+pub async fn enforce(
+    state: &Arc<AppState>,
+    group_name: Option<&str>,
+    model: Option<&str>,
+    body: Option<&Value>,
+) -> Result<()> {
+    let (limit, exact_config) = {
+        let config = state.config.read().await;
+        (
+            config.server.max_tokens_per_request,
+            config.exact_token_count.clone(),
+        )
+    };
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    let Some(json_body) = body else {
+        return Ok(());
+    };
+
+    let estimate = estimate_request_tokens(json_body);
+    let mut tokens = estimate;
+    let mut method = "estimate";
+    if let (Some(config), Some(model)) = (&exact_config, model) {
+        if near_limit(estimate, limit, config.margin) {
+            if let Some((exact, exact_method)) =
+                exact_count(state, config, group_name, model, json_body).await
+            {
+                tokens = exact;
+                method = exact_method;
+            }
+        }
+    }
+
+    state.metrics.record_request_tokens(tokens as u64);
+    if tokens as u64 > limit {
+        state
+            .metrics
+            .record_token_limit_block(model.map(str::to_owned));
+        info!(
+            method,
+            tokens, estimate, limit, "Rejecting request over the token limit"
+        );
+        return Err(AppError::RequestTooLarge {
+            size: tokens,
+            max_size: limit as usize,
+        });
+    }
+    if method == "estimate" {
+        debug!(method, tokens, limit, "Request within the token limit");
+    } else {
+        info!(
+            method,
+            tokens, estimate, limit, "Request within the token limit"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_margin_and_count_request() {
+        assert!(near_limit(95, 100, 0.1));
+        assert!(near_limit(110, 100, 0.1));
+        assert!(!near_limit(80, 100, 0.1));
+        assert!(!near_limit(120, 100, 0.1));
+
+        let body = json!({
+            "contents": [{"parts": [{"text": "hi"}]}],
+            "generationConfig": {"temperature": 0},
+            "cachedContent": "cachedContents/abc"
+        });
+        let request = count_request("gemini-2.0-flash", &body).unwrap();
+        assert_eq!(
+            request,
+            json!({"generateContentRequest": {
+                "model": "models/gemini-2.0-flash",
+                "contents": [{"parts": [{"text": "hi"}]}],
+                "cachedContent": "cachedContents/abc"
+            }})
+        );
+        let chat = json!({
+            "model": "gemini-2.0-flash",
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        assert_eq!(
+            count_request("gemini-2.0-flash", &chat).unwrap()["generateContentRequest"]["contents"],
+            json!([{"role": "user", "parts": [{"text": "hi"}]}])
+        );
+        assert!(count_request("gemini-2.0-flash", &json!({"input": "hi"})).is_none());
+    }
+
+    #[test]
+    fn test_cache_expires_entries() {
+        let cache = TokenCountCache::new(2);
+        cache.put("a", 10, Duration::from_secs(60));
+        cache.put("b", 20, Duration::ZERO);
+        assert_eq!(cache.get("a"), Some(10));
+        assert_eq!(cache.get("b"), None);
+    }
+}
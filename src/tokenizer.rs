@@ -1,9 +1,12 @@
 // src/tokenizer.rs
 
 pub mod gemini_ml_calibrated;
+pub mod multimodal;
 pub mod smart_parallel;
 
 // --- Public API ---
+// Token estimate for a whole request, including inline media
+pub use multimodal::estimate_request_tokens;
 // Main entry point for text processing
 pub use smart_parallel::process_text_smart;
 // Processing result returned to the user
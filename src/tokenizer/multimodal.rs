@@ -0,0 +1,362 @@
+// src/tokenizer/multimodal.rs
+
+//! Token estimation for whole requests, including inline media.
+//!
+//! Text is counted with the ML-calibrated tokenizer. Inline data is priced with
+//! Gemini's published per-modality costs: 258 tokens per 768x768 image tile
+//! (258 for images up to 384x384), 32 tokens per second of audio, 263 tokens
+//! per second of video and 258 tokens per PDF page. Image sizes, WAV byte rates
+//! and PDF page counts are read from the decoded data; other durations are
+//! derived from the decoded size at an assumed bitrate.
+
+use super::gemini_ml_calibrated::count_ml_calibrated_gemini_tokens;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_json::Value;
+use tracing::{debug, warn};
+
+/// Tokens per image tile, and for an image fitting in `SMALL_IMAGE_MAX_SIDE`.
+pub const TOKENS_PER_IMAGE_TILE: usize = 258;
+/// Tokens per second of audio.
+pub const TOKENS_PER_AUDIO_SECOND: usize = 32;
+/// Tokens per second of video, including its audio track.
+pub const TOKENS_PER_VIDEO_SECOND: usize = 263;
+/// Tokens per PDF page.
+pub const TOKENS_PER_PDF_PAGE: usize = 258;
+
+const SMALL_IMAGE_MAX_SIDE: u32 = 384;
+const IMAGE_TILE_SIDE: u32 = 768;
+/// Assumed bitrate of compressed audio (128 kbit/s).
+const AUDIO_BYTES_PER_SECOND: usize = 16_000;
+/// Assumed bitrate of video (2 Mbit/s).
+const VIDEO_BYTES_PER_SECOND: usize = 250_000;
+/// Assumed page size of PDFs whose page objects cannot be found.
+const PDF_BYTES_PER_PAGE: usize = 100_000;
+
+/// Text and media collected from a request.
+#[derive(Default)]
+struct Collected {
+    text: String,
+    media_tokens: usize,
+}
+
+impl Collected {
+    /// Adds base64-encoded inline data of the given MIME type.
+    fn add_inline(&mut self, mime_type: &str, data: &str) {
+        let bytes = match STANDARD.decode(data) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                debug!(mime_type, error = %e, "Inline data is not valid base64; not counted");
+                return;
+            }
+        };
+        if mime_type.starts_with("text/") {
+            self.text.push_str(&String::from_utf8_lossy(&bytes));
+        } else {
+            self.media_tokens += media_tokens(mime_type, &bytes);
+        }
+    }
+
+    /// Adds a `data:<mime>;base64,<data>` URL; other URLs count as one image tile
+    /// when `is_image`, since their content is unknown.
+    fn add_url(&mut self, url: &str, is_image: bool) {
+        let inline = url.strip_prefix("data:").and_then(|rest| {
+            let (meta, data) = rest.split_once(',')?;
+            Some((meta.strip_suffix(";base64")?, data))
+        });
+        match inline {
+            Some((mime_type, data)) => self.add_inline(mime_type, data),
+            None if is_image => self.media_tokens += TOKENS_PER_IMAGE_TILE,
+            None => {}
+        }
+    }
+
+    /// Adds a Gemini `Part`.
+    fn add_gemini_part(&mut self, part: &Value) {
+        if let Some(text) = part.get("text").and_then(Value::as_str) {
+            self.text.push_str(text);
+        }
+        if let Some(inline) = part.get("inline_data").or_else(|| part.get("inlineData")) {
+            let mime_type = inline
+                .get("mime_type")
+                .or_else(|| inline.get("mimeType"))
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            if let Some(data) = inline.get("data").and_then(Value::as_str) {
+                self.add_inline(mime_type, data);
+            }
+        }
+    }
+
+    /// Adds an item of an OpenAI `content` array.
+    fn add_openai_part(&mut self, part: &Value) {
+        match part.get("type").and_then(Value::as_str) {
+            Some("text") => {
+                if let Some(text) = part.get("text").and_then(Value::as_str) {
+                    self.text.push_str(text);
+                }
+            }
+            Some("image_url") => {
+                let image_url = &part["image_url"];
+                if let Some(url) = image_url
+                    .as_str()
+                    .or_else(|| image_url.get("url").and_then(Value::as_str))
+                {
+                    self.add_url(url, true);
+                }
+            }
+            Some("input_audio") => {
+                let audio = &part["input_audio"];
+                if let Some(data) = audio.get("data").and_then(Value::as_str) {
+                    let format = audio.get("format").and_then(Value::as_str).unwrap_or("wav");
+                    self.add_inline(&format!("audio/{format}"), data);
+                }
+            }
+            Some("file") => {
+                if let Some(url) = part["file"].get("file_data").and_then(Value::as_str) {
+                    self.add_url(url, false);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Estimates the input tokens of a Gemini (`contents`) or OpenAI (`messages`)
+/// request body. If the text tokenizer is unavailable, only media is counted.
+pub fn estimate_request_tokens(body: &Value) -> usize {
+    let mut collected = Collected::default();
+
+    if let Some(contents) = body.get("contents") {
+        match contents {
+            Value::Array(contents) => {
+                for content in contents {
+                    if let Some(parts) = content.get("parts").and_then(Value::as_array) {
+                        parts
+                            .iter()
+                            .for_each(|part| collected.add_gemini_part(part));
+                    }
+                }
+            }
+            Value::String(text) => collected.text.push_str(text),
+            _ => {}
+        }
+    } else if let Some(messages) = body.get("messages").and_then(Value::as_array) {
+        for message in messages {
+            match message.get("content") {
+                Some(Value::String(content)) => collected.text.push_str(content),
+                Some(Value::Array(parts)) => {
+                    parts
+                        .iter()
+                        .for_each(|part| collected.add_openai_part(part));
+                }
+                _ => continue,
+            }
+            collected.text.push('\n'); // Add separator between messages
+        }
+    }
+
+    let text_tokens = if collected.text.trim().is_empty() {
+        0
+    } else {
+        count_ml_calibrated_gemini_tokens(&collected.text).unwrap_or_else(|e| {
+            warn!("Token counting failed: {}", e);
+            0
+        })
+    };
+    text_tokens + collected.media_tokens
+}
+
+/// Returns the token cost of decoded inline data of the given MIME type.
+pub fn media_tokens(mime_type: &str, bytes: &[u8]) -> usize {
+    let mime_type = mime_type.to_ascii_lowercase();
+    if mime_type.starts_with("image/") {
+        image_tokens(bytes)
+    } else if mime_type.starts_with("audio/") {
+        let bytes_per_second = wav_byte_rate(bytes).unwrap_or(AUDIO_BYTES_PER_SECOND);
+        seconds(bytes.len(), bytes_per_second) * TOKENS_PER_AUDIO_SECOND
+    } else if mime_type.starts_with("video/") {
+        seconds(bytes.len(), VIDEO_BYTES_PER_SECOND) * TOKENS_PER_VIDEO_SECOND
+    } else if mime_type == "application/pdf" {
+        pdf_pages(bytes) * TOKENS_PER_PDF_PAGE
+    } else {
+        0
+    }
+}
+
+/// Whole seconds of media, rounded up and at least one.
+fn seconds(len: usize, bytes_per_second: usize) -> usize {
+    ((len + bytes_per_second - 1) / bytes_per_second).max(1)
+}
+
+fn image_tokens(bytes: &[u8]) -> usize {
+    match image_dimensions(bytes) {
+        Some((width, height)) if width > SMALL_IMAGE_MAX_SIDE || height > SMALL_IMAGE_MAX_SIDE => {
+            // Dimensions come straight from client-supplied headers, so the
+            // math is done in u64 where it cannot overflow.
+            let tile = u64::from(IMAGE_TILE_SIDE);
+            let tiles = |side: u32| (u64::from(side) + tile - 1) / tile;
+            let tokens = tiles(width) * tiles(height) * TOKENS_PER_IMAGE_TILE as u64;
+            usize::try_from(tokens).unwrap_or(usize::MAX)
+        }
+        _ => TOKENS_PER_IMAGE_TILE,
+    }
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16)
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Reads the pixel size from a PNG, GIF, JPEG or WebP header.
+fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let size = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+        return Some((size(16)?, size(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some((le_u16(bytes, 6)?, le_u16(bytes, 8)?));
+    }
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        return jpeg_dimensions(bytes);
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return match bytes.get(12..16)? {
+            b"VP8 " => Some((le_u16(bytes, 26)? & 0x3FFF, le_u16(bytes, 28)? & 0x3FFF)),
+            b"VP8L" => {
+                let bits = le_u32(bytes, 21)?;
+                Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+            }
+            b"VP8X" => Some((le_u24(bytes, 24)? + 1, le_u24(bytes, 27)? + 1)),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Walks the JPEG segments up to the first start-of-frame marker.
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    loop {
+        while *bytes.get(at)? != 0xFF {
+            at += 1;
+        }
+        while *bytes.get(at)? == 0xFF {
+            at += 1;
+        }
+        let marker = *bytes.get(at)?;
+        at += 1;
+        if matches!(marker, 0xD0..=0xD9 | 0x01) {
+            continue; // Standalone markers without a length.
+        }
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return Some((be_u16(bytes, at + 5)?, be_u16(bytes, at + 3)?));
+        }
+        at += be_u16(bytes, at)? as usize;
+    }
+}
+
+/// Reads the byte rate of an uncompressed WAV file.
+fn wav_byte_rate(bytes: &[u8]) -> Option<usize> {
+    if !bytes.starts_with(b"RIFF")
+        || bytes.get(8..12) != Some(b"WAVE")
+        || bytes.get(12..16) != Some(b"fmt ")
+    {
+        return None;
+    }
+    let rate = le_u32(bytes, 28)? as usize;
+    (rate > 0).then_some(rate)
+}
+
+/// Counts `/Type /Page` objects, falling back to an estimate from the size
+/// when the page tree is compressed.
+fn pdf_pages(bytes: &[u8]) -> usize {
+    let pattern = b"/Type";
+    let mut pages = 0;
+    let mut at = 0;
+    while let Some(offset) = bytes[at..]
+        .windows(pattern.len())
+        .position(|w| w == pattern)
+    {
+        at += offset + pattern.len();
+        let rest = &bytes[at..];
+        let rest = &rest[rest.iter().take_while(|b| b.is_ascii_whitespace()).count()..];
+        if rest.starts_with(b"/Page") && rest.get(5) != Some(&b's') {
+            pages += 1;
+        }
+    }
+    if pages == 0 {
+        seconds(bytes.len(), PDF_BYTES_PER_PAGE)
+    } else {
+        pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_images_are_priced_per_tile() {
+        assert_eq!(media_tokens("image/png", &png(300, 200)), 258);
+        assert_eq!(media_tokens("image/png", &png(1000, 1600)), 2 * 3 * 258);
+        assert!(media_tokens("image/png", &png(u32::MAX, u32::MAX)) > 258);
+
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x03,
+            0x00, 0x04, 0x00,
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((1024, 768)));
+        assert_eq!(media_tokens("image/jpeg", b"not an image"), 258);
+    }
+
+    #[test]
+    fn test_audio_video_and_pdf_costs() {
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        wav.extend_from_slice(&[0; 12]);
+        wav.extend_from_slice(&32_000u32.to_le_bytes());
+        wav.resize(32_000 * 10, 0);
+        assert_eq!(media_tokens("audio/wav", &wav), 10 * 32);
+        assert_eq!(media_tokens("audio/mp3", &[0; 16_000 * 3]), 3 * 32);
+        assert_eq!(media_tokens("video/mp4", &[0; 250_000 * 2]), 2 * 263);
+
+        let pdf = b"%PDF-1.4 /Type /Pages /Type/Page /Type /Page /Type /Catalog";
+        assert_eq!(media_tokens("application/pdf", pdf), 2 * 258);
+    }
+
+    #[test]
+    fn test_request_media_is_counted_in_both_formats() {
+        let image = STANDARD.encode(png(1000, 1000));
+        let gemini = json!({
+            "contents": [{"parts": [{"inline_data": {"mime_type": "image/png", "data": image}}]}]
+        });
+        assert_eq!(estimate_request_tokens(&gemini), 4 * 258);
+
+        let openai = json!({
+            "messages": [{"role": "user", "content": [
+                {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{image}")}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+            ]}]
+        });
+        assert_eq!(estimate_request_tokens(&openai), 4 * 258 + 258);
+    }
+}
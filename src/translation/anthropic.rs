@@ -0,0 +1,672 @@
+// src/translation/anthropic.rs
+
+//! Translation between the Anthropic Messages API and Gemini `generateContent`.
+
+use super::{normalize_model_name, GeminiMethod, GeminiRequest, StreamTranslator};
+use crate::error::{AppError, Result};
+use axum::{body::Bytes, http::StatusCode};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Translates an Anthropic Messages request into a native Gemini request.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRequest` if the body is missing `model` or
+/// `messages`, or uses a content block type that has no Gemini equivalent.
+pub fn messages_request_to_gemini(request: &Value) -> Result<GeminiRequest> {
+    let obj = request
+        .as_object()
+        .ok_or_else(|| invalid("request body must be a JSON object"))?;
+    let model = obj
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("`model` is required"))?;
+    let messages = obj
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("`messages` must be an array"))?;
+
+    let mut body = Map::new();
+
+    let system_parts = match obj.get("system") {
+        Some(system) => blocks_to_parts(system, &mut HashMap::new())?,
+        None => Vec::new(),
+    };
+    if !system_parts.is_empty() {
+        body.insert("systemInstruction".into(), json!({ "parts": system_parts }));
+    }
+
+    let mut contents: Vec<Value> = Vec::new();
+    // tool_result blocks only carry the tool_use id; Gemini wants the name.
+    let mut tool_names = HashMap::new();
+    for message in messages {
+        let role = match message.get("role").and_then(Value::as_str) {
+            Some("assistant") => "model",
+            _ => "user",
+        };
+        let parts = blocks_to_parts(
+            message.get("content").unwrap_or(&Value::Null),
+            &mut tool_names,
+        )?;
+        push_content(&mut contents, role, parts);
+    }
+    body.insert("contents".into(), Value::Array(contents));
+
+    let mut generation_config = Map::new();
+    for (from, to) in [
+        ("max_tokens", "maxOutputTokens"),
+        ("temperature", "temperature"),
+        ("top_p", "topP"),
+        ("top_k", "topK"),
+        ("stop_sequences", "stopSequences"),
+    ] {
+        if let Some(value) = obj.get(from).filter(|v| !v.is_null()) {
+            generation_config.insert(to.into(), value.clone());
+        }
+    }
+    if let Some(thinking) = obj.get("thinking") {
+        let config = match thinking.get("type").and_then(Value::as_str) {
+            Some("enabled") => json!({
+                "thinkingBudget": thinking.get("budget_tokens").cloned().unwrap_or(json!(-1)),
+            }),
+            _ => json!({ "thinkingBudget": 0 }),
+        };
+        generation_config.insert("thinkingConfig".into(), config);
+    }
+    if !generation_config.is_empty() {
+        body.insert("generationConfig".into(), Value::Object(generation_config));
+    }
+
+    if let Some(tools) = obj.get("tools").and_then(Value::as_array) {
+        let declarations: Vec<Value> = tools.iter().filter_map(function_declaration).collect();
+        if !declarations.is_empty() {
+            body.insert(
+                "tools".into(),
+                json!([{ "functionDeclarations": declarations }]),
+            );
+        }
+    }
+    if let Some(tool_config) = obj.get("tool_choice").and_then(tool_config_from) {
+        body.insert("toolConfig".into(), tool_config);
+    }
+
+    Ok(GeminiRequest {
+        model: normalize_model_name(model),
+        method: GeminiMethod::GenerateContent,
+        body: Value::Object(body),
+        stream: obj.get("stream").and_then(Value::as_bool).unwrap_or(false),
+    })
+}
+
+/// Translates a buffered Gemini `GenerateContentResponse` into an Anthropic
+/// `message` object.
+pub fn gemini_response_to_message(response: &Value, model: &str) -> Value {
+    let candidate = response.pointer("/candidates/0").unwrap_or(&Value::Null);
+
+    let mut content = Vec::new();
+    let mut text = String::new();
+    for part in candidate_parts(candidate) {
+        if let Some(t) = part.get("text").and_then(Value::as_str) {
+            text.push_str(t);
+        } else if let Some(call) = part.get("functionCall") {
+            if !text.is_empty() {
+                content.push(json!({ "type": "text", "text": std::mem::take(&mut text) }));
+            }
+            content.push(tool_use_block(call));
+        }
+    }
+    if !text.is_empty() {
+        content.push(json!({ "type": "text", "text": text }));
+    }
+
+    let has_tool_use = content.iter().any(|block| block["type"] == "tool_use");
+    json!({
+        "id": message_id(),
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": content,
+        "stop_reason": stop_reason(candidate, has_tool_use),
+        "stop_sequence": Value::Null,
+        "usage": usage_to_anthropic(response.get("usageMetadata")),
+    })
+}
+
+/// Converts a Gemini error response body into an Anthropic error object.
+pub fn error_to_anthropic(status: StatusCode, body: &[u8]) -> Value {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    let error_type = match status.as_u16() {
+        400 | 413 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        429 => "rate_limit_error",
+        503 | 529 => "overloaded_error",
+        _ => "api_error",
+    };
+    json!({ "type": "error", "error": { "type": error_type, "message": message } })
+}
+
+/// Streams Gemini SSE events out as the Anthropic Messages event sequence:
+/// `message_start`, `content_block_*`, `message_delta`, `message_stop`.
+pub struct MessagesStreamTranslator {
+    id: String,
+    model: String,
+    started: bool,
+    /// Index and kind of the content block currently open, if any.
+    open_block: Option<(usize, BlockKind)>,
+    next_index: usize,
+    stop_reason: Option<&'static str>,
+    usage: Option<Value>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BlockKind {
+    Text,
+    Thinking,
+}
+
+impl MessagesStreamTranslator {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            id: message_id(),
+            model: model.into(),
+            started: false,
+            open_block: None,
+            next_index: 0,
+            stop_reason: None,
+            usage: None,
+        }
+    }
+
+    fn start(&mut self, frames: &mut Vec<Bytes>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        frames.push(event_frame(
+            "message_start",
+            &json!({
+                "type": "message_start",
+                "message": {
+                    "id": self.id,
+                    "type": "message",
+                    "role": "assistant",
+                    "model": self.model,
+                    "content": [],
+                    "stop_reason": Value::Null,
+                    "stop_sequence": Value::Null,
+                    "usage": usage_to_anthropic(self.usage.as_ref()),
+                },
+            }),
+        ));
+    }
+
+    fn close_block(&mut self, frames: &mut Vec<Bytes>) {
+        if let Some((index, _)) = self.open_block.take() {
+            frames.push(event_frame(
+                "content_block_stop",
+                &json!({ "type": "content_block_stop", "index": index }),
+            ));
+        }
+    }
+
+    fn open_block(&mut self, frames: &mut Vec<Bytes>, block: Value) -> usize {
+        self.close_block(frames);
+        let index = self.next_index;
+        self.next_index += 1;
+        frames.push(event_frame(
+            "content_block_start",
+            &json!({ "type": "content_block_start", "index": index, "content_block": block }),
+        ));
+        index
+    }
+
+    fn text_delta(&mut self, frames: &mut Vec<Bytes>, kind: BlockKind, text: &str) {
+        let index = match self.open_block {
+            Some((index, open)) if open == kind => index,
+            _ => {
+                let block = match kind {
+                    BlockKind::Text => json!({ "type": "text", "text": "" }),
+                    BlockKind::Thinking => json!({ "type": "thinking", "thinking": "" }),
+                };
+                let index = self.open_block(frames, block);
+                self.open_block = Some((index, kind));
+                index
+            }
+        };
+        let delta = match kind {
+            BlockKind::Text => json!({ "type": "text_delta", "text": text }),
+            BlockKind::Thinking => json!({ "type": "thinking_delta", "thinking": text }),
+        };
+        frames.push(event_frame(
+            "content_block_delta",
+            &json!({ "type": "content_block_delta", "index": index, "delta": delta }),
+        ));
+    }
+}
+
+impl StreamTranslator for MessagesStreamTranslator {
+    fn on_event(&mut self, event: &Value) -> Vec<Bytes> {
+        let mut frames = Vec::new();
+        if let Some(error) = event.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            frames.push(event_frame(
+                "error",
+                &json!({ "type": "error", "error": { "type": "api_error", "message": message } }),
+            ));
+            return frames;
+        }
+        if let Some(usage) = event.get("usageMetadata") {
+            self.usage = Some(usage.clone());
+        }
+        self.start(&mut frames);
+
+        let candidate = event.pointer("/candidates/0").unwrap_or(&Value::Null);
+        for part in candidate_parts(candidate) {
+            if let Some(text) = part.get("text").and_then(Value::as_str) {
+                let kind = if part.get("thought").and_then(Value::as_bool) == Some(true) {
+                    BlockKind::Thinking
+                } else {
+                    BlockKind::Text
+                };
+                self.text_delta(&mut frames, kind, text);
+            } else if let Some(call) = part.get("functionCall") {
+                let block = tool_use_block(call);
+                let arguments = block["input"].to_string();
+                let mut start = block;
+                start["input"] = json!({});
+                let index = self.open_block(&mut frames, start);
+                frames.push(event_frame(
+                    "content_block_delta",
+                    &json!({
+                        "type": "content_block_delta",
+                        "index": index,
+                        "delta": { "type": "input_json_delta", "partial_json": arguments },
+                    }),
+                ));
+                frames.push(event_frame(
+                    "content_block_stop",
+                    &json!({ "type": "content_block_stop", "index": index }),
+                ));
+                self.stop_reason = Some("tool_use");
+            }
+        }
+
+        if candidate.get("finishReason").is_some() && self.stop_reason != Some("tool_use") {
+            self.stop_reason = Some(stop_reason(candidate, false));
+        }
+        frames
+    }
+
+    fn on_end(&mut self) -> Vec<Bytes> {
+        let mut frames = Vec::new();
+        self.start(&mut frames);
+        self.close_block(&mut frames);
+        let usage = usage_to_anthropic(self.usage.as_ref());
+        frames.push(event_frame(
+            "message_delta",
+            &json!({
+                "type": "message_delta",
+                "delta": {
+                    "stop_reason": self.stop_reason.unwrap_or("end_turn"),
+                    "stop_sequence": Value::Null,
+                },
+                "usage": { "output_tokens": usage["output_tokens"] },
+            }),
+        ));
+        frames.push(event_frame(
+            "message_stop",
+            &json!({ "type": "message_stop" }),
+        ));
+        frames
+    }
+}
+
+/* ---------- helpers ---------- */
+
+fn invalid(message: impl Into<String>) -> AppError {
+    AppError::InvalidRequest {
+        message: message.into(),
+    }
+}
+
+fn event_frame(event: &str, data: &Value) -> Bytes {
+    Bytes::from(format!("event: {event}\ndata: {data}\n\n"))
+}
+
+fn message_id() -> String {
+    format!("msg_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Appends parts to the conversation, merging consecutive turns of one role.
+fn push_content(contents: &mut Vec<Value>, role: &str, parts: Vec<Value>) {
+    if parts.is_empty() {
+        return;
+    }
+    if let Some(last) = contents.last_mut() {
+        if last["role"] == role {
+            if let Some(existing) = last["parts"].as_array_mut() {
+                existing.extend(parts);
+                return;
+            }
+        }
+    }
+    contents.push(json!({ "role": role, "parts": parts }));
+}
+
+fn blocks_to_parts(
+    content: &Value,
+    tool_names: &mut HashMap<String, String>,
+) -> Result<Vec<Value>> {
+    match content {
+        Value::Null => Ok(Vec::new()),
+        Value::String(text) if text.is_empty() => Ok(Vec::new()),
+        Value::String(text) => Ok(vec![json!({ "text": text })]),
+        Value::Array(blocks) => {
+            let mut parts = Vec::with_capacity(blocks.len());
+            for block in blocks {
+                if let Some(part) = block_to_part(block, tool_names)? {
+                    parts.push(part);
+                }
+            }
+            Ok(parts)
+        }
+        other => Err(invalid(format!("unsupported message content: {other}"))),
+    }
+}
+
+fn block_to_part(block: &Value, tool_names: &mut HashMap<String, String>) -> Result<Option<Value>> {
+    let kind = block.get("type").and_then(Value::as_str).unwrap_or("text");
+    let part = match kind {
+        "text" => json!({ "text": block.get("text").and_then(Value::as_str).unwrap_or_default() }),
+        "image" | "document" => source_to_part(block.get("source").unwrap_or(&Value::Null))?,
+        "tool_use" => {
+            let name = block
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            if let Some(id) = block.get("id").and_then(Value::as_str) {
+                tool_names.insert(id.to_string(), name.to_string());
+            }
+            json!({
+                "functionCall": {
+                    "name": name,
+                    "args": block.get("input").cloned().unwrap_or_else(|| json!({})),
+                }
+            })
+        }
+        "tool_result" => {
+            let name = block
+                .get("tool_use_id")
+                .and_then(Value::as_str)
+                .and_then(|id| tool_names.get(id).cloned())
+                .unwrap_or_default();
+            let text = match block.get("content") {
+                Some(Value::String(text)) => text.clone(),
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .filter_map(|item| item.get("text").and_then(Value::as_str))
+                    .collect::<Vec<_>>()
+                    .join(""),
+                _ => String::new(),
+            };
+            let key = if block.get("is_error").and_then(Value::as_bool) == Some(true) {
+                "error"
+            } else {
+                "content"
+            };
+            json!({ "functionResponse": { "name": name, "response": { key: text } } })
+        }
+        // Prior thinking is not replayable into Gemini; drop it.
+        "thinking" | "redacted_thinking" => return Ok(None),
+        other => return Err(invalid(format!("unsupported content block type `{other}`"))),
+    };
+    Ok(Some(part))
+}
+
+fn source_to_part(source: &Value) -> Result<Value> {
+    let media_type = source.get("media_type").and_then(Value::as_str);
+    match source.get("type").and_then(Value::as_str) {
+        Some("base64") => Ok(json!({
+            "inlineData": {
+                "mimeType": media_type.unwrap_or("image/jpeg"),
+                "data": source.get("data").and_then(Value::as_str).unwrap_or_default(),
+            }
+        })),
+        Some("url") => {
+            let mut file_data = json!({
+                "fileUri": source.get("url").and_then(Value::as_str).unwrap_or_default(),
+            });
+            if let Some(media_type) = media_type {
+                file_data["mimeType"] = json!(media_type);
+            }
+            Ok(json!({ "fileData": file_data }))
+        }
+        Some("text") => Ok(json!({
+            "text": source.get("data").and_then(Value::as_str).unwrap_or_default(),
+        })),
+        _ => Err(invalid("unsupported content source")),
+    }
+}
+
+fn function_declaration(tool: &Value) -> Option<Value> {
+    let mut declaration = Map::new();
+    declaration.insert("name".into(), tool.get("name")?.clone());
+    if let Some(description) = tool.get("description") {
+        declaration.insert("description".into(), description.clone());
+    }
+    if let Some(schema) = tool.get("input_schema") {
+        declaration.insert("parametersJsonSchema".into(), schema.clone());
+    }
+    Some(Value::Object(declaration))
+}
+
+fn tool_config_from(choice: &Value) -> Option<Value> {
+    let config = match choice.get("type").and_then(Value::as_str)? {
+        "none" => json!({ "mode": "NONE" }),
+        "any" => json!({ "mode": "ANY" }),
+        "tool" => json!({ "mode": "ANY", "allowedFunctionNames": [choice.get("name")?] }),
+        _ => json!({ "mode": "AUTO" }),
+    };
+    Some(json!({ "functionCallingConfig": config }))
+}
+
+fn candidate_parts(candidate: &Value) -> impl Iterator<Item = &Value> {
+    candidate
+        .pointer("/content/parts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+fn tool_use_block(call: &Value) -> Value {
+    let id = call
+        .get("id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("toolu_{}", uuid::Uuid::new_v4().simple()));
+    json!({
+        "type": "tool_use",
+        "id": id,
+        "name": call.get("name").cloned().unwrap_or(Value::Null),
+        "input": call.get("args").cloned().unwrap_or_else(|| json!({})),
+    })
+}
+
+fn stop_reason(candidate: &Value, has_tool_use: bool) -> &'static str {
+    if has_tool_use {
+        return "tool_use";
+    }
+    match candidate.get("finishReason").and_then(Value::as_str) {
+        Some("MAX_TOKENS") => "max_tokens",
+        Some(
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY",
+        ) => "refusal",
+        _ => "end_turn",
+    }
+}
+
+fn usage_to_anthropic(usage: Option<&Value>) -> Value {
+    let count = |field: &str| {
+        usage
+            .and_then(|u| u.get(field))
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+    };
+    let cached = count("cachedContentTokenCount");
+    json!({
+        "input_tokens": count("promptTokenCount").saturating_sub(cached),
+        "output_tokens": count("candidatesTokenCount") + count("thoughtsTokenCount"),
+        "cache_read_input_tokens": cached,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_frames(frames: &[Bytes]) -> Vec<(String, Value)> {
+        frames
+            .iter()
+            .map(|frame| {
+                let text = std::str::from_utf8(frame).unwrap();
+                let mut lines = text.lines();
+                let event = lines
+                    .next()
+                    .unwrap()
+                    .trim_start_matches("event: ")
+                    .to_string();
+                let data = lines.next().unwrap().trim_start_matches("data: ");
+                (event, serde_json::from_str(data).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_messages_request_translation() {
+        let request = json!({
+            "model": "gemini-2.5-flash",
+            "max_tokens": 256,
+            "system": [{"type": "text", "text": "You are helpful."}],
+            "stop_sequences": ["###"],
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "text", "text": "Describe this"},
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}}
+                ]},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_1", "name": "lookup", "input": {"q": "cat"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": "a cat"}
+                ]}
+            ],
+            "tools": [{"name": "lookup", "input_schema": {"type": "object"}}],
+            "tool_choice": {"type": "tool", "name": "lookup"}
+        });
+
+        let translated = messages_request_to_gemini(&request).unwrap();
+        let body = translated.body;
+
+        assert_eq!(
+            body["systemInstruction"]["parts"][0]["text"],
+            "You are helpful."
+        );
+        assert_eq!(
+            body["contents"][0]["parts"][1]["inlineData"]["mimeType"],
+            "image/png"
+        );
+        assert_eq!(body["contents"][1]["role"], "model");
+        assert_eq!(
+            body["contents"][1]["parts"][0]["functionCall"]["args"]["q"],
+            "cat"
+        );
+        assert_eq!(
+            body["contents"][2]["parts"][0]["functionResponse"]["name"],
+            "lookup"
+        );
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 256);
+        assert_eq!(body["generationConfig"]["stopSequences"], json!(["###"]));
+        assert_eq!(
+            body["toolConfig"]["functionCallingConfig"]["allowedFunctionNames"],
+            json!(["lookup"])
+        );
+    }
+
+    #[test]
+    fn test_gemini_response_to_message() {
+        let response = json!({
+            "candidates": [{
+                "content": {"parts": [
+                    {"text": "Let me check."},
+                    {"functionCall": {"name": "lookup", "args": {"q": "dog"}}}
+                ]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 7}
+        });
+
+        let message = gemini_response_to_message(&response, "gemini-2.5-flash");
+
+        assert_eq!(message["type"], "message");
+        assert_eq!(message["content"][0]["text"], "Let me check.");
+        assert_eq!(message["content"][1]["type"], "tool_use");
+        assert_eq!(message["content"][1]["input"]["q"], "dog");
+        assert_eq!(message["stop_reason"], "tool_use");
+        assert_eq!(message["usage"]["input_tokens"], 12);
+        assert_eq!(message["usage"]["output_tokens"], 7);
+    }
+
+    #[test]
+    fn test_stream_translator_event_sequence() {
+        let mut translator = MessagesStreamTranslator::new("gemini-2.5-flash");
+        let mut frames = translator.on_event(&json!({
+            "candidates": [{"content": {"parts": [{"text": "Hel"}]}}]
+        }));
+        frames.extend(translator.on_event(&json!({
+            "candidates": [{"content": {"parts": [{"text": "lo"}]}, "finishReason": "MAX_TOKENS"}],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2}
+        })));
+        frames.extend(translator.on_end());
+
+        let events = parse_frames(&frames);
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        assert_eq!(events[3].1["delta"]["text"], "lo");
+        assert_eq!(events[5].1["delta"]["stop_reason"], "max_tokens");
+        assert_eq!(events[5].1["usage"]["output_tokens"], 2);
+    }
+
+    #[test]
+    fn test_error_to_anthropic() {
+        let error = error_to_anthropic(
+            StatusCode::TOO_MANY_REQUESTS,
+            br#"{"error":{"message":"quota exhausted"}}"#,
+        );
+        assert_eq!(error["error"]["type"], "rate_limit_error");
+        assert_eq!(error["error"]["message"], "quota exhausted");
+    }
+}
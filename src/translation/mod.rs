@@ -0,0 +1,225 @@
+// src/translation/mod.rs
+
+//! Translation layers between client-facing API dialects and the native
+//! Gemini `generateContent` API.
+
+pub mod anthropic;
+pub mod ollama;
+pub mod openai;
+
+use axum::body::{Body, Bytes};
+use futures_util::StreamExt;
+use serde_json::Value;
+
+/// The native Gemini model method a translated request is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeminiMethod {
+    #[default]
+    GenerateContent,
+    BatchEmbedContents,
+}
+
+/// A request translated into a native Gemini model call.
+#[derive(Debug, Clone)]
+pub struct GeminiRequest {
+    /// Bare model name, without the `models/` prefix.
+    pub model: String,
+    pub method: GeminiMethod,
+    /// Native request body for `method`.
+    pub body: Value,
+    /// Whether the client asked for a streamed response.
+    pub stream: bool,
+}
+
+impl GeminiRequest {
+    /// Returns the upstream path (and query, for streaming) for this request.
+    pub fn path_and_query(&self) -> String {
+        match (self.method, self.stream) {
+            (GeminiMethod::GenerateContent, true) => format!(
+                "/v1beta/models/{}:streamGenerateContent?alt=sse",
+                self.model
+            ),
+            (GeminiMethod::GenerateContent, false) => {
+                format!("/v1beta/models/{}:generateContent", self.model)
+            }
+            (GeminiMethod::BatchEmbedContents, _) => {
+                format!("/v1beta/models/{}:batchEmbedContents", self.model)
+            }
+        }
+    }
+}
+
+/// Strips an optional `models/` prefix from a client-supplied model name.
+pub fn normalize_model_name(model: &str) -> String {
+    model.trim_start_matches("models/").to_string()
+}
+
+/// Converts a stream of upstream Gemini SSE events into another wire format.
+pub trait StreamTranslator: Send + 'static {
+    /// Called for every `data:` payload that parses as JSON.
+    fn on_event(&mut self, event: &Value) -> Vec<Bytes>;
+
+    /// Called once after the upstream stream has ended.
+    fn on_end(&mut self) -> Vec<Bytes>;
+}
+
+/// Wraps an upstream SSE body, feeding each event through `translator`.
+///
+/// Events are reassembled across chunk boundaries before parsing, so a JSON
+/// payload split over several network reads is still translated as one event.
+pub fn translate_event_stream<T: StreamTranslator>(body: Body, translator: T) -> Body {
+    let initial = (body.into_data_stream(), translator, Vec::new(), false);
+
+    let stream = futures_util::stream::unfold(
+        initial,
+        |(mut upstream, mut translator, mut buffer, done)| async move {
+            if done {
+                return None;
+            }
+            loop {
+                match upstream.next().await {
+                    Some(Ok(chunk)) => {
+                        buffer.extend(chunk.iter().filter(|b| **b != b'\r'));
+                        let frames = drain_events(&mut buffer, &mut translator);
+                        if !frames.is_empty() {
+                            return Some((
+                                Ok(concat(frames)),
+                                (upstream, translator, buffer, false),
+                            ));
+                        }
+                    }
+                    Some(Err(e)) => return Some((Err(e), (upstream, translator, buffer, true))),
+                    None => {
+                        if !buffer.is_empty() {
+                            buffer.extend_from_slice(b"\n\n");
+                        }
+                        let mut frames = drain_events(&mut buffer, &mut translator);
+                        frames.extend(translator.on_end());
+                        return Some((Ok(concat(frames)), (upstream, translator, buffer, true)));
+                    }
+                }
+            }
+        },
+    );
+
+    Body::from_stream(stream)
+}
+
+/// Parses every complete SSE event in a buffered body into JSON payloads.
+pub fn parse_sse_events(body: &[u8]) -> Vec<Value> {
+    let mut buffer: Vec<u8> = body.iter().copied().filter(|b| *b != b'\r').collect();
+    buffer.extend_from_slice(b"\n\n");
+    let mut events = Vec::new();
+    while let Some(event) = next_event(&mut buffer) {
+        events.extend(event);
+    }
+    events
+}
+
+/// Formats a JSON value as a single SSE `data:` frame.
+pub fn sse_frame(value: &Value) -> Bytes {
+    Bytes::from(format!("data: {value}\n\n"))
+}
+
+fn drain_events<T: StreamTranslator>(buffer: &mut Vec<u8>, translator: &mut T) -> Vec<Bytes> {
+    let mut frames = Vec::new();
+    while let Some(event) = next_event(buffer) {
+        if let Some(value) = event {
+            frames.extend(translator.on_event(&value));
+        }
+    }
+    frames
+}
+
+/// Removes the next complete event from `buffer`.
+///
+/// Returns `None` when no complete event is buffered yet, and `Some(None)` for
+/// events without a JSON payload (comments, keep-alives, `[DONE]`).
+fn next_event(buffer: &mut Vec<u8>) -> Option<Option<Value>> {
+    let end = buffer.windows(2).position(|w| w == b"\n\n")?;
+    let raw: Vec<u8> = buffer.drain(..end + 2).collect();
+    let text = String::from_utf8_lossy(&raw);
+
+    let data = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(str::trim_start)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if data.is_empty() || data == "[DONE]" {
+        return Some(None);
+    }
+    Some(serde_json::from_str(&data).ok())
+}
+
+fn concat(frames: Vec<Bytes>) -> Bytes {
+    if frames.len() == 1 {
+        return frames.into_iter().next().unwrap_or_default();
+    }
+    frames.concat().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use serde_json::json;
+
+    struct Echo;
+
+    impl StreamTranslator for Echo {
+        fn on_event(&mut self, event: &Value) -> Vec<Bytes> {
+            vec![Bytes::from(format!("{}\n", event["n"]))]
+        }
+
+        fn on_end(&mut self) -> Vec<Bytes> {
+            vec![Bytes::from_static(b"end\n")]
+        }
+    }
+
+    #[test]
+    fn test_parse_sse_events_handles_crlf_and_done() {
+        let body = b"data: {\"n\":1}\r\n\r\ndata: {\"n\":2}\n\ndata: [DONE]\n\n";
+        let events = parse_sse_events(body);
+        assert_eq!(events, vec![json!({"n": 1}), json!({"n": 2})]);
+    }
+
+    #[tokio::test]
+    async fn test_translate_event_stream_reassembles_split_events() {
+        let chunks: Vec<Result<Bytes, std::io::Error>> = vec![
+            Ok(Bytes::from_static(b"data: {\"n\"")),
+            Ok(Bytes::from_static(b":1}\n\ndata: {\"n\":2}")),
+        ];
+        let body = Body::from_stream(futures_util::stream::iter(chunks));
+
+        let translated = translate_event_stream(body, Echo);
+        let bytes = to_bytes(translated, usize::MAX).await.unwrap();
+
+        assert_eq!(&bytes[..], b"1\n2\nend\n");
+    }
+
+    #[test]
+    fn test_gemini_request_paths() {
+        let mut request = GeminiRequest {
+            model: "gemini-2.0-flash".to_string(),
+            method: GeminiMethod::GenerateContent,
+            body: json!({}),
+            stream: false,
+        };
+        assert_eq!(
+            request.path_and_query(),
+            "/v1beta/models/gemini-2.0-flash:generateContent"
+        );
+        request.stream = true;
+        assert_eq!(
+            request.path_and_query(),
+            "/v1beta/models/gemini-2.0-flash:streamGenerateContent?alt=sse"
+        );
+        request.method = GeminiMethod::BatchEmbedContents;
+        assert_eq!(
+            request.path_and_query(),
+            "/v1beta/models/gemini-2.0-flash:batchEmbedContents"
+        );
+    }
+}
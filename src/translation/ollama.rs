@@ -0,0 +1,670 @@
+// src/translation/ollama.rs
+
+//! Translation between the Ollama REST API (`/api/chat`, `/api/generate`,
+//! `/api/embed`, `/api/tags`) and native Gemini calls.
+
+use super::{normalize_model_name, GeminiMethod, GeminiRequest, StreamTranslator};
+use crate::error::{AppError, Result};
+use axum::body::Bytes;
+use serde_json::{json, Map, Value};
+use std::time::Instant;
+
+/// Which Ollama endpoint a generation request came from. The two share a
+/// request pipeline but differ in where the generated text is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OllamaEndpoint {
+    Chat,
+    Generate,
+}
+
+/// Translates an `/api/chat` request into a native Gemini request.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRequest` if `model` or `messages` is missing.
+pub fn chat_request_to_gemini(request: &Value) -> Result<GeminiRequest> {
+    let model = model_of(request)?;
+    let messages = request
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("`messages` must be an array"))?;
+
+    let mut body = Map::new();
+    let mut system_parts = Vec::new();
+    let mut contents: Vec<Value> = Vec::new();
+    let mut last_tool_name = String::new();
+
+    for message in messages {
+        let content = message.get("content").and_then(Value::as_str).unwrap_or("");
+        match message
+            .get("role")
+            .and_then(Value::as_str)
+            .unwrap_or("user")
+        {
+            "system" => {
+                if !content.is_empty() {
+                    system_parts.push(json!({ "text": content }));
+                }
+            }
+            "assistant" => {
+                let mut parts = text_parts(content);
+                for call in message
+                    .get("tool_calls")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    let function = call.get("function").unwrap_or(&Value::Null);
+                    let name = function
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    last_tool_name = name.to_string();
+                    parts.push(json!({
+                        "functionCall": {
+                            "name": name,
+                            "args": function.get("arguments").cloned().unwrap_or_else(|| json!({})),
+                        }
+                    }));
+                }
+                push_content(&mut contents, "model", parts);
+            }
+            "tool" => {
+                let name = message
+                    .get("tool_name")
+                    .and_then(Value::as_str)
+                    .unwrap_or(&last_tool_name);
+                let response = match serde_json::from_str::<Value>(content) {
+                    Ok(value @ Value::Object(_)) => value,
+                    _ => json!({ "content": content }),
+                };
+                push_content(
+                    &mut contents,
+                    "user",
+                    vec![json!({ "functionResponse": { "name": name, "response": response } })],
+                );
+            }
+            _ => {
+                let mut parts = text_parts(content);
+                parts.extend(image_parts(message));
+                push_content(&mut contents, "user", parts);
+            }
+        }
+    }
+
+    if !system_parts.is_empty() {
+        body.insert("systemInstruction".into(), json!({ "parts": system_parts }));
+    }
+    body.insert("contents".into(), Value::Array(contents));
+
+    if let Some(tools) = request.get("tools").and_then(Value::as_array) {
+        let declarations: Vec<Value> = tools.iter().filter_map(function_declaration).collect();
+        if !declarations.is_empty() {
+            body.insert(
+                "tools".into(),
+                json!([{ "functionDeclarations": declarations }]),
+            );
+        }
+    }
+    insert_generation_config(request, &mut body);
+
+    Ok(GeminiRequest {
+        model,
+        method: GeminiMethod::GenerateContent,
+        body: Value::Object(body),
+        stream: is_streaming(request),
+    })
+}
+
+/// Translates an `/api/generate` request into a native Gemini request.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRequest` if `model` is missing.
+pub fn generate_request_to_gemini(request: &Value) -> Result<GeminiRequest> {
+    let model = model_of(request)?;
+    let prompt = request.get("prompt").and_then(Value::as_str).unwrap_or("");
+
+    let mut body = Map::new();
+    if let Some(system) = request
+        .get("system")
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+    {
+        body.insert(
+            "systemInstruction".into(),
+            json!({ "parts": [{ "text": system }] }),
+        );
+    }
+    let mut parts = text_parts(prompt);
+    parts.extend(image_parts(request));
+    body.insert(
+        "contents".into(),
+        json!([{ "role": "user", "parts": parts }]),
+    );
+    insert_generation_config(request, &mut body);
+
+    Ok(GeminiRequest {
+        model,
+        method: GeminiMethod::GenerateContent,
+        body: Value::Object(body),
+        stream: is_streaming(request),
+    })
+}
+
+/// Translates an `/api/embed` request into a Gemini `batchEmbedContents` call.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRequest` if `model` or `input` is missing.
+pub fn embed_request_to_gemini(request: &Value) -> Result<GeminiRequest> {
+    let model = model_of(request)?;
+    let inputs: Vec<&str> = match request.get("input") {
+        Some(Value::String(text)) => vec![text.as_str()],
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+        _ => return Err(invalid("`input` must be a string or an array of strings")),
+    };
+    let dimensions = request.get("dimensions").and_then(Value::as_u64);
+
+    let requests: Vec<Value> = inputs
+        .into_iter()
+        .map(|text| {
+            let mut entry = json!({
+                "model": format!("models/{model}"),
+                "content": { "parts": [{ "text": text }] },
+            });
+            if let Some(dimensions) = dimensions {
+                entry["outputDimensionality"] = json!(dimensions);
+            }
+            entry
+        })
+        .collect();
+
+    Ok(GeminiRequest {
+        model,
+        method: GeminiMethod::BatchEmbedContents,
+        body: json!({ "requests": requests }),
+        stream: false,
+    })
+}
+
+/// Translates a buffered Gemini `GenerateContentResponse` into the final
+/// (`done: true`) Ollama response object.
+pub fn gemini_response_to_ollama(
+    response: &Value,
+    model: &str,
+    endpoint: OllamaEndpoint,
+    started: Instant,
+) -> Value {
+    let candidate = response.pointer("/candidates/0").unwrap_or(&Value::Null);
+    let (text, thinking, tool_calls) = split_parts(candidate);
+
+    let mut object = base_object(model);
+    insert_output(&mut object, endpoint, text, thinking, tool_calls);
+    insert_final_stats(
+        &mut object,
+        done_reason(candidate),
+        response.get("usageMetadata"),
+        started,
+    );
+    object
+}
+
+/// Translates a Gemini `batchEmbedContents` response into an `/api/embed`
+/// response.
+pub fn embed_response_to_ollama(response: &Value, model: &str) -> Value {
+    let embeddings: Vec<Value> = response
+        .get("embeddings")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|embedding| {
+            embedding
+                .get("values")
+                .cloned()
+                .unwrap_or_else(|| json!([]))
+        })
+        .collect();
+    json!({ "model": model, "embeddings": embeddings })
+}
+
+/// Builds an `/api/tags` listing for the given model names.
+pub fn tags_response(models: &[String]) -> Value {
+    let modified_at = chrono::Utc::now().to_rfc3339();
+    let models: Vec<Value> = models
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "model": name,
+                "modified_at": modified_at,
+                "size": 0,
+                "digest": "",
+                "details": {
+                    "format": "gemini",
+                    "family": "gemini",
+                    "families": ["gemini"],
+                    "parameter_size": "",
+                    "quantization_level": "",
+                },
+            })
+        })
+        .collect();
+    json!({ "models": models })
+}
+
+/// Converts an upstream error body into Ollama's `{"error": "..."}` shape.
+pub fn error_to_ollama(body: &[u8]) -> Value {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    json!({ "error": message })
+}
+
+/// Streams Gemini SSE events out as Ollama newline-delimited JSON.
+pub struct NdjsonStreamTranslator {
+    model: String,
+    endpoint: OllamaEndpoint,
+    started: Instant,
+    done_reason: &'static str,
+    usage: Option<Value>,
+}
+
+impl NdjsonStreamTranslator {
+    pub fn new(model: impl Into<String>, endpoint: OllamaEndpoint, started: Instant) -> Self {
+        Self {
+            model: model.into(),
+            endpoint,
+            started,
+            done_reason: "stop",
+            usage: None,
+        }
+    }
+}
+
+impl StreamTranslator for NdjsonStreamTranslator {
+    fn on_event(&mut self, event: &Value) -> Vec<Bytes> {
+        if event.get("error").is_some() {
+            return vec![ndjson_line(&error_to_ollama(event.to_string().as_bytes()))];
+        }
+        if let Some(usage) = event.get("usageMetadata") {
+            self.usage = Some(usage.clone());
+        }
+        let candidate = event.pointer("/candidates/0").unwrap_or(&Value::Null);
+        if candidate.get("finishReason").is_some() {
+            self.done_reason = done_reason(candidate);
+        }
+
+        let (text, thinking, tool_calls) = split_parts(candidate);
+        if text.is_empty() && thinking.is_empty() && tool_calls.is_empty() {
+            return Vec::new();
+        }
+        let mut object = base_object(&self.model);
+        insert_output(&mut object, self.endpoint, text, thinking, tool_calls);
+        object["done"] = json!(false);
+        vec![ndjson_line(&object)]
+    }
+
+    fn on_end(&mut self) -> Vec<Bytes> {
+        let mut object = base_object(&self.model);
+        insert_output(
+            &mut object,
+            self.endpoint,
+            String::new(),
+            String::new(),
+            Vec::new(),
+        );
+        insert_final_stats(
+            &mut object,
+            self.done_reason,
+            self.usage.as_ref(),
+            self.started,
+        );
+        vec![ndjson_line(&object)]
+    }
+}
+
+/* ---------- helpers ---------- */
+
+fn invalid(message: impl Into<String>) -> AppError {
+    AppError::InvalidRequest {
+        message: message.into(),
+    }
+}
+
+/// Ollama names carry a tag (`gemini-2.0-flash:latest`); Gemini has none.
+fn model_of(request: &Value) -> Result<String> {
+    let model = request
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("`model` is required"))?;
+    Ok(normalize_model_name(
+        model.strip_suffix(":latest").unwrap_or(model),
+    ))
+}
+
+/// Ollama streams unless the client explicitly opts out.
+fn is_streaming(request: &Value) -> bool {
+    request
+        .get("stream")
+        .and_then(Value::as_bool)
+        .unwrap_or(true)
+}
+
+fn ndjson_line(value: &Value) -> Bytes {
+    Bytes::from(format!("{value}\n"))
+}
+
+fn text_parts(text: &str) -> Vec<Value> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "text": text })]
+    }
+}
+
+/// Ollama sends images as bare base64; the MIME type is sniffed from the
+/// leading bytes of the encoded data.
+fn image_parts(message: &Value) -> Vec<Value> {
+    message
+        .get("images")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|data| {
+            let mime_type = match data.get(..6).unwrap_or(data) {
+                d if d.starts_with("iVBOR") => "image/png",
+                d if d.starts_with("R0lGOD") => "image/gif",
+                d if d.starts_with("UklGR") => "image/webp",
+                _ => "image/jpeg",
+            };
+            json!({ "inlineData": { "mimeType": mime_type, "data": data } })
+        })
+        .collect()
+}
+
+/// Appends parts to the conversation, merging consecutive turns of one role.
+fn push_content(contents: &mut Vec<Value>, role: &str, parts: Vec<Value>) {
+    if parts.is_empty() {
+        return;
+    }
+    if let Some(last) = contents.last_mut() {
+        if last["role"] == role {
+            if let Some(existing) = last["parts"].as_array_mut() {
+                existing.extend(parts);
+                return;
+            }
+        }
+    }
+    contents.push(json!({ "role": role, "parts": parts }));
+}
+
+fn function_declaration(tool: &Value) -> Option<Value> {
+    let function = tool.get("function")?;
+    let mut declaration = Map::new();
+    declaration.insert("name".into(), function.get("name")?.clone());
+    if let Some(description) = function.get("description") {
+        declaration.insert("description".into(), description.clone());
+    }
+    if let Some(parameters) = function.get("parameters") {
+        declaration.insert("parametersJsonSchema".into(), parameters.clone());
+    }
+    Some(Value::Object(declaration))
+}
+
+fn insert_generation_config(request: &Value, body: &mut Map<String, Value>) {
+    let mut config = Map::new();
+    if let Some(options) = request.get("options").and_then(Value::as_object) {
+        for (from, to) in [
+            ("temperature", "temperature"),
+            ("top_p", "topP"),
+            ("top_k", "topK"),
+            ("seed", "seed"),
+            ("presence_penalty", "presencePenalty"),
+            ("frequency_penalty", "frequencyPenalty"),
+            ("stop", "stopSequences"),
+        ] {
+            if let Some(value) = options.get(from).filter(|v| !v.is_null()) {
+                config.insert(to.into(), value.clone());
+            }
+        }
+        // Ollama uses -1/-2 for "unlimited" and "fill context".
+        if let Some(limit) = options
+            .get("num_predict")
+            .and_then(Value::as_i64)
+            .filter(|n| *n > 0)
+        {
+            config.insert("maxOutputTokens".into(), json!(limit));
+        }
+    }
+    match request.get("format") {
+        Some(Value::String(format)) if format == "json" => {
+            config.insert("responseMimeType".into(), json!("application/json"));
+        }
+        Some(schema @ Value::Object(_)) => {
+            config.insert("responseMimeType".into(), json!("application/json"));
+            config.insert("responseJsonSchema".into(), schema.clone());
+        }
+        _ => {}
+    }
+    if let Some(think) = request.get("think").and_then(Value::as_bool) {
+        config.insert(
+            "thinkingConfig".into(),
+            if think {
+                json!({ "includeThoughts": true })
+            } else {
+                json!({ "thinkingBudget": 0 })
+            },
+        );
+    }
+    if !config.is_empty() {
+        body.insert("generationConfig".into(), Value::Object(config));
+    }
+}
+
+fn base_object(model: &str) -> Value {
+    json!({
+        "model": model,
+        "created_at": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+    })
+}
+
+/// Splits a candidate into visible text, thought text and Ollama tool calls.
+fn split_parts(candidate: &Value) -> (String, String, Vec<Value>) {
+    let mut text = String::new();
+    let mut thinking = String::new();
+    let mut tool_calls = Vec::new();
+    for part in candidate
+        .pointer("/content/parts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(t) = part.get("text").and_then(Value::as_str) {
+            if part.get("thought").and_then(Value::as_bool) == Some(true) {
+                thinking.push_str(t);
+            } else {
+                text.push_str(t);
+            }
+        } else if let Some(call) = part.get("functionCall") {
+            tool_calls.push(json!({
+                "function": {
+                    "name": call.get("name").cloned().unwrap_or(Value::Null),
+                    "arguments": call.get("args").cloned().unwrap_or_else(|| json!({})),
+                }
+            }));
+        }
+    }
+    (text, thinking, tool_calls)
+}
+
+fn insert_output(
+    object: &mut Value,
+    endpoint: OllamaEndpoint,
+    text: String,
+    thinking: String,
+    tool_calls: Vec<Value>,
+) {
+    match endpoint {
+        OllamaEndpoint::Chat => {
+            let mut message = json!({ "role": "assistant", "content": text });
+            if !thinking.is_empty() {
+                message["thinking"] = json!(thinking);
+            }
+            if !tool_calls.is_empty() {
+                message["tool_calls"] = Value::Array(tool_calls);
+            }
+            object["message"] = message;
+        }
+        OllamaEndpoint::Generate => {
+            object["response"] = json!(text);
+            if !thinking.is_empty() {
+                object["thinking"] = json!(thinking);
+            }
+        }
+    }
+}
+
+fn insert_final_stats(
+    object: &mut Value,
+    done_reason: &str,
+    usage: Option<&Value>,
+    started: Instant,
+) {
+    let count = |field: &str| {
+        usage
+            .and_then(|u| u.get(field))
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+    };
+    object["done"] = json!(true);
+    object["done_reason"] = json!(done_reason);
+    object["total_duration"] = json!(started.elapsed().as_nanos() as u64);
+    object["prompt_eval_count"] = json!(count("promptTokenCount"));
+    object["eval_count"] = json!(count("candidatesTokenCount") + count("thoughtsTokenCount"));
+}
+
+fn done_reason(candidate: &Value) -> &'static str {
+    match candidate.get("finishReason").and_then(Value::as_str) {
+        Some("MAX_TOKENS") => "length",
+        _ => "stop",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_request_translation() {
+        let request = json!({
+            "model": "gemini-2.0-flash:latest",
+            "messages": [
+                {"role": "system", "content": "Be kind."},
+                {"role": "user", "content": "What is in this image?", "images": ["iVBORw0KGgo="]}
+            ],
+            "options": {"temperature": 0.1, "num_predict": 32, "stop": ["\n\n"]},
+            "format": "json"
+        });
+
+        let translated = chat_request_to_gemini(&request).unwrap();
+
+        assert_eq!(translated.model, "gemini-2.0-flash");
+        assert!(translated.stream, "Ollama streams by default");
+        let body = translated.body;
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be kind.");
+        assert_eq!(
+            body["contents"][0]["parts"][1]["inlineData"]["mimeType"],
+            "image/png"
+        );
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 32);
+        assert_eq!(
+            body["generationConfig"]["responseMimeType"],
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn test_generate_and_embed_request_translation() {
+        let generate = generate_request_to_gemini(&json!({
+            "model": "gemini-2.0-flash",
+            "prompt": "Why is the sky blue?",
+            "system": "Answer briefly.",
+            "stream": false
+        }))
+        .unwrap();
+        assert!(!generate.stream);
+        assert_eq!(
+            generate.body["contents"][0]["parts"][0]["text"],
+            "Why is the sky blue?"
+        );
+
+        let embed = embed_request_to_gemini(&json!({
+            "model": "text-embedding-004",
+            "input": ["a", "b"]
+        }))
+        .unwrap();
+        assert_eq!(embed.method, GeminiMethod::BatchEmbedContents);
+        assert_eq!(
+            embed.body["requests"][1]["model"],
+            "models/text-embedding-004"
+        );
+        assert_eq!(
+            embed.body["requests"][1]["content"]["parts"][0]["text"],
+            "b"
+        );
+    }
+
+    #[test]
+    fn test_buffered_responses() {
+        let response = json!({
+            "candidates": [{"content": {"parts": [{"text": "Rayleigh"}]}, "finishReason": "STOP"}],
+            "usageMetadata": {"promptTokenCount": 6, "candidatesTokenCount": 1}
+        });
+
+        let chat = gemini_response_to_ollama(&response, "m", OllamaEndpoint::Chat, Instant::now());
+        assert_eq!(chat["message"]["content"], "Rayleigh");
+        assert_eq!(chat["done"], true);
+        assert_eq!(chat["prompt_eval_count"], 6);
+
+        let generate =
+            gemini_response_to_ollama(&response, "m", OllamaEndpoint::Generate, Instant::now());
+        assert_eq!(generate["response"], "Rayleigh");
+        assert_eq!(generate["done_reason"], "stop");
+
+        let embed = embed_response_to_ollama(
+            &json!({"embeddings": [{"values": [0.1, 0.2]}]}),
+            "text-embedding-004",
+        );
+        assert_eq!(embed["embeddings"], json!([[0.1, 0.2]]));
+    }
+
+    #[test]
+    fn test_ndjson_stream_translator() {
+        let mut translator = NdjsonStreamTranslator::new("m", OllamaEndpoint::Chat, Instant::now());
+        let mut lines = translator.on_event(&json!({
+            "candidates": [{"content": {"parts": [{"text": "Hi"}]}, "finishReason": "MAX_TOKENS"}],
+            "usageMetadata": {"promptTokenCount": 2, "candidatesTokenCount": 1}
+        }));
+        lines.extend(translator.on_end());
+
+        let objects: Vec<Value> = lines
+            .iter()
+            .map(|line| {
+                assert!(line.ends_with(b"\n"));
+                serde_json::from_slice(line).unwrap()
+            })
+            .collect();
+        assert_eq!(objects[0]["message"]["content"], "Hi");
+        assert_eq!(objects[0]["done"], false);
+        assert_eq!(objects[1]["done"], true);
+        assert_eq!(objects[1]["done_reason"], "length");
+        assert_eq!(objects[1]["eval_count"], 1);
+    }
+}
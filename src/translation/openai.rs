@@ -0,0 +1,891 @@
+// src/translation/openai.rs
+
+//! Native translation between OpenAI `chat/completions` and Gemini
+//! `generateContent`.
+//!
+//! Gemini-only features that the OpenAI schema has no room for are accepted
+//! through a `google` extension object, either at the top level of the request
+//! or nested under `extra_body` (the shape the official OpenAI SDKs produce):
+//!
+//! ```json
+//! { "extra_body": { "google": {
+//!     "safety_settings": [...],
+//!     "thinking_config": { "thinking_budget": 1024, "include_thoughts": true },
+//!     "cached_content": "cachedContents/abc",
+//!     "generation_config": { "response_modalities": ["TEXT"] }
+//! } } }
+//! ```
+
+use super::{normalize_model_name, sse_frame, GeminiMethod, GeminiRequest, StreamTranslator};
+use crate::error::{AppError, Result};
+use axum::body::Bytes;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Returns true if `path` is an OpenAI chat completions endpoint.
+pub fn is_chat_completions_path(path: &str) -> bool {
+    matches!(
+        path,
+        "/v1/chat/completions" | "/chat/completions" | "/v1beta/openai/chat/completions"
+    )
+}
+
+/// Returns true if the client asked for a trailing usage chunk in a stream.
+pub fn wants_stream_usage(request: &Value) -> bool {
+    request
+        .pointer("/stream_options/include_usage")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Translates an OpenAI chat completions request into a native Gemini request.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRequest` if the body is missing `model` or
+/// `messages`, or uses a content part type that has no Gemini equivalent.
+pub fn chat_request_to_gemini(request: &Value) -> Result<GeminiRequest> {
+    let obj = request
+        .as_object()
+        .ok_or_else(|| invalid("request body must be a JSON object"))?;
+    let model = obj
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("`model` is required"))?;
+    let messages = obj
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("`messages` must be an array"))?;
+
+    let mut body = Map::new();
+    let (system_parts, contents) = messages_to_contents(messages)?;
+    if !system_parts.is_empty() {
+        body.insert("systemInstruction".into(), json!({ "parts": system_parts }));
+    }
+    body.insert("contents".into(), Value::Array(contents));
+
+    let mut generation_config = generation_config_from(obj);
+
+    if let Some(tools) = obj.get("tools").and_then(Value::as_array) {
+        let declarations: Vec<Value> = tools.iter().filter_map(function_declaration).collect();
+        if !declarations.is_empty() {
+            body.insert(
+                "tools".into(),
+                json!([{ "functionDeclarations": declarations }]),
+            );
+        }
+    }
+    if let Some(tool_config) = obj.get("tool_choice").and_then(tool_config_from) {
+        body.insert("toolConfig".into(), tool_config);
+    }
+
+    if let Some(google) = google_extensions(obj) {
+        apply_google_extensions(google, &mut body, &mut generation_config);
+    }
+    if !generation_config.is_empty() {
+        body.insert("generationConfig".into(), Value::Object(generation_config));
+    }
+
+    Ok(GeminiRequest {
+        model: normalize_model_name(model),
+        method: GeminiMethod::GenerateContent,
+        body: Value::Object(body),
+        stream: obj.get("stream").and_then(Value::as_bool).unwrap_or(false),
+    })
+}
+
+/// Translates a buffered Gemini `GenerateContentResponse` into a
+/// `chat.completion` object.
+pub fn gemini_response_to_chat(response: &Value, model: &str) -> Value {
+    let candidates = candidates_of(response);
+
+    let choices: Vec<Value> = if candidates.is_empty() {
+        // The prompt itself was blocked; surface it as a filtered empty choice.
+        vec![json!({
+            "index": 0,
+            "message": { "role": "assistant", "content": Value::Null },
+            "finish_reason": "content_filter",
+        })]
+    } else {
+        candidates
+            .iter()
+            .enumerate()
+            .map(|(position, candidate)| {
+                let index = candidate_index(candidate, position);
+                let (text, tool_calls) = split_candidate_parts(candidate);
+                let mut message = json!({
+                    "role": "assistant",
+                    "content": if text.is_empty() { Value::Null } else { Value::String(text) },
+                });
+                let has_tool_calls = !tool_calls.is_empty();
+                if has_tool_calls {
+                    message["tool_calls"] = Value::Array(tool_calls);
+                }
+                json!({
+                    "index": index,
+                    "message": message,
+                    "finish_reason": finish_reason(candidate, has_tool_calls),
+                })
+            })
+            .collect()
+    };
+
+    let mut completion = json!({
+        "id": completion_id(),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": model,
+        "choices": choices,
+    });
+    if let Some(usage) = response.get("usageMetadata") {
+        completion["usage"] = usage_to_openai(usage);
+    }
+    completion
+}
+
+/// Converts Gemini `usageMetadata` into an OpenAI `usage` object.
+pub fn usage_to_openai(usage: &Value) -> Value {
+    let count = |field: &str| usage.get(field).and_then(Value::as_u64).unwrap_or(0);
+    let prompt = count("promptTokenCount");
+    let candidates = count("candidatesTokenCount");
+    let thoughts = count("thoughtsTokenCount");
+    let completion = candidates + thoughts;
+
+    json!({
+        "prompt_tokens": prompt,
+        "completion_tokens": completion,
+        "total_tokens": usage
+            .get("totalTokenCount")
+            .and_then(Value::as_u64)
+            .unwrap_or(prompt + completion),
+        "prompt_tokens_details": { "cached_tokens": count("cachedContentTokenCount") },
+        "completion_tokens_details": { "reasoning_tokens": thoughts },
+    })
+}
+
+/// Streams Gemini SSE events out as `chat.completion.chunk` events.
+pub struct ChatStreamTranslator {
+    id: String,
+    created: i64,
+    model: String,
+    include_usage: bool,
+    usage: Option<Value>,
+    started: HashSet<u64>,
+    tool_call_counts: HashMap<u64, usize>,
+}
+
+impl ChatStreamTranslator {
+    pub fn new(model: impl Into<String>, include_usage: bool) -> Self {
+        Self {
+            id: completion_id(),
+            created: chrono::Utc::now().timestamp(),
+            model: model.into(),
+            include_usage,
+            usage: None,
+            started: HashSet::new(),
+            tool_call_counts: HashMap::new(),
+        }
+    }
+
+    fn chunk(&self, choices: Vec<Value>) -> Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": choices,
+        })
+    }
+}
+
+impl StreamTranslator for ChatStreamTranslator {
+    fn on_event(&mut self, event: &Value) -> Vec<Bytes> {
+        if event.get("error").is_some() {
+            return vec![sse_frame(event)];
+        }
+        if let Some(usage) = event.get("usageMetadata") {
+            self.usage = Some(usage.clone());
+        }
+
+        let mut choices = Vec::new();
+        for (position, candidate) in candidates_of(event).iter().enumerate() {
+            let index = candidate_index(candidate, position);
+            let (text, tool_calls) = split_candidate_parts(candidate);
+
+            let mut delta = Map::new();
+            if self.started.insert(index) {
+                delta.insert("role".into(), json!("assistant"));
+            }
+            if !text.is_empty() {
+                delta.insert("content".into(), Value::String(text));
+            }
+            if !tool_calls.is_empty() {
+                let offset = self.tool_call_counts.entry(index).or_insert(0);
+                let indexed: Vec<Value> = tool_calls
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, mut call)| {
+                        call["index"] = json!(*offset + i);
+                        call
+                    })
+                    .collect();
+                *offset += indexed.len();
+                delta.insert("tool_calls".into(), Value::Array(indexed));
+            }
+
+            let finish = candidate.get("finishReason").map(|_| {
+                finish_reason(
+                    candidate,
+                    self.tool_call_counts.get(&index).copied() > Some(0),
+                )
+            });
+            if delta.is_empty() && finish.is_none() {
+                continue;
+            }
+            choices.push(json!({
+                "index": index,
+                "delta": delta,
+                "finish_reason": finish.map_or(Value::Null, Value::from),
+            }));
+        }
+
+        if choices.is_empty() {
+            return Vec::new();
+        }
+        vec![sse_frame(&self.chunk(choices))]
+    }
+
+    fn on_end(&mut self) -> Vec<Bytes> {
+        let mut frames = Vec::new();
+        if self.include_usage {
+            if let Some(usage) = &self.usage {
+                let mut chunk = self.chunk(Vec::new());
+                chunk["usage"] = usage_to_openai(usage);
+                frames.push(sse_frame(&chunk));
+            }
+        }
+        frames.push(Bytes::from_static(b"data: [DONE]\n\n"));
+        frames
+    }
+}
+
+/* ---------- request helpers ---------- */
+
+fn invalid(message: impl Into<String>) -> AppError {
+    AppError::InvalidRequest {
+        message: message.into(),
+    }
+}
+
+fn messages_to_contents(messages: &[Value]) -> Result<(Vec<Value>, Vec<Value>)> {
+    let mut system_parts = Vec::new();
+    let mut contents: Vec<Value> = Vec::new();
+    // Tool results only carry the call id; Gemini wants the function name.
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+
+    for message in messages {
+        let role = message
+            .get("role")
+            .and_then(Value::as_str)
+            .unwrap_or("user");
+        match role {
+            "system" | "developer" => {
+                system_parts.extend(content_to_parts(message.get("content"))?);
+            }
+            "assistant" => {
+                let mut parts = content_to_parts(message.get("content"))?;
+                for call in message
+                    .get("tool_calls")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    let function = call.get("function").unwrap_or(&Value::Null);
+                    let name = function
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    if let Some(id) = call.get("id").and_then(Value::as_str) {
+                        tool_names.insert(id.to_string(), name.to_string());
+                    }
+                    parts.push(json!({
+                        "functionCall": { "name": name, "args": parse_arguments(function.get("arguments")) }
+                    }));
+                }
+                push_content(&mut contents, "model", parts);
+            }
+            "tool" | "function" => {
+                let name = message
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .or_else(|| {
+                        message
+                            .get("tool_call_id")
+                            .and_then(Value::as_str)
+                            .and_then(|id| tool_names.get(id).cloned())
+                    })
+                    .unwrap_or_default();
+                let response = tool_response_value(message.get("content"));
+                push_content(
+                    &mut contents,
+                    "user",
+                    vec![json!({ "functionResponse": { "name": name, "response": response } })],
+                );
+            }
+            _ => push_content(
+                &mut contents,
+                "user",
+                content_to_parts(message.get("content"))?,
+            ),
+        }
+    }
+
+    Ok((system_parts, contents))
+}
+
+/// Appends parts to the conversation, merging consecutive turns of one role.
+fn push_content(contents: &mut Vec<Value>, role: &str, parts: Vec<Value>) {
+    if parts.is_empty() {
+        return;
+    }
+    if let Some(last) = contents.last_mut() {
+        if last["role"] == role {
+            if let Some(existing) = last["parts"].as_array_mut() {
+                existing.extend(parts);
+                return;
+            }
+        }
+    }
+    contents.push(json!({ "role": role, "parts": parts }));
+}
+
+fn content_to_parts(content: Option<&Value>) -> Result<Vec<Value>> {
+    match content {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(text)) if text.is_empty() => Ok(Vec::new()),
+        Some(Value::String(text)) => Ok(vec![json!({ "text": text })]),
+        Some(Value::Array(items)) => items.iter().map(content_part).collect(),
+        Some(other) => Err(invalid(format!("unsupported message content: {other}"))),
+    }
+}
+
+fn content_part(part: &Value) -> Result<Value> {
+    let kind = part.get("type").and_then(Value::as_str).unwrap_or("text");
+    match kind {
+        "text" => {
+            Ok(json!({ "text": part.get("text").and_then(Value::as_str).unwrap_or_default() }))
+        }
+        "image_url" => {
+            let url = part
+                .get("image_url")
+                .and_then(|u| u.get("url").or(Some(u)))
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("image_url part is missing `url`"))?;
+            Ok(url_part(url, guess_mime_type(url, "image/jpeg")))
+        }
+        "input_audio" => {
+            let audio = part.get("input_audio").unwrap_or(&Value::Null);
+            let data = audio
+                .get("data")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("input_audio part is missing `data`"))?;
+            let format = audio.get("format").and_then(Value::as_str).unwrap_or("wav");
+            Ok(json!({ "inlineData": { "mimeType": format!("audio/{format}"), "data": data } }))
+        }
+        "file" => {
+            let file = part.get("file").unwrap_or(&Value::Null);
+            if let Some(data) = file.get("file_data").and_then(Value::as_str) {
+                let fallback = file
+                    .get("filename")
+                    .and_then(Value::as_str)
+                    .map_or("application/pdf", |name| {
+                        guess_mime_type(name, "application/pdf")
+                    });
+                Ok(url_part(data, fallback))
+            } else if let Some(id) = file.get("file_id").and_then(Value::as_str) {
+                Ok(json!({ "fileData": { "fileUri": id } }))
+            } else {
+                Err(invalid("file part needs `file_data` or `file_id`"))
+            }
+        }
+        other => Err(invalid(format!("unsupported content part type `{other}`"))),
+    }
+}
+
+/// Builds an inline part from a data URL (or bare base64), or a file part
+/// referencing a remote URI.
+fn url_part(url: &str, fallback_mime: &str) -> Value {
+    if let Some(rest) = url.strip_prefix("data:") {
+        if let Some((meta, data)) = rest.split_once(',') {
+            let mime = meta.split(';').next().filter(|m| !m.is_empty());
+            return json!({
+                "inlineData": { "mimeType": mime.unwrap_or(fallback_mime), "data": data }
+            });
+        }
+    }
+    if url.starts_with("http://") || url.starts_with("https://") || url.starts_with("gs://") {
+        return json!({ "fileData": { "mimeType": fallback_mime, "fileUri": url } });
+    }
+    json!({ "inlineData": { "mimeType": fallback_mime, "data": url } })
+}
+
+fn guess_mime_type<'a>(name: &str, fallback: &'a str) -> &'a str {
+    let extension = name
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "heic" => "image/heic",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mp3",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        _ => fallback,
+    }
+}
+
+fn parse_arguments(arguments: Option<&Value>) -> Value {
+    match arguments {
+        Some(Value::String(raw)) => serde_json::from_str(raw).unwrap_or_else(|_| json!({})),
+        Some(value @ Value::Object(_)) => value.clone(),
+        _ => json!({}),
+    }
+}
+
+fn tool_response_value(content: Option<&Value>) -> Value {
+    let text = match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join(""),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    };
+    match serde_json::from_str::<Value>(&text) {
+        Ok(value @ Value::Object(_)) => value,
+        Ok(value) => json!({ "content": value }),
+        Err(_) => json!({ "content": text }),
+    }
+}
+
+fn generation_config_from(obj: &Map<String, Value>) -> Map<String, Value> {
+    let mut config = Map::new();
+    let mut copy = |from: &str, to: &str| {
+        if let Some(value) = obj.get(from).filter(|v| !v.is_null()) {
+            config.insert(to.to_string(), value.clone());
+        }
+    };
+    copy("temperature", "temperature");
+    copy("top_p", "topP");
+    copy("top_k", "topK");
+    copy("max_tokens", "maxOutputTokens");
+    copy("max_completion_tokens", "maxOutputTokens");
+    copy("n", "candidateCount");
+    copy("presence_penalty", "presencePenalty");
+    copy("frequency_penalty", "frequencyPenalty");
+    copy("seed", "seed");
+    copy("logprobs", "responseLogprobs");
+    copy("top_logprobs", "logprobs");
+
+    match obj.get("stop") {
+        Some(Value::String(stop)) => {
+            config.insert("stopSequences".into(), json!([stop]));
+        }
+        Some(stops @ Value::Array(_)) => {
+            config.insert("stopSequences".into(), stops.clone());
+        }
+        _ => {}
+    }
+
+    if let Some(format) = obj.get("response_format") {
+        match format.get("type").and_then(Value::as_str) {
+            Some("json_object") => {
+                config.insert("responseMimeType".into(), json!("application/json"));
+            }
+            Some("json_schema") => {
+                config.insert("responseMimeType".into(), json!("application/json"));
+                if let Some(schema) = format.pointer("/json_schema/schema") {
+                    config.insert("responseJsonSchema".into(), schema.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(effort) = obj.get("reasoning_effort").and_then(Value::as_str) {
+        let budget = match effort {
+            "none" | "minimal" => 0,
+            "low" => 1024,
+            "high" => 24_576,
+            _ => 8192,
+        };
+        config.insert("thinkingConfig".into(), json!({ "thinkingBudget": budget }));
+    }
+
+    config
+}
+
+fn function_declaration(tool: &Value) -> Option<Value> {
+    if tool.get("type").and_then(Value::as_str) != Some("function") {
+        return None;
+    }
+    let function = tool.get("function")?;
+    let mut declaration = Map::new();
+    declaration.insert("name".into(), function.get("name")?.clone());
+    if let Some(description) = function.get("description") {
+        declaration.insert("description".into(), description.clone());
+    }
+    if let Some(parameters) = function.get("parameters") {
+        declaration.insert("parametersJsonSchema".into(), parameters.clone());
+    }
+    Some(Value::Object(declaration))
+}
+
+fn tool_config_from(choice: &Value) -> Option<Value> {
+    let config = match choice {
+        Value::String(mode) => match mode.as_str() {
+            "none" => json!({ "mode": "NONE" }),
+            "required" => json!({ "mode": "ANY" }),
+            _ => json!({ "mode": "AUTO" }),
+        },
+        Value::Object(_) => {
+            let name = choice.pointer("/function/name")?;
+            json!({ "mode": "ANY", "allowedFunctionNames": [name] })
+        }
+        _ => return None,
+    };
+    Some(json!({ "functionCallingConfig": config }))
+}
+
+fn google_extensions(obj: &Map<String, Value>) -> Option<&Map<String, Value>> {
+    obj.get("extra_body")
+        .and_then(|extra| extra.get("google"))
+        .or_else(|| obj.get("google"))
+        .and_then(Value::as_object)
+}
+
+fn apply_google_extensions(
+    google: &Map<String, Value>,
+    body: &mut Map<String, Value>,
+    generation_config: &mut Map<String, Value>,
+) {
+    for (key, value) in google {
+        match camel_case(key).as_str() {
+            "safetySettings" => {
+                body.insert("safetySettings".into(), camelize_keys(value));
+            }
+            "cachedContent" => {
+                body.insert("cachedContent".into(), value.clone());
+            }
+            "thinkingConfig" => {
+                generation_config.insert("thinkingConfig".into(), camelize_keys(value));
+            }
+            "generationConfig" => {
+                if let Some(extra) = value.as_object() {
+                    for (k, v) in extra {
+                        generation_config.insert(camel_case(k), v.clone());
+                    }
+                }
+            }
+            "toolConfig" => {
+                body.insert("toolConfig".into(), camelize_keys(value));
+            }
+            "labels" => {
+                body.insert("labels".into(), value.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Converts the keys of an object (or of every object in an array) to
+/// camelCase. Only one level deep, so user-defined names are left alone.
+fn camelize_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (camel_case(k), v.clone()))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(camelize_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+fn camel_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/* ---------- response helpers ---------- */
+
+fn completion_id() -> String {
+    format!("chatcmpl-{}", uuid::Uuid::new_v4().simple())
+}
+
+fn candidates_of(response: &Value) -> Vec<Value> {
+    response
+        .get("candidates")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn candidate_index(candidate: &Value, position: usize) -> u64 {
+    candidate
+        .get("index")
+        .and_then(Value::as_u64)
+        .unwrap_or(position as u64)
+}
+
+/// Splits a candidate's parts into visible text and OpenAI tool calls.
+/// Thought summaries are dropped; OpenAI has no slot for them.
+fn split_candidate_parts(candidate: &Value) -> (String, Vec<Value>) {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+
+    for part in candidate
+        .pointer("/content/parts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if part.get("thought").and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+        if let Some(t) = part.get("text").and_then(Value::as_str) {
+            text.push_str(t);
+        } else if let Some(call) = part.get("functionCall") {
+            let id = call
+                .get("id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+            let arguments = call.get("args").cloned().unwrap_or_else(|| json!({}));
+            tool_calls.push(json!({
+                "id": id,
+                "type": "function",
+                "function": {
+                    "name": call.get("name").cloned().unwrap_or(Value::Null),
+                    "arguments": arguments.to_string(),
+                },
+            }));
+        }
+    }
+
+    (text, tool_calls)
+}
+
+fn finish_reason(candidate: &Value, has_tool_calls: bool) -> &'static str {
+    if has_tool_calls {
+        return "tool_calls";
+    }
+    match candidate.get("finishReason").and_then(Value::as_str) {
+        Some("MAX_TOKENS") => "length",
+        Some(
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY",
+        ) => "content_filter",
+        _ => "stop",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_request_translation_basic() {
+        let request = json!({
+            "model": "models/gemini-2.0-flash",
+            "messages": [
+                {"role": "system", "content": "Be terse."},
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": "Hello"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}}
+                ]}
+            ],
+            "temperature": 0.2,
+            "max_tokens": 128,
+            "stop": "END",
+            "stream": true
+        });
+
+        let translated = chat_request_to_gemini(&request).unwrap();
+
+        assert_eq!(translated.model, "gemini-2.0-flash");
+        assert!(translated.stream);
+        let body = translated.body;
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be terse.");
+        assert_eq!(body["contents"].as_array().unwrap().len(), 3);
+        assert_eq!(body["contents"][1]["role"], "model");
+        assert_eq!(
+            body["contents"][2]["parts"][1]["inlineData"]["mimeType"],
+            "image/png"
+        );
+        assert_eq!(body["generationConfig"]["temperature"], 0.2);
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 128);
+        assert_eq!(body["generationConfig"]["stopSequences"], json!(["END"]));
+    }
+
+    #[test]
+    fn test_chat_request_translation_tools_and_json_mode() {
+        let request = json!({
+            "model": "gemini-2.5-pro",
+            "messages": [
+                {"role": "user", "content": "Weather?"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function",
+                     "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "{\"temp\": 21}"}
+            ],
+            "tools": [{"type": "function", "function": {
+                "name": "get_weather", "parameters": {"type": "object"}
+            }}],
+            "tool_choice": "required",
+            "response_format": {"type": "json_object"}
+        });
+
+        let body = chat_request_to_gemini(&request).unwrap().body;
+
+        assert_eq!(
+            body["contents"][1]["parts"][0]["functionCall"]["args"]["city"],
+            "Paris"
+        );
+        assert_eq!(
+            body["contents"][2]["parts"][0]["functionResponse"]["name"],
+            "get_weather"
+        );
+        assert_eq!(
+            body["contents"][2]["parts"][0]["functionResponse"]["response"]["temp"],
+            21
+        );
+        assert_eq!(
+            body["tools"][0]["functionDeclarations"][0]["name"],
+            "get_weather"
+        );
+        assert_eq!(body["toolConfig"]["functionCallingConfig"]["mode"], "ANY");
+        assert_eq!(
+            body["generationConfig"]["responseMimeType"],
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn test_google_extensions_are_passed_through() {
+        let request = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "extra_body": {"google": {
+                "safety_settings": [{"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_NONE"}],
+                "thinking_config": {"thinking_budget": 512, "include_thoughts": true},
+                "cached_content": "cachedContents/abc"
+            }}
+        });
+
+        let body = chat_request_to_gemini(&request).unwrap().body;
+
+        assert_eq!(body["safetySettings"][0]["threshold"], "BLOCK_NONE");
+        assert_eq!(body["cachedContent"], "cachedContents/abc");
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"]["thinkingBudget"],
+            512
+        );
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"]["includeThoughts"],
+            true
+        );
+    }
+
+    #[test]
+    fn test_chat_request_requires_messages() {
+        let result = chat_request_to_gemini(&json!({"model": "gemini-2.0-flash"}));
+        assert!(matches!(result, Err(AppError::InvalidRequest { .. })));
+    }
+
+    #[test]
+    fn test_gemini_response_translation() {
+        let response = json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "thinking...", "thought": true},
+                    {"text": "Hello"},
+                    {"functionCall": {"name": "lookup", "args": {"q": "x"}}}
+                ]},
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 10,
+                "candidatesTokenCount": 5,
+                "thoughtsTokenCount": 3,
+                "cachedContentTokenCount": 4,
+                "totalTokenCount": 18
+            }
+        });
+
+        let completion = gemini_response_to_chat(&response, "gemini-2.5-flash");
+
+        assert_eq!(completion["object"], "chat.completion");
+        assert_eq!(completion["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(completion["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(
+            completion["choices"][0]["message"]["tool_calls"][0]["function"]["arguments"],
+            "{\"q\":\"x\"}"
+        );
+        assert_eq!(completion["usage"]["prompt_tokens"], 10);
+        assert_eq!(completion["usage"]["completion_tokens"], 8);
+        assert_eq!(
+            completion["usage"]["prompt_tokens_details"]["cached_tokens"],
+            4
+        );
+    }
+
+    #[test]
+    fn test_stream_translator_emits_chunks_usage_and_done() {
+        let mut translator = ChatStreamTranslator::new("gemini-2.0-flash", true);
+
+        let first = translator.on_event(&json!({
+            "candidates": [{"content": {"parts": [{"text": "Hel"}]}, "index": 0}]
+        }));
+        let last = translator.on_event(&json!({
+            "candidates": [{"content": {"parts": [{"text": "lo"}]}, "finishReason": "MAX_TOKENS", "index": 0}],
+            "usageMetadata": {"promptTokenCount": 2, "candidatesTokenCount": 2, "totalTokenCount": 4}
+        }));
+        let end = translator.on_end();
+
+        let first: Value = serde_json::from_slice(&first[0][6..]).unwrap();
+        assert_eq!(first["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(first["choices"][0]["delta"]["content"], "Hel");
+
+        let last: Value = serde_json::from_slice(&last[0][6..]).unwrap();
+        assert!(last["choices"][0]["delta"].get("role").is_none());
+        assert_eq!(last["choices"][0]["finish_reason"], "length");
+
+        assert_eq!(end.len(), 2);
+        let usage: Value = serde_json::from_slice(&end[0][6..]).unwrap();
+        assert_eq!(usage["usage"]["total_tokens"], 4);
+        assert_eq!(&end[1][..], b"data: [DONE]\n\n");
+    }
+}
@@ -0,0 +1,795 @@
+// src/usage.rs
+
+//! Token usage accounting.
+//!
+//! Successful upstream responses report their token usage in `usageMetadata`
+//! (Gemini) or `usage` (OpenAI). The counts are read from buffered bodies and
+//! from streams, where the last SSE chunk carrying usage wins, then aggregated
+//! per key, group, model and client in hourly buckets. With a `pricing` table
+//! configured each response is also priced, using the long-context tier its
+//! prompt falls into. Totals are exported as Prometheus metrics and served by
+//! `GET /admin/usage` and, per day as JSON or CSV, `GET /admin/usage/daily`.
+
+use crate::{
+    catalog::ModelCatalog,
+    config::{AppConfig, ModelPricing},
+    error::{AppError, Result},
+    key_manager::{FlattenedKeyInfo, KeyManager},
+    metrics::MetricsRegistry,
+    priority::CLIENT_ID_HEADER,
+    state::AppState,
+};
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap},
+    response::Response,
+};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use futures_util::StreamExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Width of an aggregation bucket.
+const BUCKET_SECS: i64 = 3600;
+/// Number of hourly buckets kept (31 days).
+const RETENTION_BUCKETS: usize = 31 * 24;
+/// Label used when a request has no model or client id.
+const UNKNOWN: &str = "unknown";
+/// Metric label for clients and models that are not configured.
+const OTHER: &str = "other";
+/// Largest non-streamed body kept in memory to read its usage from.
+const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+/// Token counts and cost of one or more responses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UsageCounts {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub candidates_tokens: u64,
+    pub cached_tokens: u64,
+    pub thoughts_tokens: u64,
+    pub total_tokens: u64,
+    /// Cost in the currency of the pricing table; 0 for unpriced models.
+    #[serde(default)]
+    pub cost: f64,
+}
+
+impl UsageCounts {
+    /// Reads Gemini `usageMetadata` or OpenAI `usage` from a response object.
+    pub fn from_response(response: &Value) -> Option<Self> {
+        let count = |object: &Value, path: &[&str]| {
+            path.iter()
+                .try_fold(object, |value, field| value.get(field))
+                .and_then(Value::as_u64)
+                .unwrap_or(0)
+        };
+        if let Some(meta) = response.get("usageMetadata") {
+            return Some(Self {
+                requests: 1,
+                prompt_tokens: count(meta, &["promptTokenCount"]),
+                candidates_tokens: count(meta, &["candidatesTokenCount"]),
+                cached_tokens: count(meta, &["cachedContentTokenCount"]),
+                thoughts_tokens: count(meta, &["thoughtsTokenCount"]),
+                total_tokens: count(meta, &["totalTokenCount"]),
+                cost: 0.0,
+            });
+        }
+        let usage = response.get("usage").filter(|usage| usage.is_object())?;
+        // OpenAI includes reasoning in `completion_tokens`; Gemini does not.
+        let thoughts_tokens = count(usage, &["completion_tokens_details", "reasoning_tokens"]);
+        Some(Self {
+            requests: 1,
+            prompt_tokens: count(usage, &["prompt_tokens"]),
+            candidates_tokens: count(usage, &["completion_tokens"]).saturating_sub(thoughts_tokens),
+            cached_tokens: count(usage, &["prompt_tokens_details", "cached_tokens"]),
+            thoughts_tokens,
+            total_tokens: count(usage, &["total_tokens"]),
+            cost: 0.0,
+        })
+    }
+
+    /// Reads the usage of a buffered JSON body. Streamed JSON arrays report
+    /// their final usage in the last element that has one.
+    pub fn from_body(body: &[u8]) -> Option<Self> {
+        match serde_json::from_slice::<Value>(body).ok()? {
+            Value::Array(chunks) => chunks.iter().rev().find_map(Self::from_response),
+            response => Self::from_response(&response),
+        }
+    }
+
+    pub fn add(&mut self, other: &UsageCounts) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.candidates_tokens += other.candidates_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.thoughts_tokens += other.thoughts_tokens;
+        self.total_tokens += other.total_tokens;
+        self.cost += other.cost;
+    }
+
+    /// Sets the cost of a single response from the rates of the tier its
+    /// prompt size falls into. Cached prompt tokens are charged at the cached
+    /// rate instead of the input rate.
+    pub fn price(&mut self, pricing: &ModelPricing) {
+        let rates = pricing.rates_for(self.prompt_tokens);
+        let cached = self.cached_tokens.min(self.prompt_tokens);
+        let charges = [
+            (self.prompt_tokens - cached, rates.input_per_million),
+            (
+                cached,
+                rates.cached_per_million.unwrap_or(rates.input_per_million),
+            ),
+            (self.candidates_tokens, rates.output_per_million),
+            (
+                self.thoughts_tokens,
+                rates
+                    .thinking_per_million
+                    .unwrap_or(rates.output_per_million),
+            ),
+        ];
+        self.cost = charges
+            .iter()
+            .map(|(tokens, per_million)| *tokens as f64 * per_million / 1_000_000.0)
+            .sum();
+    }
+}
+
+/// Dimensions usage is aggregated by.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct UsageLabels {
+    pub key: String,
+    pub group: String,
+    pub model: String,
+    pub client: String,
+}
+
+impl UsageLabels {
+    /// Labels for a request sent with `key_info`; the client is identified by
+    /// the `X-Client-Id` header.
+    pub fn new(key_info: &FlattenedKeyInfo, model: &str, headers: &HeaderMap) -> Self {
+        let client = headers
+            .get(CLIENT_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .unwrap_or(UNKNOWN);
+        Self {
+            key: KeyManager::preview_key(&key_info.key),
+            group: key_info.group_name.clone(),
+            model: if model.is_empty() { UNKNOWN } else { model }.to_string(),
+            client: client.to_string(),
+        }
+    }
+
+    /// Labels for the Prometheus series. Clients and models come from the
+    /// request, so only those named in the configuration or listed by the
+    /// model catalog keep their own series; everything else is `other`.
+    fn bounded(&self, config: &AppConfig, catalog: &ModelCatalog) -> Self {
+        let known_client = self.client == UNKNOWN
+            || config
+                .priority
+                .as_ref()
+                .is_some_and(|priority| priority.clients.contains_key(&self.client))
+            || config
+                .group_selection
+                .as_ref()
+                .is_some_and(|selection| selection.clients.contains_key(&self.client));
+        let known_model = self.model == UNKNOWN
+            || config.get_group_for_model(&self.model).is_some()
+            || config
+                .pricing
+                .as_ref()
+                .is_some_and(|pricing| pricing.for_model(&self.model).is_some())
+            || catalog.contains(&self.model);
+        Self {
+            key: self.key.clone(),
+            group: self.group.clone(),
+            model: if known_model { &self.model } else { OTHER }.to_string(),
+            client: if known_client { &self.client } else { OTHER }.to_string(),
+        }
+    }
+}
+
+/// Query parameters of `GET /admin/usage`.
+#[derive(Debug, Default, Deserialize)]
+pub struct UsageQuery {
+    /// Start of the range (inclusive, rounded down to the hour).
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range (exclusive).
+    pub to: Option<DateTime<Utc>>,
+    /// Comma-separated dimensions to group by: `key`, `group`, `model`,
+    /// `client`. Defaults to all of them.
+    pub group_by: Option<String>,
+    pub key: Option<String>,
+    pub group: Option<String>,
+    pub model: Option<String>,
+    pub client: Option<String>,
+}
+
+/// Usage of one combination of the grouped dimensions.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(flatten)]
+    pub usage: UsageCounts,
+}
+
+/// Usage of one day and combination of the grouped dimensions.
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyUsageRow {
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub row: UsageRow,
+}
+
+/// Response of `GET /admin/usage/daily`.
+#[derive(Debug, Serialize)]
+pub struct DailyCostReport {
+    pub currency: String,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub days: Vec<DailyUsageRow>,
+    pub totals: UsageCounts,
+}
+
+impl DailyCostReport {
+    /// Renders the rows as CSV with a header line. Only the grouped
+    /// dimensions get a column.
+    pub fn to_csv(&self, group_by: &[&str]) -> String {
+        let mut csv = String::from("date");
+        for dimension in group_by {
+            csv.push(',');
+            csv.push_str(dimension);
+        }
+        csv.push_str(
+            ",requests,prompt_tokens,candidates_tokens,cached_tokens,thoughts_tokens,total_tokens,cost\n",
+        );
+        for DailyUsageRow { date, row } in &self.days {
+            csv.push_str(&date.to_string());
+            for dimension in group_by {
+                let value = match *dimension {
+                    "key" => &row.key,
+                    "group" => &row.group,
+                    "model" => &row.model,
+                    _ => &row.client,
+                };
+                csv.push(',');
+                csv.push_str(&csv_field(value.as_deref().unwrap_or_default()));
+            }
+            let usage = &row.usage;
+            csv.push_str(&format!(
+                ",{},{},{},{},{},{},{:.6}\n",
+                usage.requests,
+                usage.prompt_tokens,
+                usage.candidates_tokens,
+                usage.cached_tokens,
+                usage.thoughts_tokens,
+                usage.total_tokens,
+                usage.cost
+            ));
+        }
+        csv
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns the dimensions named in `group_by`, or all of them.
+pub fn group_by_dimensions(group_by: Option<&str>) -> Result<Vec<&'static str>> {
+    let Some(list) = group_by else {
+        return Ok(vec!["key", "group", "model", "client"]);
+    };
+    list.split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| match d {
+            "key" => Ok("key"),
+            "group" => Ok("group"),
+            "model" => Ok("model"),
+            "client" => Ok("client"),
+            other => Err(AppError::InvalidRequest {
+                message: format!(
+                    "Unknown group_by dimension '{other}'; use key, group, model or client"
+                ),
+            }),
+        })
+        .collect()
+}
+
+/// Response of `GET /admin/usage`.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub rows: Vec<UsageRow>,
+    pub totals: UsageCounts,
+}
+
+/// A report row and, for daily reports, its date.
+type DatedRow = (Option<NaiveDate>, UsageRow);
+
+/// In-memory usage totals in hourly buckets.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    buckets: Mutex<BTreeMap<i64, HashMap<UsageLabels, UsageCounts>>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, labels: &UsageLabels, counts: &UsageCounts, at: DateTime<Utc>) {
+        let bucket = at.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS;
+        let mut buckets = self.buckets.lock();
+        buckets
+            .entry(bucket)
+            .or_default()
+            .entry(labels.clone())
+            .or_default()
+            .add(counts);
+        while buckets.len() > RETENTION_BUCKETS {
+            buckets.pop_first();
+        }
+    }
+
+    /// Returns the usage per `(bucket start, labels)` in the query's range that
+    /// matches its filters.
+    pub fn entries(&self, query: &UsageQuery) -> Vec<(DateTime<Utc>, UsageLabels, UsageCounts)> {
+        let from = query.from.map_or(i64::MIN, |t| {
+            t.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS
+        });
+        let to = query.to.map_or(i64::MAX, |t| t.timestamp());
+        let matches =
+            |filter: &Option<String>, value: &str| filter.as_deref().map_or(true, |f| f == value);
+
+        let buckets = self.buckets.lock();
+        buckets
+            .range(from..to.max(from))
+            .flat_map(|(start, usage)| {
+                usage
+                    .iter()
+                    .map(move |(labels, counts)| (*start, labels, counts))
+            })
+            .filter(|(_, labels, _)| {
+                matches(&query.key, &labels.key)
+                    && matches(&query.group, &labels.group)
+                    && matches(&query.model, &labels.model)
+                    && matches(&query.client, &labels.client)
+            })
+            .map(|(start, labels, counts)| {
+                let start = Utc.timestamp_opt(start, 0).single().unwrap_or_default();
+                (start, labels.clone(), *counts)
+            })
+            .collect()
+    }
+
+    /// Sums usage over the query's range, grouped by its dimensions and, if
+    /// `daily`, by UTC date.
+    fn aggregate(&self, query: &UsageQuery, daily: bool) -> Result<(Vec<DatedRow>, UsageCounts)> {
+        let dimensions = group_by_dimensions(query.group_by.as_deref())?;
+        let pick = |name: &str, value: &str| dimensions.contains(&name).then(|| value.to_string());
+
+        type RowKey = (Option<NaiveDate>, [Option<String>; 4]);
+        let mut grouped: BTreeMap<RowKey, UsageCounts> = BTreeMap::new();
+        let mut totals = UsageCounts::default();
+        for (start, labels, counts) in self.entries(query) {
+            let row_key = [
+                pick("key", &labels.key),
+                pick("group", &labels.group),
+                pick("model", &labels.model),
+                pick("client", &labels.client),
+            ];
+            let date = daily.then(|| start.date_naive());
+            grouped.entry((date, row_key)).or_default().add(&counts);
+            totals.add(&counts);
+        }
+
+        let rows = grouped
+            .into_iter()
+            .map(|((date, [key, group, model, client]), usage)| {
+                let row = UsageRow {
+                    key,
+                    group,
+                    model,
+                    client,
+                    usage,
+                };
+                (date, row)
+            })
+            .collect();
+        Ok((rows, totals))
+    }
+
+    /// Sums usage over the query's range, grouped by its dimensions.
+    pub fn report(&self, query: &UsageQuery) -> Result<UsageReport> {
+        let (rows, totals) = self.aggregate(query, false)?;
+        Ok(UsageReport {
+            from: query.from,
+            to: query.to,
+            rows: rows.into_iter().map(|(_, row)| row).collect(),
+            totals,
+        })
+    }
+
+    /// Sums usage and cost over the query's range per UTC day, grouped by its
+    /// dimensions.
+    pub fn daily_report(&self, query: &UsageQuery, currency: &str) -> Result<DailyCostReport> {
+        let (rows, totals) = self.aggregate(query, true)?;
+        let days = rows
+            .into_iter()
+            .filter_map(|(date, row)| Some(DailyUsageRow { date: date?, row }))
+            .collect();
+        Ok(DailyCostReport {
+            currency: currency.to_string(),
+            from: query.from,
+            to: query.to,
+            days,
+            totals,
+        })
+    }
+}
+
+/// How a response body is scanned for usage.
+enum Scan {
+    /// Server-sent events; holds the incomplete last line.
+    Events(Vec<u8>),
+    /// A JSON body, kept whole until it ends.
+    Buffered(Vec<Bytes>),
+    /// A JSON body that grew past `MAX_BUFFERED_BYTES`; no longer scanned.
+    Skip,
+}
+
+/// Records the usage of a response body once it has been sent.
+struct UsageRecorder {
+    tracker: Arc<UsageTracker>,
+    metrics: Arc<MetricsRegistry>,
+    labels: UsageLabels,
+    metric_labels: UsageLabels,
+    pricing: Option<ModelPricing>,
+    scan: Scan,
+    last: Option<UsageCounts>,
+}
+
+impl UsageRecorder {
+    fn feed(&mut self, chunk: &Bytes) {
+        let line_buffer = match &mut self.scan {
+            Scan::Buffered(chunks) => {
+                let buffered: usize = chunks.iter().map(Bytes::len).sum();
+                if buffered + chunk.len() > MAX_BUFFERED_BYTES {
+                    self.scan = Scan::Skip;
+                } else {
+                    chunks.push(chunk.clone());
+                }
+                return;
+            }
+            Scan::Skip => return,
+            Scan::Events(line_buffer) => line_buffer,
+        };
+        line_buffer.extend_from_slice(chunk);
+        let Some(end) = line_buffer.iter().rposition(|b| *b == b'\n') else {
+            return;
+        };
+        let lines: Vec<u8> = line_buffer.drain(..=end).collect();
+        for line in lines.split(|b| *b == b'\n') {
+            let Some(data) = line.strip_prefix(b"data:") else {
+                continue;
+            };
+            if !data
+                .windows(7)
+                .any(|w| w == b"\"usage\"" || w == b"\"usageM")
+            {
+                continue;
+            }
+            if let Some(usage) = UsageCounts::from_body(data) {
+                self.last = Some(usage);
+            }
+        }
+    }
+}
+
+impl Drop for UsageRecorder {
+    fn drop(&mut self) {
+        if let Scan::Buffered(chunks) = &self.scan {
+            self.last = UsageCounts::from_body(&chunks.concat());
+        }
+        if let Some(usage) = &mut self.last {
+            if let Some(pricing) = &self.pricing {
+                usage.price(pricing);
+            }
+            self.tracker.record(&self.labels, usage, Utc::now());
+            self.metrics.record_usage(&self.metric_labels, usage);
+        }
+    }
+}
+
+/// Records the usage of a response to a request for `model` sent with
+/// `key_info`, priced with the configured rates of the model.
+pub async fn track_response(
+    state: &Arc<AppState>,
+    key_info: &FlattenedKeyInfo,
+    model: &str,
+    request_headers: &HeaderMap,
+    response: Response,
+) -> Response {
+    let config = state.config.read().await;
+    let pricing = config
+        .pricing
+        .as_ref()
+        .and_then(|pricing| pricing.for_model(model))
+        .cloned();
+    let labels = UsageLabels::new(key_info, model, request_headers);
+    let metric_labels = labels.bounded(&config, &state.model_catalog);
+    drop(config);
+    track(
+        &state.usage,
+        &state.metrics,
+        labels,
+        metric_labels,
+        pricing,
+        response,
+    )
+}
+
+/// Records the usage reported by a successful response once its body has been
+/// sent to the client. Event streams are scanned line by line; JSON bodies up
+/// to `MAX_BUFFERED_BYTES` are read whole and any other body is not scanned.
+/// `metric_labels` are the bounded labels of the Prometheus series.
+pub fn track(
+    tracker: &Arc<UsageTracker>,
+    metrics: &Arc<MetricsRegistry>,
+    labels: UsageLabels,
+    metric_labels: UsageLabels,
+    pricing: Option<ModelPricing>,
+    response: Response,
+) -> Response {
+    if !response.status().is_success() {
+        return response;
+    }
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let scan = if content_type.contains("text/event-stream") {
+        Scan::Events(Vec::new())
+    } else if content_type.contains("application/json") {
+        Scan::Buffered(Vec::new())
+    } else {
+        return response;
+    };
+    let mut recorder = UsageRecorder {
+        tracker: tracker.clone(),
+        metrics: metrics.clone(),
+        labels,
+        metric_labels,
+        pricing,
+        scan,
+        last: None,
+    };
+
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            recorder.feed(bytes);
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn labels(key: &str, model: &str) -> UsageLabels {
+        UsageLabels {
+            key: key.to_string(),
+            group: "default".to_string(),
+            model: model.to_string(),
+            client: UNKNOWN.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_usage_is_read_from_both_formats() {
+        let gemini = json!({"usageMetadata": {
+            "promptTokenCount": 10, "candidatesTokenCount": 5,
+            "cachedContentTokenCount": 4, "thoughtsTokenCount": 2, "totalTokenCount": 17
+        }});
+        let usage = UsageCounts::from_response(&gemini).unwrap();
+        assert_eq!(
+            (
+                usage.prompt_tokens,
+                usage.cached_tokens,
+                usage.thoughts_tokens
+            ),
+            (10, 4, 2)
+        );
+
+        let openai = json!({"usage": {
+            "prompt_tokens": 7, "completion_tokens": 3, "total_tokens": 10,
+            "completion_tokens_details": {"reasoning_tokens": 1}
+        }});
+        let usage = UsageCounts::from_response(&openai).unwrap();
+        assert_eq!((usage.candidates_tokens, usage.thoughts_tokens), (2, 1));
+        assert!(UsageCounts::from_response(&json!({"usage": null})).is_none());
+    }
+
+    #[test]
+    fn test_report_filters_by_time_and_groups_dimensions() {
+        let tracker = UsageTracker::new();
+        let usage = UsageCounts {
+            requests: 1,
+            total_tokens: 10,
+            ..Default::default()
+        };
+        let at = |hour: u32| Utc.with_ymd_and_hms(2026, 1, 1, hour, 30, 0).unwrap();
+        tracker.record(&labels("k1", "flash"), &usage, at(1));
+        tracker.record(&labels("k2", "flash"), &usage, at(2));
+        tracker.record(&labels("k2", "pro"), &usage, at(3));
+
+        let report = tracker
+            .report(&UsageQuery {
+                from: Some(at(2)),
+                group_by: Some("model".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(report.totals.total_tokens, 20);
+        assert_eq!(report.rows.len(), 2);
+        assert!(report.rows.iter().all(|row| row.key.is_none()));
+
+        let query = UsageQuery {
+            group_by: Some("region".to_string()),
+            ..Default::default()
+        };
+        assert!(tracker.report(&query).is_err());
+    }
+
+    #[test]
+    fn test_cost_uses_long_context_tier_and_cached_rate() {
+        let pricing: ModelPricing = serde_json::from_value(json!({
+            "input_per_million": 1.0,
+            "output_per_million": 10.0,
+            "cached_per_million": 0.25,
+            "long_context": [{
+                "above_prompt_tokens": 200000,
+                "input_per_million": 2.0,
+                "output_per_million": 20.0
+            }]
+        }))
+        .unwrap();
+        let mut usage = UsageCounts {
+            requests: 1,
+            prompt_tokens: 1_000_000,
+            cached_tokens: 400_000,
+            candidates_tokens: 100_000,
+            thoughts_tokens: 50_000,
+            ..Default::default()
+        };
+        usage.price(&pricing);
+        // The long-context tier has no cached or thinking rates of its own.
+        assert!((usage.cost - (1.2 + 0.8 + 2.0 + 1.0)).abs() < 1e-9);
+
+        usage.prompt_tokens = 1_000;
+        usage.cached_tokens = 0;
+        usage.price(&pricing);
+        assert!((usage.cost - (0.001 + 1.0 + 0.5)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_metric_labels_bucket_unconfigured_clients_and_models() {
+        let config = AppConfig {
+            groups: vec![crate::config::KeyGroup {
+                name: "default".to_string(),
+                model_aliases: vec!["flash".to_string()],
+                ..Default::default()
+            }],
+            priority: Some(crate::config::PriorityConfig {
+                clients: HashMap::from([(
+                    "nightly".to_string(),
+                    crate::config::PriorityClass::Batch,
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let catalog = ModelCatalog::new();
+        let mut request = labels("k1", "flash");
+        request.client = "nightly".to_string();
+        assert_eq!(request.bounded(&config, &catalog), request);
+
+        request.model = "made-up-model".to_string();
+        request.client = "random-client".to_string();
+        let bounded = request.bounded(&config, &catalog);
+        assert_eq!(
+            (bounded.model.as_str(), bounded.client.as_str()),
+            (OTHER, OTHER)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_small_json_bodies_are_buffered() {
+        let tracker = Arc::new(UsageTracker::new());
+        let metrics = Arc::new(MetricsRegistry::new());
+        let usage = json!({"usageMetadata": {"totalTokenCount": 5}}).to_string();
+        let respond = |content_type: &str, body: String| {
+            let response = Response::builder()
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap();
+            track(
+                &tracker,
+                &metrics,
+                labels("k1", "flash"),
+                labels("k1", "flash"),
+                None,
+                response,
+            )
+        };
+
+        for response in [
+            respond("text/plain", usage.clone()),
+            respond(
+                "application/json",
+                format!("{usage}{}", " ".repeat(MAX_BUFFERED_BYTES)),
+            ),
+            respond("application/json", usage),
+        ] {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+        }
+
+        let report = tracker.report(&UsageQuery::default()).unwrap();
+        assert_eq!(report.totals.requests, 1);
+    }
+
+    #[test]
+    fn test_daily_report_renders_csv() {
+        let tracker = UsageTracker::new();
+        let usage = UsageCounts {
+            requests: 1,
+            total_tokens: 10,
+            cost: 0.5,
+            ..Default::default()
+        };
+        let at = |day: u32| Utc.with_ymd_and_hms(2026, 1, day, 12, 0, 0).unwrap();
+        tracker.record(&labels("k1", "flash"), &usage, at(1));
+        tracker.record(&labels("k1", "pro,001"), &usage, at(2));
+        tracker.record(&labels("k2", "pro,001"), &usage, at(2));
+
+        let query = UsageQuery {
+            group_by: Some("model".to_string()),
+            ..Default::default()
+        };
+        let report = tracker.daily_report(&query, "USD").unwrap();
+        assert_eq!(report.days.len(), 2);
+        assert_eq!(report.totals.cost, 1.5);
+        assert_eq!(
+            report.to_csv(&["model"]),
+            "date,model,requests,prompt_tokens,candidates_tokens,cached_tokens,thoughts_tokens,total_tokens,cost\n\
+             2026-01-01,flash,1,0,0,0,0,10,0.500000\n\
+             2026-01-02,\"pro,001\",2,0,0,0,0,20,1.000000\n"
+        );
+    }
+}
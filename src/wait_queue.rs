@@ -0,0 +1,255 @@
+// src/wait_queue.rs
+
+//! Bounded per-group wait queue for requests that find every key of their
+//! group temporarily blocked.
+//!
+//! Instead of failing with `NoHealthyKeys`, a request takes a slot and sleeps
+//! until the earliest `blocked_until` of its group passes, as long as that is
+//! within its deadline. Requests that would exceed the deadline or the queue
+//! limit get a 503 with `Retry-After`.
+
+use crate::config::{KeyWaitQueueConfig, PriorityClass};
+use crate::error::{AppError, Result};
+use crate::metrics::MetricsRegistry;
+use crate::state::AppState;
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use secrecy::ExposeSecret;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Request header with which clients lower their maximum wait, in milliseconds.
+pub const MAX_WAIT_HEADER: &str = "x-proxy-max-wait-ms";
+
+const DEFAULT_GROUP: &str = "default";
+
+/// Per-group count of waiting requests.
+#[derive(Debug, Default)]
+pub struct KeyWaitQueue {
+    waiting: Mutex<HashMap<String, usize>>,
+}
+
+/// A place in the queue, released on drop.
+pub struct WaitSlot {
+    queue: Arc<KeyWaitQueue>,
+    metrics: Arc<MetricsRegistry>,
+    group: String,
+    deadline: Instant,
+    started: Instant,
+}
+
+impl KeyWaitQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of requests currently waiting for `group`.
+    pub fn depth(&self, group: &str) -> usize {
+        self.waiting.lock().get(group).copied().unwrap_or(0)
+    }
+
+    fn try_enter(
+        self: &Arc<Self>,
+        metrics: &Arc<MetricsRegistry>,
+        group: &str,
+        limit: usize,
+        deadline: Instant,
+    ) -> Option<WaitSlot> {
+        let mut waiting = self.waiting.lock();
+        let depth = waiting.entry(group.to_string()).or_insert(0);
+        if *depth >= limit {
+            return None;
+        }
+        *depth += 1;
+        metrics.set_key_wait_queue_depth(group.to_string(), *depth);
+        Some(WaitSlot {
+            queue: self.clone(),
+            metrics: metrics.clone(),
+            group: group.to_string(),
+            deadline,
+            started: Instant::now(),
+        })
+    }
+}
+
+impl WaitSlot {
+    /// Records the total wait once a key has been obtained.
+    pub fn finish(self) {
+        self.metrics
+            .record_key_wait(self.group.clone(), self.started.elapsed(), "acquired");
+    }
+}
+
+impl Drop for WaitSlot {
+    fn drop(&mut self) {
+        let mut waiting = self.queue.waiting.lock();
+        if let Some(depth) = waiting.get_mut(&self.group) {
+            *depth = depth.saturating_sub(1);
+            self.metrics
+                .set_key_wait_queue_depth(self.group.clone(), *depth);
+        }
+    }
+}
+
+/// Computes how long this request may wait: the configured maximum, lowered
+/// by the client's `X-Proxy-Max-Wait-Ms` header.
+pub fn max_wait(config: &KeyWaitQueueConfig, headers: &HeaderMap) -> Duration {
+    let configured = Duration::from_secs(config.max_wait_secs);
+    headers
+        .get(MAX_WAIT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map_or(configured, |ms| Duration::from_millis(ms).min(configured))
+}
+
+/// Returns the earliest time a temporarily blocked key of `group` becomes
+/// available again. Keys blocked until reset are ignored.
+pub async fn earliest_unblock(
+    state: &Arc<AppState>,
+    group: Option<&str>,
+) -> Result<Option<DateTime<Utc>>> {
+    let key_manager = state.key_manager.read().await;
+    let key_info = key_manager.get_all_key_info().await;
+    let key_states = key_manager.get_key_states().await?;
+    Ok(key_info
+        .values()
+        .filter(|info| group.map_or(true, |g| info.group_name == g))
+        .filter_map(|info| key_states.get(info.key.expose_secret()))
+        .filter_map(|state| state.temporarily_blocked_until())
+        .min())
+}
+
+fn retry_after_secs(until: DateTime<Utc>) -> u64 {
+    let millis = (until - Utc::now()).num_milliseconds().max(0) as u64;
+    ((millis + 999) / 1000).max(1)
+}
+
+/// Waits for a key of `group` to come back.
+///
+/// Returns `Ok(false)` if waiting cannot help (queue disabled, or no key is
+/// only temporarily blocked), so the caller should fail as before. `slot` holds
+/// this request's place across retries. Batch requests may only fill
+/// `priority.batch_queue_share` of the queue.
+///
+/// # Errors
+///
+/// Returns `AppError::KeysExhausted` if the key would come back after the
+/// request's deadline or the group's queue is full.
+pub async fn wait_for_key(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    group: Option<&str>,
+    class: PriorityClass,
+    slot: &mut Option<WaitSlot>,
+) -> Result<bool> {
+    let (config, batch_share) = {
+        let config_guard = state.config.read().await;
+        let Some(config) = config_guard.key_wait_queue.clone() else {
+            return Ok(false);
+        };
+        let batch_share = config_guard
+            .priority
+            .as_ref()
+            .map_or(1.0, |p| p.batch_queue_share);
+        (config, batch_share)
+    };
+    let Some(until) = earliest_unblock(state, group).await? else {
+        return Ok(false);
+    };
+    let group_label = group.unwrap_or(DEFAULT_GROUP);
+
+    if slot.is_none() {
+        let deadline = Instant::now() + max_wait(&config, headers);
+        // Batch requests only get a share of the queue, so they are shed first.
+        let limit = match class {
+            PriorityClass::Interactive => config.max_waiters_per_group,
+            PriorityClass::Batch => (config.max_waiters_per_group as f64 * batch_share) as usize,
+        };
+        match state
+            .key_wait_queue
+            .try_enter(&state.metrics, group_label, limit, deadline)
+        {
+            Some(entered) => *slot = Some(entered),
+            None => {
+                warn!(group = group_label, "Key wait queue is full");
+                state.metrics.record_key_wait(
+                    group_label.to_string(),
+                    Duration::ZERO,
+                    "queue_full",
+                );
+                return Err(AppError::KeysExhausted {
+                    retry_after_secs: retry_after_secs(until),
+                });
+            }
+        }
+    }
+    let Some(current) = slot.as_ref() else {
+        return Ok(false);
+    };
+
+    let wait = (until - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+    let wake_at = Instant::now() + wait;
+    if wake_at > current.deadline {
+        warn!(
+            group = group_label,
+            wait_ms = wait.as_millis() as u64,
+            "No key becomes available before the request deadline"
+        );
+        state.metrics.record_key_wait(
+            group_label.to_string(),
+            current.started.elapsed(),
+            "timeout",
+        );
+        return Err(AppError::KeysExhausted {
+            retry_after_secs: retry_after_secs(until),
+        });
+    }
+
+    info!(
+        group = group_label,
+        wait_ms = wait.as_millis() as u64,
+        depth = state.key_wait_queue.depth(group_label),
+        "All keys temporarily blocked; waiting for the earliest to recover"
+    );
+    tokio::time::sleep_until(wake_at.into()).await;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_max_wait_is_capped_by_config() {
+        let config = KeyWaitQueueConfig {
+            max_waiters_per_group: 1,
+            max_wait_secs: 10,
+        };
+        let mut headers = HeaderMap::new();
+        assert_eq!(max_wait(&config, &headers), Duration::from_secs(10));
+        headers.insert(MAX_WAIT_HEADER, HeaderValue::from_static("2500"));
+        assert_eq!(max_wait(&config, &headers), Duration::from_millis(2500));
+        headers.insert(MAX_WAIT_HEADER, HeaderValue::from_static("60000"));
+        assert_eq!(max_wait(&config, &headers), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_queue_limit_and_release() {
+        let queue = Arc::new(KeyWaitQueue::new());
+        let metrics = Arc::new(MetricsRegistry::new());
+        let deadline = Instant::now() + Duration::from_secs(1);
+
+        let first = queue.try_enter(&metrics, "g", 1, deadline);
+        assert!(first.is_some());
+        assert!(queue.try_enter(&metrics, "g", 1, deadline).is_none());
+        assert!(queue.try_enter(&metrics, "other", 1, deadline).is_some());
+
+        drop(first);
+        assert_eq!(queue.depth("g"), 0);
+        assert!(queue.try_enter(&metrics, "g", 1, deadline).is_some());
+    }
+}
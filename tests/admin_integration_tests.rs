@@ -362,3 +362,68 @@ async fn test_update_config() {
     let body_str = String::from_utf8(body.to_vec()).unwrap();
     assert!(body_str.contains("new_key_in_updated_config"));
 }
+
+#[tokio::test]
+async fn test_list_and_force_circuit_breakers() {
+    let mut app = TestApp::new().await;
+    let anonymous = Request::builder()
+        .uri("/admin/circuit-breakers")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.router.clone().oneshot(anonymous).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    app.login().await;
+    app.get_csrf_token().await;
+
+    let response = app
+        .authed_request(Method::GET, "/circuit-breakers", Body::empty())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let breakers: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(breakers.as_array().unwrap().len(), 1);
+    assert_eq!(breakers[0]["state"], "closed");
+    let key_id = breakers[0]["key_id"].as_str().unwrap().to_string();
+
+    let force = serde_json::json!({"key_id": key_id, "state": "open"});
+    let response = app
+        .authed_request(
+            Method::POST,
+            "/circuit-breakers/force",
+            Body::from(force.to_string()),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let changed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(changed[0]["state"], "open");
+    assert_eq!(changed[0]["forced"], true);
+    assert!(changed[0]["next_attempt_at"].is_null());
+
+    let target_url = changed[0]["target_url"].as_str().unwrap().to_string();
+    let release = serde_json::json!({"target_url": target_url, "state": "auto"});
+    let response = app
+        .authed_request(
+            Method::POST,
+            "/circuit-breakers/force",
+            Body::from(release.to_string()),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let changed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(changed[0]["state"], "open");
+    assert_eq!(changed[0]["forced"], false);
+    assert!(changed[0]["next_attempt_at"].is_string());
+
+    let unknown = serde_json::json!({"key_id": "unknown", "state": "closed"});
+    let response = app
+        .authed_request(
+            Method::POST,
+            "/circuit-breakers/force",
+            Body::from(unknown.to_string()),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,178 @@
+// tests/anthropic_messages_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{body_partial_json, header_exists, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn create_app(server: &MockServer, temp_dir: &TempDir) -> Router {
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["messages-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    create_router(Arc::new(state))
+}
+
+fn messages_request(body: Value) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri("/v1/messages")
+        .header("content-type", "application/json")
+        .header("x-api-key", "anthropic-client-key")
+        .header("anthropic-version", "2023-06-01")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_messages_are_translated_to_generate_content() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.5-flash:generateContent"))
+        .and(query_param("key", "messages-key"))
+        .and(body_partial_json(json!({
+            "systemInstruction": {"parts": [{"text": "Be brief."}]},
+            "contents": [{"role": "user", "parts": [{"text": "Hi"}]}],
+            "generationConfig": {"maxOutputTokens": 64, "stopSequences": ["STOP"]}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Hello!"}]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 4, "candidatesTokenCount": 2}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(messages_request(json!({
+            "model": "gemini-2.5-flash",
+            "max_tokens": 64,
+            "system": "Be brief.",
+            "stop_sequences": ["STOP"],
+            "messages": [{"role": "user", "content": "Hi"}]
+        })))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let message: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(message["type"], "message");
+    assert_eq!(message["content"][0]["text"], "Hello!");
+    assert_eq!(message["stop_reason"], "end_turn");
+    assert_eq!(message["usage"]["input_tokens"], 4);
+
+    let received = server.received_requests().await.unwrap();
+    assert!(received[0].headers.get("x-api-key").is_none());
+}
+
+#[tokio::test]
+async fn test_streaming_messages_emit_anthropic_events() {
+    let server = MockServer::start().await;
+    let sse = concat!(
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hi\"}]}}]}\n\n",
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\" there\"}]},\"finishReason\":\"STOP\"}],",
+        "\"usageMetadata\":{\"promptTokenCount\":1,\"candidatesTokenCount\":2}}\n\n",
+    );
+    Mock::given(method("POST"))
+        .and(path(
+            "/v1beta/models/gemini-2.5-flash:streamGenerateContent",
+        ))
+        .and(header_exists("content-length"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(sse, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(messages_request(json!({
+            "model": "gemini-2.5-flash",
+            "max_tokens": 64,
+            "stream": true,
+            "messages": [{"role": "user", "content": "Hi"}]
+        })))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    let events: Vec<&str> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_upstream_errors_use_anthropic_error_shape() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.5-flash:generateContent"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": {"code": 400, "message": "bad schema", "status": "INVALID_ARGUMENT"}
+        })))
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(messages_request(json!({
+            "model": "gemini-2.5-flash",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "Hi"}]
+        })))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["type"], "error");
+    assert_eq!(error["error"]["type"], "invalid_request_error");
+    assert_eq!(error["error"]["message"], "bad schema");
+}
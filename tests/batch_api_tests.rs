@@ -0,0 +1,238 @@
+// tests/batch_api_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn mount_for_key(
+    server: &MockServer,
+    http_method: &str,
+    endpoint: &str,
+    key: &str,
+    body: Value,
+) {
+    Mock::given(method(http_method))
+        .and(path(endpoint))
+        .and(query_param("key", key))
+        .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        .mount(server)
+        .await;
+}
+
+async fn send(app: &Router, http_method: Method, uri: &str) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http_method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"batch":{"input_config":{}}}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_batch_calls_are_routed_to_the_creating_key() {
+    let server = MockServer::start().await;
+    let create = "/v1beta/models/gemini-2.0-flash:batchGenerateContent";
+    mount_for_key(
+        &server,
+        "POST",
+        create,
+        "key-1",
+        json!({"name": "batches/one"}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "POST",
+        create,
+        "key-2",
+        json!({"name": "batches/two"}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "GET",
+        "/v1beta/batches/one",
+        "key-1",
+        json!({"name": "batches/one", "done": true, "response": {"responsesFile": "files/res-one"}}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "GET",
+        "/download/v1beta/files/res-one:download",
+        "key-1",
+        json!({"key": "r1", "response": {}}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "POST",
+        "/v1beta/batches/two:cancel",
+        "key-2",
+        json!({}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "GET",
+        "/v1beta/batches",
+        "key-1",
+        json!({"operations": [{"name": "batches/one"}], "nextPageToken": "t1"}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "GET",
+        "/v1beta/batches",
+        "key-2",
+        json!({"operations": [{"name": "batches/two"}]}),
+    )
+    .await;
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({"error": "wrong key"})))
+        .with_priority(u8::MAX)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let mut created = Vec::new();
+    for _ in 0..2 {
+        let (status, body) = send(&app, Method::POST, create).await;
+        assert_eq!(status, StatusCode::OK);
+        created.push(body["name"].as_str().unwrap().to_string());
+    }
+    created.sort();
+    assert_eq!(created, vec!["batches/one", "batches/two"]);
+
+    // Rotation would alternate keys; affinity keeps every call on the owner.
+    for _ in 0..3 {
+        let (status, body) = send(&app, Method::GET, "/v1beta/batches/one").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["done"], true);
+    }
+    for _ in 0..2 {
+        let (status, _) = send(
+            &app,
+            Method::GET,
+            "/download/v1beta/files/res-one:download?alt=media",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _) = send(&app, Method::POST, "/v1beta/batches/two:cancel").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&app, Method::GET, "/v1beta/batches").await;
+    assert_eq!(status, StatusCode::OK);
+    let mut names: Vec<&str> = body["operations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|op| op["name"].as_str().unwrap())
+        .collect();
+    names.sort_unstable();
+    assert_eq!(names, vec!["batches/one", "batches/two"]);
+    assert!(body.get("nextPageToken").is_none());
+}
+
+#[tokio::test]
+async fn test_batch_listing_skips_failing_keys() {
+    let server = MockServer::start().await;
+    let create = "/v1beta/models/gemini-2.0-flash:batchGenerateContent";
+    mount_for_key(
+        &server,
+        "POST",
+        create,
+        "key-1",
+        json!({"name": "batches/one"}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "POST",
+        create,
+        "key-2",
+        json!({"name": "batches/two"}),
+    )
+    .await;
+    mount_for_key(
+        &server,
+        "GET",
+        "/v1beta/batches",
+        "key-1",
+        json!({"operations": [{"name": "batches/one"}]}),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/v1beta/batches"))
+        .and(query_param("key", "key-2"))
+        .respond_with(
+            ResponseTemplate::new(403).set_body_json(json!({"error": "permission denied"})),
+        )
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    for _ in 0..2 {
+        let (status, _) = send(&app, Method::POST, create).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, body) = send(&app, Method::GET, "/v1beta/batches").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["operations"], json!([{"name": "batches/one"}]));
+    // Pages of a merged listing cannot be resumed.
+    let (status, _) = send(&app, Method::GET, "/v1beta/batches?pageToken=abc").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
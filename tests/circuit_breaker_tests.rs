@@ -1,6 +1,8 @@
 // tests/circuit_breaker_tests.rs
 
-use gemini_proxy::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use gemini_proxy::circuit_breaker::{
+    CircuitBreaker, CircuitBreakerConfig, CircuitBreakerRegistry, CircuitState, ForcedState,
+};
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -71,3 +73,88 @@ async fn test_circuit_breaker_half_open_failure() {
     let _ = cb.call(|| async { Err::<(), &str>("error") }).await;
     assert_eq!(cb.get_state().await, CircuitState::Open);
 }
+
+#[tokio::test]
+async fn test_registry_keeps_keys_and_models_apart() {
+    let registry = CircuitBreakerRegistry::new(CircuitBreakerConfig {
+        failure_threshold: 2,
+        recovery_timeout: Duration::from_secs(60),
+        success_threshold: 1,
+    });
+
+    registry.record_failure("key-a", Some("gemini-pro")).await;
+    // A success on another model resets the key's breaker, not the model's.
+    registry.record_success("key-a", Some("gemini-flash")).await;
+    registry.record_failure("key-a", Some("gemini-pro")).await;
+
+    assert!(!registry.try_acquire("key-a", Some("gemini-pro")).await);
+    assert!(registry.try_acquire("key-a", Some("gemini-flash")).await);
+    assert!(registry.try_acquire("key-b", Some("gemini-pro")).await);
+    assert_eq!(
+        registry.breaker("key-a", None).get_state().await,
+        CircuitState::Closed
+    );
+}
+
+#[tokio::test]
+async fn test_registry_peek_does_not_create_breakers() {
+    let registry = CircuitBreakerRegistry::new(CircuitBreakerConfig::default());
+
+    let (name, snapshot) = registry.peek("key-a", None).await;
+    assert!(name.starts_with("key_"));
+    assert_eq!(snapshot.state, CircuitState::Closed);
+    assert_eq!(snapshot.total_requests, 0);
+    assert!(registry.breakers().is_empty());
+
+    registry.record_failure("key-a", None).await;
+    let (_, snapshot) = registry.peek("key-a", None).await;
+    assert_eq!(snapshot.failure_count, 1);
+    assert_eq!(registry.breakers().len(), 1);
+}
+
+#[tokio::test]
+async fn test_registry_half_open_admits_limited_probes() {
+    let registry = CircuitBreakerRegistry::new(CircuitBreakerConfig {
+        failure_threshold: 1,
+        recovery_timeout: Duration::from_millis(50),
+        success_threshold: 1,
+    });
+
+    registry.record_failure("key-a", None).await;
+    assert!(!registry.try_acquire("key-a", None).await);
+
+    sleep(Duration::from_millis(60)).await;
+    assert!(registry.try_acquire("key-a", None).await);
+    // The probe is in flight; no further requests until it reports back.
+    assert!(!registry.try_acquire("key-a", None).await);
+
+    registry.record_success("key-a", None).await;
+    assert_eq!(
+        registry.breaker("key-a", None).get_state().await,
+        CircuitState::Closed
+    );
+}
+
+#[tokio::test]
+async fn test_forced_state_overrides_outcomes() {
+    let config = CircuitBreakerConfig {
+        failure_threshold: 1,
+        recovery_timeout: Duration::from_millis(50),
+        success_threshold: 1,
+    };
+    let cb = CircuitBreaker::new("test".to_string(), config);
+
+    cb.force(ForcedState::Open).await;
+    sleep(Duration::from_millis(60)).await;
+    assert!(!cb.try_acquire().await);
+
+    cb.force(ForcedState::Closed).await;
+    cb.record_failure().await;
+    assert!(cb.try_acquire().await);
+    assert_eq!(cb.get_state().await, CircuitState::Closed);
+
+    cb.force(ForcedState::Auto).await;
+    cb.record_failure().await;
+    assert_eq!(cb.get_state().await, CircuitState::Open);
+    assert!(!cb.snapshot().await.forced);
+}
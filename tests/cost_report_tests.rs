@@ -0,0 +1,137 @@
+// tests/cost_report_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::connect_info::MockConnectInfo,
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ModelPricing, PricingConfig, TokenRates},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn call(app: &Router, request: Request<Body>) -> (StatusCode, String, Vec<u8>) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_daily_cost_report_prices_usage_per_model() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(
+            "/v1beta/models/gemini-2.5-pro-preview:generateContent",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [],
+            "usageMetadata": {
+                "promptTokenCount": 1000,
+                "candidatesTokenCount": 100,
+                "totalTokenCount": 1100
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let pricing = PricingConfig {
+        currency: "EUR".to_string(),
+        models: HashMap::from([(
+            "gemini-2.5-pro".to_string(),
+            ModelPricing {
+                rates: TokenRates {
+                    input_per_million: 1000.0,
+                    output_per_million: 10000.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )]),
+    };
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        pricing: Some(pricing),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state))
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 3000))));
+
+    for _ in 0..2 {
+        let (status, _, _) = call(
+            &app,
+            Request::post("/v1beta/models/gemini-2.5-pro-preview:generateContent")
+                .header("content-type", "application/json")
+                .header("x-client-id", "team-a")
+                .body(Body::from(r#"{"contents":[]}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _, body) = call(
+        &app,
+        Request::get("/admin/usage/daily?group_by=client")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["currency"], "EUR");
+    let days = report["days"].as_array().unwrap();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0]["client"], "team-a");
+    // 2 × (1000 × 1000 + 100 × 10000) per million tokens.
+    assert!((report["totals"]["cost"].as_f64().unwrap() - 4.0).abs() < 1e-9);
+
+    let (status, content_type, body) = call(
+        &app,
+        Request::get("/admin/usage/daily?group_by=model&format=csv")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/csv"));
+    let csv = String::from_utf8(body).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("date,model,requests,"));
+    assert!(lines[1].ends_with(",gemini-2.5-pro-preview,2,2000,200,0,0,2200,4.000000"));
+
+    let (status, _, _) = call(
+        &app,
+        Request::get("/admin/usage/daily?format=xml")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
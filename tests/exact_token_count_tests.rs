@@ -0,0 +1,153 @@
+// tests/exact_token_count_tests.rs
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use gemini_proxy::{
+    config::{AppConfig, ExactTokenCountConfig, KeyGroup, ServerConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const GENERATE: &str = "/v1beta/models/gemini-2.0-flash:generateContent";
+const COUNT: &str = "/v1beta/models/gemini-2.0-flash:countTokens";
+
+/// A 1000x1000 PNG header: four image tiles, an estimate of 1032 tokens.
+fn image_request(label: &str) -> String {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend_from_slice(&1000u32.to_be_bytes());
+    png.extend_from_slice(&1000u32.to_be_bytes());
+    json!({
+        "contents": [{"parts": [
+            {"text": label},
+            {"inline_data": {"mime_type": "image/png", "data": STANDARD.encode(&png)}}
+        ]}]
+    })
+    .to_string()
+}
+
+async fn send(app: &Router, body: String) -> StatusCode {
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(GENERATE)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_requests_near_the_limit_are_counted_upstream() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(COUNT))
+        .and(body_string_contains("fits"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"totalTokens": 900})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(COUNT))
+        .and(body_string_contains("too big"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"totalTokens": 1200})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(GENERATE))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        server: ServerConfig {
+            max_tokens_per_request: Some(1000),
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        exact_token_count: Some(ExactTokenCountConfig::default()),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    // The estimate (1032) is over the limit, but the exact count (900) is not;
+    // the second identical request is decided from the cache.
+    for _ in 0..2 {
+        assert_eq!(send(&app, image_request("fits")).await, StatusCode::OK);
+    }
+    assert!(send(&app, image_request("too big")).await.is_client_error());
+}
+
+#[tokio::test]
+async fn test_rate_limited_count_marks_the_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(COUNT))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "60")
+                .set_body_json(json!({"error": {}})),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(GENERATE))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        server: ServerConfig {
+            max_tokens_per_request: Some(1000),
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        exact_token_count: Some(ExactTokenCountConfig::default()),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    // The count fails, so the estimate decides; the rate-limited key is then
+    // not handed out for the next request.
+    assert!(send(&app, image_request("count me"))
+        .await
+        .is_client_error());
+    let small = json!({"contents": [{"parts": [{"text": "hi"}]}]}).to_string();
+    assert_ne!(send(&app, small).await, StatusCode::OK);
+}
@@ -0,0 +1,125 @@
+// tests/group_selection_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, GroupSelectionConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const GENERATE_PATH: &str = "/v1beta/models/gemini-2.5-flash:generateContent";
+
+async fn generate(app: &Router, headers: &[(&str, &str)]) -> StatusCode {
+    let mut request = Request::post(GENERATE_PATH).header("content-type", "application/json");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let body = json!({"contents": [{"parts": [{"text": "hi"}]}]}).to_string();
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+async fn used_keys(server: &MockServer) -> Vec<String> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| {
+            assert!(request.headers.get("x-proxy-group").is_none());
+            let (_, key) = request
+                .url
+                .query_pairs()
+                .find(|(name, _)| name == "key")
+                .unwrap();
+            key.into_owned()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_allowed_clients_pin_requests_to_a_group_or_tier() {
+    let server = MockServer::start().await;
+    for key in ["key-default", "key-eval"] {
+        Mock::given(method("POST"))
+            .and(query_param("key", key))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+            .mount(&server)
+            .await;
+    }
+
+    let config = AppConfig {
+        groups: vec![
+            KeyGroup {
+                name: "default".to_string(),
+                api_keys: vec!["key-default".to_string()],
+                model_aliases: vec!["gemini-2.5-flash".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "eval".to_string(),
+                api_keys: vec!["key-eval".to_string()],
+                target_url: server.uri(),
+                tier: Some("eval".to_string()),
+                ..Default::default()
+            },
+        ],
+        group_selection: Some(GroupSelectionConfig {
+            clients: HashMap::from([("harness".to_string(), vec!["eval".to_string()])]),
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    assert_eq!(generate(&app, &[]).await, StatusCode::OK);
+    let harness = ("x-client-id", "harness");
+    assert_eq!(
+        generate(&app, &[harness, ("x-proxy-group", "eval")]).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        generate(&app, &[harness, ("x-proxy-key-tier", "eval")]).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        used_keys(&server).await,
+        ["key-default", "key-eval", "key-eval"]
+    );
+
+    // Other clients may not leave the model's group.
+    assert_eq!(
+        generate(
+            &app,
+            &[("x-client-id", "team-b"), ("x-proxy-group", "eval")]
+        )
+        .await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        generate(&app, &[harness, ("x-proxy-group", "missing")]).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(used_keys(&server).await.len(), 3);
+}
@@ -0,0 +1,206 @@
+// tests/hedging_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+};
+use gemini_proxy::{
+    config::{AppConfig, HedgingConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn test_slow_key_is_hedged_with_another_key() {
+    let server = MockServer::start().await;
+    let endpoint = "/v1beta/models/gemini-2.0-flash:generateContent";
+    Mock::given(method("POST"))
+        .and(path(endpoint))
+        .and(query_param("key", "slow-key"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"from": "slow"}))
+                .set_delay(Duration::from_secs(2)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(endpoint))
+        .and(query_param("key", "fast-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"from": "fast"})))
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["slow-key".to_string(), "fast-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        hedging: Some(HedgingConfig {
+            delay_ms: Some(50),
+            max_hedge_ratio: 1.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    // Rotation guarantees one of the two requests starts on the slow key.
+    for _ in 0..2 {
+        let started = Instant::now();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(endpoint)
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"contents":[]}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["from"], "fast");
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "hedged request should not wait for the slow key"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_resource_creation_is_not_hedged() {
+    let server = MockServer::start().await;
+    let endpoint = "/v1beta/cachedContents";
+    Mock::given(method("POST"))
+        .and(path(endpoint))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"name": "cachedContents/abc"}))
+                .set_delay(Duration::from_millis(300)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-a".to_string(), "key-b".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        hedging: Some(HedgingConfig {
+            delay_ms: Some(50),
+            max_hedge_ratio: 1.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(endpoint)
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"model":"models/gemini-2.0-flash"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // A hedge would have sent a second create on the other key.
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn test_rate_limited_hedge_key_is_blocked() {
+    let server = MockServer::start().await;
+    let endpoint = "/v1beta/models/gemini-2.0-flash:generateContent";
+    Mock::given(method("POST"))
+        .and(path(endpoint))
+        .and(query_param("key", "slow-key"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"from": "slow"}))
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    // Fails before the slow key answers, whichever key the request starts on;
+    // its result is dropped in favour of the slow key's.
+    Mock::given(method("POST"))
+        .and(path(endpoint))
+        .and(query_param("key", "limited-key"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "60")
+                .set_delay(Duration::from_millis(100)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["slow-key".to_string(), "limited-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        hedging: Some(HedgingConfig {
+            delay_ms: Some(50),
+            max_hedge_ratio: 1.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    // The second request must not reach the rate-limited key again.
+    for _ in 0..2 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(endpoint)
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"contents":[]}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["from"], "slow");
+    }
+}
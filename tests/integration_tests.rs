@@ -40,6 +40,16 @@ fn create_test_config(groups: Vec<KeyGroup>, server_port: u16, _db_num: usize) -
     AppConfig {
         server: ServerConfig {
             max_tokens_per_request: Some(250_000),
+            openai_native_translation: false,
+            model_catalog_refresh_secs: 600,
+            coalesce_requests: false,
+            stream_request_bodies: false,
+            max_request_size: 10 * 1024 * 1024,
+            max_request_size_by_route: Default::default(),
+            max_attempts: None,
+            request_deadline_secs: None,
+            public_base_url: None,
+            trust_forwarded_headers: false,
             port: server_port,
             top_p: None,
             admin_token: Some("test_token".to_string()),
@@ -51,7 +61,16 @@ fn create_test_config(groups: Vec<KeyGroup>, server_port: u16, _db_num: usize) -
         redis_url: None, // Disable Redis for tests
         redis_key_prefix: None,
         internal_retries: Some(3),
+        transient_retries: None,
         temporary_block_minutes: Some(1),
+        response_cache: None,
+        hedging: None,
+        key_wait_queue: None,
+        priority: None,
+        exact_token_count: None,
+        pricing: None,
+        group_selection: None,
+        retry_backoff: None,
         top_p: None,
         max_failures_threshold: Some(10),
         rate_limit: None,
@@ -123,6 +142,11 @@ async fn test_forward_request_openai_compat_success_no_proxy() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9999, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -186,6 +210,11 @@ async fn test_handler_retries_on_429_and_succeeds() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9998, db_num); // Different port just in case
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -260,6 +289,11 @@ async fn test_handler_returns_last_429_on_exhaustion() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9997, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -325,6 +359,11 @@ async fn test_handler_group_round_robin() {
             target_url: server.uri(),
             proxy_url: None,
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         },
         KeyGroup {
             name: "group2".to_string(),
@@ -333,6 +372,11 @@ async fn test_handler_group_round_robin() {
             target_url: server.uri(),
             proxy_url: None,
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         },
     ];
     let config = create_test_config(groups, 9996, db_num);
@@ -443,6 +487,11 @@ async fn test_openai_top_p_injection_correctly() {
             target_url: server.uri(),
             proxy_url: None,
             top_p: None, // Group level top_p is not used for this path
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         }],
         9993,
         db_num,
@@ -504,6 +553,11 @@ async fn test_health_detailed_maps_to_models_endpoint() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9992, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -603,6 +657,11 @@ async fn test_content_length_is_updated_after_top_p_injection() {
             target_url: server.uri(),
             proxy_url: None,
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         }],
         9991,
         db_num,
@@ -677,6 +736,11 @@ async fn test_top_p_client_precedence() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: Some(server_top_p), // Set a server-side value
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9994, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -739,6 +803,11 @@ async fn test_url_translation_for_v1_path() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9990, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -793,6 +862,11 @@ async fn test_proxy_handler_returns_502_on_internal_error_from_try_request_with_
         target_url: server.uri(), // Use the valid mock server URL
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9985, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -842,6 +916,11 @@ async fn test_url_translation_for_non_v1_path() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9989, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -914,6 +993,11 @@ async fn test_rotates_on_400_with_api_key_invalid_body() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9988, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -991,6 +1075,11 @@ async fn test_returns_immediately_on_400_with_other_body() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9987, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
@@ -1095,6 +1184,11 @@ async fn test_rate_limit_with_retry_after_header() {
         target_url: server.uri(),
         proxy_url: None,
         top_p: None,
+        request_rules: None,
+        max_request_size: None,
+        tier: None,
+        max_attempts: None,
+        request_deadline_secs: None,
     };
     let config = create_test_config(vec![test_group], 9986, db_num);
     let (app_state_instance, _) = AppState::new(&config, &dummy_config_path)
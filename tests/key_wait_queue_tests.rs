@@ -0,0 +1,107 @@
+// tests/key_wait_queue_tests.rs
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, KeyWaitQueueConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const ENDPOINT: &str = "/v1beta/models/gemini-2.0-flash:generateContent";
+
+async fn setup(server: &MockServer, retry_after_secs: u64) -> (Router, tempfile::TempDir) {
+    Mock::given(method("POST"))
+        .and(path(ENDPOINT))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", retry_after_secs.to_string().as_str()),
+        )
+        .up_to_n_times(1)
+        .mount(server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(ENDPOINT))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ok": true})))
+        .mount(server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["only-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        key_wait_queue: Some(KeyWaitQueueConfig {
+            max_waiters_per_group: 10,
+            max_wait_secs: 5,
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    (create_router(Arc::new(state)), temp_dir)
+}
+
+fn request(max_wait_ms: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri(ENDPOINT)
+        .header("content-type", "application/json");
+    if let Some(ms) = max_wait_ms {
+        builder = builder.header("x-proxy-max-wait-ms", ms);
+    }
+    builder.body(Body::from(r#"{"contents":[]}"#)).unwrap()
+}
+
+#[tokio::test]
+async fn test_request_waits_for_rate_limited_key() {
+    let server = MockServer::start().await;
+    let (app, _dir) = setup(&server, 1).await;
+
+    let started = Instant::now();
+    let response = app.oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn test_client_deadline_returns_503_with_retry_after() {
+    let server = MockServer::start().await;
+    let (app, _dir) = setup(&server, 1).await;
+
+    let response = app.oneshot(request(Some("0"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+}
+
+#[tokio::test]
+async fn test_block_longer_than_max_wait_fails_fast() {
+    let server = MockServer::start().await;
+    let (app, _dir) = setup(&server, 60).await;
+
+    let started = Instant::now();
+    let response = app.oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(started.elapsed() < Duration::from_secs(1));
+    let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((59..=60).contains(&retry_after));
+}
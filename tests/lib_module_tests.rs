@@ -77,6 +77,16 @@ fn test_app_config_creation() {
         server: gemini_proxy::config::ServerConfig {
             port: 8080,
             max_tokens_per_request: Some(250_000),
+            openai_native_translation: false,
+            model_catalog_refresh_secs: 600,
+            coalesce_requests: false,
+            stream_request_bodies: false,
+            max_request_size: 10 * 1024 * 1024,
+            max_request_size_by_route: Default::default(),
+            max_attempts: None,
+            request_deadline_secs: None,
+            public_base_url: None,
+            trust_forwarded_headers: false,
             test_mode: false,
             connect_timeout_secs: 10,
             request_timeout_secs: 60,
@@ -90,11 +100,25 @@ fn test_app_config_creation() {
             target_url: "https://generativelanguage.googleapis.com".to_string(),
             proxy_url: None,
             top_p: None,
+            request_rules: None,
+            max_request_size: None,
+            tier: None,
+            max_attempts: None,
+            request_deadline_secs: None,
         }],
         redis_url: None,
         redis_key_prefix: None,
         internal_retries: None,
+        transient_retries: None,
         temporary_block_minutes: None,
+        response_cache: None,
+        hedging: None,
+        key_wait_queue: None,
+        priority: None,
+        exact_token_count: None,
+        pricing: None,
+        group_selection: None,
+        retry_backoff: None,
         top_p: None,
         max_failures_threshold: None,
         rate_limit: None,
@@ -0,0 +1,207 @@
+// tests/live_tests.rs
+
+use futures::{SinkExt, StreamExt};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    create_router,
+    state::AppState,
+};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::net::TcpListener;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+        protocol::frame::coding::CloseCode,
+        Message,
+    },
+};
+
+const LIVE_PATH: &str =
+    "/ws/google.ai.generativelanguage.v1beta.GenerativeService.BidiGenerateContent";
+const SETUP: &str = r#"{"setup":{"model":"models/gemini-2.0-flash-live-001"}}"#;
+
+/// How the upstream stand-in treats a session it accepts.
+#[derive(Clone, Copy)]
+enum Upstream {
+    Echo,
+    DropAfterSetup,
+}
+
+/// Starts a WebSocket server that rejects `key-a` with 429 and handles
+/// sessions on other keys as `mode` says. Returns its address and the paths
+/// and keys of the handshakes it saw.
+// The handshake callback's error type is tungstenite's.
+#[allow(clippy::result_large_err)]
+async fn start_upstream(mode: Upstream) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handshakes = Arc::new(Mutex::new(Vec::new()));
+    let seen = handshakes.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let seen = seen.clone();
+            tokio::spawn(async move {
+                let callback = |request: &Request, response: Response| {
+                    let uri = request.uri().to_string();
+                    seen.lock().unwrap().push(uri.clone());
+                    if uri.ends_with("key=key-a") {
+                        let mut rejection = ErrorResponse::new(Some(
+                            r#"{"error":{"code":429,"status":"RESOURCE_EXHAUSTED"}}"#.to_string(),
+                        ));
+                        *rejection.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                        return Err(rejection);
+                    }
+                    Ok(response)
+                };
+                let Ok(mut socket) = tokio_tungstenite::accept_hdr_async(stream, callback).await
+                else {
+                    return;
+                };
+                while let Some(Ok(message)) = socket.next().await {
+                    match (mode, message) {
+                        (_, Message::Close(_)) => break,
+                        (Upstream::DropAfterSetup, _) => return,
+                        (Upstream::Echo, message) => {
+                            socket.send(message).await.unwrap();
+                        }
+                    }
+                }
+            });
+        }
+    });
+    (addr, handshakes)
+}
+
+async fn start_proxy(upstream: SocketAddr) -> SocketAddr {
+    start_proxy_with_timeout(upstream, ServerConfig::default().request_timeout_secs).await
+}
+
+async fn start_proxy_with_timeout(upstream: SocketAddr, request_timeout_secs: u64) -> SocketAddr {
+    let mut config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-a".to_string(), "key-b".to_string()],
+            target_url: format!("http://{upstream}"),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    config.server.request_timeout_secs = request_timeout_secs;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+#[tokio::test]
+async fn test_live_session_relays_frames_with_a_rotated_key() {
+    let (upstream, handshakes) = start_upstream(Upstream::Echo).await;
+    let proxy = start_proxy(upstream).await;
+
+    let (mut client, _) = connect_async(format!("ws://{proxy}{LIVE_PATH}"))
+        .await
+        .unwrap();
+    client.send(Message::Text(SETUP.to_string())).await.unwrap();
+    assert_eq!(
+        client.next().await.unwrap().unwrap(),
+        Message::Text(SETUP.to_string())
+    );
+    client
+        .send(Message::Binary(b"audio chunk".to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(
+        client.next().await.unwrap().unwrap(),
+        Message::Binary(b"audio chunk".to_vec())
+    );
+    client.close(None).await.unwrap();
+
+    let handshakes = handshakes.lock().unwrap().clone();
+    assert_eq!(
+        handshakes,
+        [
+            format!("{LIVE_PATH}?key=key-a"),
+            format!("{LIVE_PATH}?key=key-b"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_live_session_closes_cleanly_when_upstream_fails() {
+    let (upstream, _) = start_upstream(Upstream::DropAfterSetup).await;
+    let proxy = start_proxy(upstream).await;
+
+    let (mut client, _) = connect_async(format!("ws://{proxy}{LIVE_PATH}"))
+        .await
+        .unwrap();
+    client.send(Message::Text(SETUP.to_string())).await.unwrap();
+    client
+        .send(Message::Text(r#"{"clientContent":{}}"#.to_string()))
+        .await
+        .unwrap();
+
+    match client.next().await.unwrap().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Error),
+        other => panic!("Expected a close frame, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_live_session_requires_a_setup_message() {
+    let (upstream, handshakes) = start_upstream(Upstream::Echo).await;
+    let proxy = start_proxy(upstream).await;
+
+    let (mut client, _) = connect_async(format!("ws://{proxy}{LIVE_PATH}"))
+        .await
+        .unwrap();
+    client
+        .send(Message::Text(r#"{"clientContent":{}}"#.to_string()))
+        .await
+        .unwrap();
+
+    match client.next().await.unwrap().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Policy),
+        other => panic!("Expected a close frame, got {other:?}"),
+    }
+    assert!(handshakes.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_live_handshake_times_out() {
+    // Accepts connections but never answers the WebSocket handshake.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+    let proxy = start_proxy_with_timeout(upstream, 1).await;
+
+    let (mut client, _) = connect_async(format!("ws://{proxy}{LIVE_PATH}"))
+        .await
+        .unwrap();
+    let started = Instant::now();
+    client.send(Message::Text(SETUP.to_string())).await.unwrap();
+
+    let message = tokio::time::timeout(Duration::from_secs(10), client.next())
+        .await
+        .expect("handshake was not bounded");
+    match message.unwrap().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Error),
+        other => panic!("Expected a close frame, got {other:?}"),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
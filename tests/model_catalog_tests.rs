@@ -0,0 +1,166 @@
+// tests/model_catalog_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn create_state(server: &MockServer, temp_dir: &TempDir, refresh_secs: u64) -> Arc<AppState> {
+    let config = AppConfig {
+        server: ServerConfig {
+            model_catalog_refresh_secs: refresh_secs,
+            ..Default::default()
+        },
+        groups: vec![
+            KeyGroup {
+                name: "flash".to_string(),
+                api_keys: vec!["flash-key".to_string()],
+                model_aliases: vec!["gemini-2.0-flash".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "tuned".to_string(),
+                api_keys: vec!["tuned-key".to_string()],
+                model_aliases: vec!["tuned-model".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    Arc::new(state)
+}
+
+async fn get_json(app: Router, uri: &str) -> Value {
+    let response = app
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+async fn mount_model_list(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/v1beta/models"))
+        .and(query_param("key", "flash-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "models": [
+                {"name": "models/gemini-2.0-flash", "displayName": "Gemini 2.0 Flash"},
+                {"name": "models/text-embedding-004", "displayName": "Text Embedding 004"}
+            ]
+        })))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1beta/models"))
+        .and(query_param("key", "tuned-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"models": []})))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_catalog_merges_upstream_models_and_aliases() {
+    let server = MockServer::start().await;
+    mount_model_list(&server).await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let state = create_state(&server, &temp_dir, 600).await;
+
+    // Block the only key of the "tuned" group.
+    state
+        .key_manager
+        .read()
+        .await
+        .handle_api_failure("tuned-key", true)
+        .await
+        .unwrap();
+
+    let openai = get_json(create_router(state.clone()), "/v1/models").await;
+    let ids: Vec<&str> = openai["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        ids,
+        vec!["gemini-2.0-flash", "text-embedding-004", "tuned-model"]
+    );
+    assert_eq!(openai["data"][0]["available"], true);
+    assert_eq!(openai["data"][2]["available"], false);
+
+    let gemini = get_json(create_router(state), "/v1beta/models").await;
+    assert_eq!(gemini["models"][0]["displayName"], "Gemini 2.0 Flash");
+    assert_eq!(gemini["models"][2]["name"], "models/tuned-model");
+    assert_eq!(gemini["models"][2]["available"], false);
+}
+
+#[tokio::test]
+async fn test_catalog_disabled_proxies_models_list() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1beta/openai/models"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"object": "list", "data": []})),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let state = create_state(&server, &temp_dir, 0).await;
+
+    let models = get_json(create_router(state), "/v1/models").await;
+    assert_eq!(models["data"], json!([]));
+}
+
+#[tokio::test]
+async fn test_catalog_refresh_rotates_keys() {
+    let server = MockServer::start().await;
+    for key in ["key-a", "key-b"] {
+        Mock::given(method("GET"))
+            .and(path("/v1beta/models"))
+            .and(query_param("key", key))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"models": []})))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-a".to_string(), "key-b".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let state = Arc::new(state);
+
+    state.model_catalog.refresh(&state).await;
+    state.model_catalog.refresh(&state).await;
+    assert!(state.model_catalog.is_loaded());
+}
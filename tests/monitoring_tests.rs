@@ -36,6 +36,7 @@ impl MockKeyManager {
                 is_blocked: false,
                 consecutive_failures: 0,
                 last_failure: None,
+                blocked_until: None,
             };
 
             keys.insert(key.clone(), key_info);
@@ -98,6 +99,34 @@ impl KeyManagerTrait for MockKeyManager {
         Ok(())
     }
 
+    async fn set_resource_owner(
+        &self,
+        _resource: &str,
+        _api_key: &str,
+    ) -> Result<(), gemini_proxy::error::AppError> {
+        Ok(())
+    }
+
+    async fn get_resource_owner(
+        &self,
+        _resource: &str,
+    ) -> Result<Option<String>, gemini_proxy::error::AppError> {
+        Ok(None)
+    }
+
+    async fn remove_resource_owner(
+        &self,
+        _resource: &str,
+    ) -> Result<(), gemini_proxy::error::AppError> {
+        Ok(())
+    }
+
+    async fn get_resource_owners(
+        &self,
+    ) -> Result<HashMap<String, String>, gemini_proxy::error::AppError> {
+        Ok(HashMap::new())
+    }
+
     async fn reload(
         &mut self,
         _config: &gemini_proxy::config::AppConfig,
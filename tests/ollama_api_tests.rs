@@ -0,0 +1,195 @@
+// tests/ollama_api_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{body_partial_json, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn create_app(server: &MockServer, temp_dir: &TempDir) -> Router {
+    let config = AppConfig {
+        groups: vec![
+            KeyGroup {
+                name: "flash".to_string(),
+                api_keys: vec!["ollama-key".to_string()],
+                model_aliases: vec!["gemini-2.0-flash".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "pro".to_string(),
+                api_keys: vec!["ollama-key-2".to_string()],
+                model_aliases: vec!["gemini-2.5-pro".to_string(), "gemini-2.0-flash".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    create_router(Arc::new(state))
+}
+
+fn post(uri: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_tags_lists_model_aliases() {
+    let server = MockServer::start().await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/tags")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let tags: Value = serde_json::from_slice(&body).unwrap();
+    let names: Vec<&str> = tags["models"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["gemini-2.0-flash", "gemini-2.5-pro"]);
+}
+
+#[tokio::test]
+async fn test_chat_streams_ndjson() {
+    let server = MockServer::start().await;
+    let sse = concat!(
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hel\"}]}}]}\n\n",
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"lo\"}]},\"finishReason\":\"STOP\"}],",
+        "\"usageMetadata\":{\"promptTokenCount\":3,\"candidatesTokenCount\":2}}\n\n",
+    );
+    Mock::given(method("POST"))
+        .and(path(
+            "/v1beta/models/gemini-2.0-flash:streamGenerateContent",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(sse, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(post(
+            "/api/chat",
+            json!({
+                "model": "gemini-2.0-flash:latest",
+                "messages": [{"role": "user", "content": "Hi"}]
+            }),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "application/x-ndjson"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let lines: Vec<Value> = String::from_utf8(body.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["message"]["content"], "Hel");
+    assert_eq!(lines[1]["message"]["content"], "lo");
+    assert_eq!(lines[2]["done"], true);
+    assert_eq!(lines[2]["eval_count"], 2);
+}
+
+#[tokio::test]
+async fn test_generate_without_streaming() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.5-pro:generateContent"))
+        .and(query_param("key", "ollama-key-2"))
+        .and(body_partial_json(json!({
+            "contents": [{"role": "user", "parts": [{"text": "Why?"}]}]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{"content": {"parts": [{"text": "Because."}]}, "finishReason": "STOP"}]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(post(
+            "/api/generate",
+            json!({"model": "gemini-2.5-pro", "prompt": "Why?", "stream": false}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let generated: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(generated["response"], "Because.");
+    assert_eq!(generated["done"], true);
+}
+
+#[tokio::test]
+async fn test_embed_uses_batch_embed_contents() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/text-embedding-004:batchEmbedContents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "embeddings": [{"values": [0.5, 0.25]}, {"values": [0.1, 0.2]}]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+
+    let response = app
+        .oneshot(post(
+            "/api/embed",
+            json!({"model": "text-embedding-004", "input": ["a", "b"]}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let embed: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(embed["embeddings"], json!([[0.5, 0.25], [0.1, 0.2]]));
+}
@@ -0,0 +1,179 @@
+// tests/openai_translation_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{Method, StatusCode},
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    handlers,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use wiremock::{
+    matchers::{body_partial_json, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn create_state(server: &MockServer, temp_dir: &TempDir) -> Arc<AppState> {
+    let config = AppConfig {
+        server: ServerConfig {
+            openai_native_translation: true,
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["native-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    Arc::new(state)
+}
+
+async fn post_chat(state: Arc<AppState>, body: Value) -> axum::response::Response {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    handlers::proxy_handler(State(state), request)
+        .await
+        .expect("Proxy handler returned an error")
+}
+
+#[tokio::test]
+async fn test_chat_completion_is_translated_to_generate_content() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .and(query_param("key", "native-key"))
+        .and(body_partial_json(json!({
+            "systemInstruction": {"parts": [{"text": "Be terse."}]},
+            "contents": [{"role": "user", "parts": [{"text": "Hi"}]}],
+            "generationConfig": {"maxOutputTokens": 16}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Hello!"}]},
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let state = create_state(&server, &temp_dir).await;
+
+    let response = post_chat(
+        state,
+        json!({
+            "model": "gemini-2.0-flash",
+            "messages": [
+                {"role": "system", "content": "Be terse."},
+                {"role": "user", "content": "Hi"}
+            ],
+            "max_tokens": 16
+        }),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let completion: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(completion["object"], "chat.completion");
+    assert_eq!(completion["choices"][0]["message"]["content"], "Hello!");
+    assert_eq!(completion["choices"][0]["finish_reason"], "stop");
+    assert_eq!(completion["usage"]["total_tokens"], 5);
+}
+
+#[tokio::test]
+async fn test_streaming_chat_completion_is_translated() {
+    let server = MockServer::start().await;
+    let sse = concat!(
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hel\"}]},\"index\":0}]}\r\n\r\n",
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"lo\"}]},\"finishReason\":\"STOP\",\"index\":0}],",
+        "\"usageMetadata\":{\"promptTokenCount\":1,\"candidatesTokenCount\":2,\"totalTokenCount\":3}}\r\n\r\n",
+    );
+    Mock::given(method("POST"))
+        .and(path(
+            "/v1beta/models/gemini-2.0-flash:streamGenerateContent",
+        ))
+        .and(query_param("alt", "sse"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(sse, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let state = create_state(&server, &temp_dir).await;
+
+    let response = post_chat(
+        state,
+        json!({
+            "model": "gemini-2.0-flash",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "stream": true,
+            "stream_options": {"include_usage": true}
+        }),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+
+    let chunks: Vec<Value> = text
+        .split("\n\n")
+        .filter_map(|event| event.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0]["object"], "chat.completion.chunk");
+    assert_eq!(chunks[0]["choices"][0]["delta"]["content"], "Hel");
+    assert_eq!(chunks[1]["choices"][0]["finish_reason"], "stop");
+    assert_eq!(chunks[2]["usage"]["completion_tokens"], 2);
+    assert!(text.ends_with("data: [DONE]\n\n"));
+}
+
+#[tokio::test]
+async fn test_upstream_errors_pass_through_untranslated() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": {"code": 400, "message": "bad request", "status": "INVALID_ARGUMENT"}
+        })))
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let state = create_state(&server, &temp_dir).await;
+
+    let response = post_chat(
+        state,
+        json!({"model": "gemini-2.0-flash", "messages": [{"role": "user", "content": "Hi"}]}),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["error"]["status"], "INVALID_ARGUMENT");
+}
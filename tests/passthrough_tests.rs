@@ -0,0 +1,136 @@
+// tests/passthrough_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const EMBED_PATH: &str = "/v1beta/models/text-embedding-004:embedContent";
+
+async fn embed(app: &Router, text: &str) -> StatusCode {
+    let body = json!({"content": {"parts": [{"text": text}]}}).to_string();
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post(EMBED_PATH)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+#[tokio::test]
+async fn test_streamed_bodies_reach_upstream_without_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(EMBED_PATH))
+        .and(query_param("key", "key-a"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({"error": {"code": 429}})))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(EMBED_PATH))
+        .and(query_param("key", "key-b"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"embedding": {"values": [0.5]}})),
+        )
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        server: ServerConfig {
+            stream_request_bodies: true,
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-a".to_string(), "key-b".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let text = "streamed ".repeat(10_000);
+    let mut statuses = vec![embed(&app, &text).await, embed(&app, &text).await];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::TOO_MANY_REQUESTS]);
+
+    // Each request was sent once, with its body intact.
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let expected = json!({"content": {"parts": [{"text": text}]}});
+    for request in &requests {
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&request.body).unwrap(),
+            expected
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_groups_with_top_p_keep_the_retrying_path() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(EMBED_PATH))
+        .and(query_param("key", "key-a"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({"error": {"code": 429}})))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(EMBED_PATH))
+        .and(query_param("key", "key-b"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"embedding": {"values": [0.5]}})),
+        )
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        server: ServerConfig {
+            stream_request_bodies: true,
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-a".to_string(), "key-b".to_string()],
+            target_url: server.uri(),
+            top_p: Some(0.9),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    // The body is buffered, so the rate-limited key is followed by the other.
+    for _ in 0..2 {
+        assert_eq!(embed(&app, "hello").await, StatusCode::OK);
+    }
+}
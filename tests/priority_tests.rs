@@ -0,0 +1,125 @@
+// tests/priority_tests.rs
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, PriorityConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const ENDPOINT: &str = "/v1beta/models/gemini-2.0-flash:generateContent";
+
+async fn setup(
+    server: &MockServer,
+    api_keys: &[&str],
+    priority: PriorityConfig,
+    delay: Duration,
+) -> (Router, Arc<AppState>, tempfile::TempDir) {
+    Mock::given(method("POST"))
+        .and(path(ENDPOINT))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"ok": true}))
+                .set_delay(delay),
+        )
+        .mount(server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: api_keys.iter().map(|k| k.to_string()).collect(),
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        priority: Some(priority),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let state = Arc::new(state);
+    (create_router(state.clone()), state, temp_dir)
+}
+
+fn request(class: &str) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(ENDPOINT)
+        .header("content-type", "application/json")
+        .header("x-proxy-priority", class)
+        .body(Body::from(r#"{"contents":[]}"#))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_batch_concurrency_cap_sheds_only_batch() {
+    let server = MockServer::start().await;
+    let priority = PriorityConfig {
+        batch_max_concurrent: Some(1),
+        ..Default::default()
+    };
+    let (app, _state, _dir) =
+        setup(&server, &["key-1"], priority, Duration::from_millis(300)).await;
+
+    let (first, second, interactive) = tokio::join!(
+        app.clone().oneshot(request("batch")),
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            app.clone().oneshot(request("batch")).await
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            app.clone().oneshot(request("interactive")).await
+        },
+    );
+
+    assert_eq!(first.unwrap().status(), StatusCode::OK);
+    let second = second.unwrap();
+    assert_eq!(second.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(second.headers()[header::RETRY_AFTER], "1");
+    assert_eq!(interactive.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_last_key_is_reserved_for_interactive_under_pressure() {
+    let server = MockServer::start().await;
+    let (app, state, _dir) = setup(
+        &server,
+        &["key-1", "key-2"],
+        PriorityConfig::default(),
+        Duration::ZERO,
+    )
+    .await;
+
+    // With every key healthy, batch traffic is served normally.
+    let response = app.clone().oneshot(request("batch")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    state
+        .key_manager
+        .write()
+        .await
+        .handle_api_failure("key-1", true)
+        .await
+        .unwrap();
+
+    let response = app.clone().oneshot(request("batch")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let response = app.oneshot(request("interactive")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
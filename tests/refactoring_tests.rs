@@ -1,7 +1,7 @@
 // tests/refactoring_tests.rs
 
 use gemini_proxy::{
-    config::{AppConfig, KeyGroup, ServerConfig},
+    config::{app::CircuitBreakerConfig, AppConfig, KeyGroup, ServerConfig},
     key_manager::{KeyManager, KeyManagerTrait},
     storage::{memory::InMemoryStore, traits::KeyStore},
 };
@@ -45,6 +45,46 @@ async fn test_key_selector_round_robin() {
     );
 }
 
+#[tokio::test]
+async fn test_key_selection_skips_open_circuit_breakers() {
+    let mut config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "test_group".to_string(),
+            api_keys: vec!["key1".to_string(), "key2".to_string()],
+            target_url: "https://api.example.com".to_string(),
+            ..Default::default()
+        }],
+        circuit_breaker: Some(CircuitBreakerConfig {
+            failure_threshold: 1,
+            recovery_timeout_secs: 60,
+            half_open_max_calls: 1,
+        }),
+        ..Default::default()
+    };
+    let mut key_manager = KeyManager::new(&config, None).await.unwrap();
+    let breakers = key_manager.circuit_breakers();
+
+    breakers.record_failure("key1", Some("gemini-pro")).await;
+    for _ in 0..4 {
+        let key = key_manager
+            .get_next_available_key_info_for_model(Some("test_group"), Some("gemini-pro"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(key.key.expose_secret(), "key2");
+    }
+
+    // Raised thresholds apply on reload; the open breaker stays open.
+    config.circuit_breaker.as_mut().unwrap().failure_threshold = 3;
+    key_manager.reload(&config, None).await.unwrap();
+    breakers.record_failure("key2", Some("gemini-pro")).await;
+    let key = key_manager
+        .get_next_available_key_info_for_model(Some("test_group"), Some("gemini-pro"))
+        .await
+        .unwrap();
+    assert_eq!(key.unwrap().key.expose_secret(), "key2");
+}
+
 #[tokio::test]
 async fn test_memory_store_operations() {
     let mut key_info_map = HashMap::new();
@@ -0,0 +1,124 @@
+// tests/request_coalescing_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn create_app(server: &MockServer, temp_dir: &TempDir) -> Router {
+    let config = AppConfig {
+        server: ServerConfig {
+            coalesce_requests: true,
+            ..Default::default()
+        },
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["coalesce-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    create_router(Arc::new(state))
+}
+
+async fn send(app: Router, uri: &'static str, body: &'static str) -> (StatusCode, Value) {
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_identical_requests_share_one_upstream_call() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"candidates": [{"content": {"parts": [{"text": "hi"}]}}]}))
+                .set_delay(Duration::from_millis(200)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+    let uri = "/v1beta/models/gemini-2.0-flash:generateContent";
+
+    // Same request with different key order and whitespace.
+    let (a, b, c) = tokio::join!(
+        send(
+            app.clone(),
+            uri,
+            r#"{"contents":[{"parts":[{"text":"x"}]}],"n":1}"#
+        ),
+        send(
+            app.clone(),
+            uri,
+            r#"{"n": 1, "contents": [{"parts": [{"text": "x"}]}]}"#
+        ),
+        send(
+            app.clone(),
+            uri,
+            r#"{"contents":[{"parts":[{"text":"x"}]}],"n":1}"#
+        ),
+    );
+    for (status, body) in [a, b, c] {
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["candidates"][0]["content"]["parts"][0]["text"], "hi");
+    }
+}
+
+#[tokio::test]
+async fn test_upstream_errors_are_shared() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .respond_with(
+            ResponseTemplate::new(400)
+                .set_body_json(json!({"error": {"code": 400, "message": "bad"}}))
+                .set_delay(Duration::from_millis(200)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+    let uri = "/v1beta/models/gemini-2.0-flash:generateContent";
+    let body = r#"{"contents":[]}"#;
+
+    let (a, b) = tokio::join!(send(app.clone(), uri, body), send(app.clone(), uri, body));
+    assert_eq!(a.0, StatusCode::BAD_REQUEST);
+    assert_eq!(a, b);
+}
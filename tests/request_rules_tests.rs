@@ -0,0 +1,128 @@
+// tests/request_rules_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ParamRule, RequestRules},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn post(app: &Router, uri: &str, body: Value) -> StatusCode {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+#[tokio::test]
+async fn test_group_rules_rewrite_gemini_and_openai_bodies() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![
+            KeyGroup {
+                name: "default".to_string(),
+                api_keys: vec!["key-1".to_string()],
+                target_url: server.uri(),
+                request_rules: Some(RequestRules {
+                    temperature: Some(ParamRule::Clamp {
+                        min: Some(0.0),
+                        max: Some(1.0),
+                    }),
+                    safety_settings: Some(ParamRule::Remove),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "pro".to_string(),
+                api_keys: vec!["key-2".to_string()],
+                model_aliases: vec!["gemini-2.5-pro".to_string()],
+                target_url: server.uri(),
+                top_p: Some(0.5),
+                request_rules: Some(RequestRules {
+                    max_output_tokens: Some(ParamRule::Set(json!(256))),
+                    system_instruction: Some(ParamRule::Default(json!("Answer in English."))),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let status = post(
+        &app,
+        "/v1beta/models/gemini-2.0-flash:generateContent",
+        json!({
+            "contents": [{"parts": [{"text": "Hi"}]}],
+            "generationConfig": {"temperature": 1.8},
+            "safetySettings": [{"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_NONE"}]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let status = post(
+        &app,
+        "/v1/chat/completions",
+        json!({
+            "model": "gemini-2.5-pro",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 4096,
+            "temperature": 1.8
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    for request in &requests {
+        let length: usize = request.headers["content-length"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(length, request.body.len());
+    }
+
+    let gemini: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(gemini["generationConfig"]["temperature"], 1.0);
+    assert!(gemini.get("safetySettings").is_none());
+
+    // The pro group's rules replace the default group's for its models.
+    let openai: Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(openai["temperature"], 1.8);
+    assert_eq!(openai["max_tokens"], 256);
+    assert_eq!(openai["top_p"], 0.5);
+    assert_eq!(
+        openai["messages"][0],
+        json!({"role": "system", "content": "Answer in English."})
+    );
+}
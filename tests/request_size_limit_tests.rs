@@ -0,0 +1,215 @@
+// tests/request_size_limit_tests.rs
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    http::{Request, StatusCode},
+    middleware::from_fn_with_state,
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ServerConfig},
+    create_router,
+    middleware::request_size_limit_middleware,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use serial_test::serial;
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use tower::ServiceExt;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const KIB: usize = 1024;
+
+fn chunked(size: usize) -> Body {
+    let chunk = Bytes::from(vec![b'x'; KIB]);
+    Body::from_stream(futures::stream::iter(
+        (0..size / KIB).map(move |_| Ok::<_, Infallible>(chunk.clone())),
+    ))
+}
+
+async fn upstream() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+    server
+}
+
+async fn app(server: &MockServer, server_config: ServerConfig, groups: Vec<KeyGroup>) -> Router {
+    let groups = groups
+        .into_iter()
+        .map(|group| KeyGroup {
+            api_keys: vec![format!("key-{}", group.name)],
+            target_url: server.uri(),
+            ..group
+        })
+        .collect();
+    let config = AppConfig {
+        server: server_config,
+        groups,
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let state = Arc::new(state);
+    create_router(state.clone()).layer(from_fn_with_state(state, request_size_limit_middleware))
+}
+
+async fn send(app: &Router, uri: &str, body: Body) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(Request::post(uri).body(body).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_chunked_bodies_over_the_limit_get_413() {
+    let server = upstream().await;
+    let app = app(
+        &server,
+        ServerConfig {
+            max_request_size: 16 * KIB,
+            ..Default::default()
+        },
+        vec![KeyGroup {
+            name: "default".to_string(),
+            ..Default::default()
+        }],
+    )
+    .await;
+    let uri = "/v1beta/models/gemini-2.0-flash:generateContent";
+
+    let (status, body) = send(&app, uri, chunked(32 * KIB)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["status"], 413);
+    assert_eq!(
+        body["detail"],
+        "Request body exceeds the limit of 16384 bytes"
+    );
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    let (status, _) = send(&app, uri, chunked(8 * KIB)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_group_and_route_limits_override_the_server_limit() {
+    let server = upstream().await;
+    let app = app(
+        &server,
+        ServerConfig {
+            max_request_size: 16 * KIB,
+            max_request_size_by_route: HashMap::from([(
+                "/v1beta/models/text-embedding".to_string(),
+                64 * KIB,
+            )]),
+            ..Default::default()
+        },
+        vec![
+            KeyGroup {
+                name: "default".to_string(),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "large".to_string(),
+                model_aliases: vec!["gemini-2.5-pro".to_string()],
+                max_request_size: Some(64 * KIB),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "small".to_string(),
+                model_aliases: vec!["gemini-2.5-flash-lite".to_string()],
+                max_request_size: Some(KIB),
+                ..Default::default()
+            },
+        ],
+    )
+    .await;
+
+    let cases = [
+        ("/v1beta/models/gemini-2.5-pro:generateContent", 32, 200),
+        ("/v1beta/models/gemini-2.0-flash:generateContent", 32, 413),
+        ("/v1beta/models/text-embedding-004:embedContent", 32, 200),
+        (
+            "/v1beta/models/gemini-2.5-flash-lite:generateContent",
+            2,
+            413,
+        ),
+    ];
+    for (uri, kib, expected) in cases {
+        let (status, _) = send(&app, uri, chunked(kib * KIB)).await;
+        assert_eq!(status.as_u16(), expected, "{uri} with {kib} KiB");
+    }
+
+    // Chat completions name the model in the body
+    let body = json!({
+        "model": "gemini-2.5-flash-lite",
+        "messages": [{"role": "user", "content": "x".repeat(2 * KIB)}],
+    });
+    let (status, _) = send(&app, "/v1/chat/completions", Body::from(body.to_string())).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_streamed_passthrough_bodies_are_limited() {
+    let server = upstream().await;
+    let app = app(
+        &server,
+        ServerConfig {
+            stream_request_bodies: true,
+            max_request_size: 16 * KIB,
+            ..Default::default()
+        },
+        vec![KeyGroup {
+            name: "default".to_string(),
+            ..Default::default()
+        }],
+    )
+    .await;
+    let uri = "/v1beta/models/text-embedding-004:embedContent";
+
+    let (status, body) = send(&app, uri, chunked(64 * KIB)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["title"], "Validation Error");
+
+    let (status, _) = send(&app, uri, chunked(8 * KIB)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+fn write_minimal_config(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let config_path = dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        "groups:\n  - name: default\n    api_keys: [\"key\"]\n",
+    )
+    .unwrap();
+    config_path
+}
+
+#[test]
+#[serial]
+fn test_max_request_size_env_override() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = write_minimal_config(&dir);
+
+    std::env::set_var("GEMINI_PROXY_MAX_REQUEST_SIZE", "2048");
+    let config = gemini_proxy::config::load_config(&config_path).unwrap();
+    assert_eq!(config.server.max_request_size, 2048);
+
+    // A value that does not parse is ignored with a warning.
+    std::env::set_var("GEMINI_PROXY_MAX_REQUEST_SIZE", "10MB");
+    let config = gemini_proxy::config::load_config(&config_path).unwrap();
+    assert_eq!(
+        config.server.max_request_size,
+        gemini_proxy::config::ServerConfig::default().max_request_size
+    );
+
+    std::env::remove_var("GEMINI_PROXY_MAX_REQUEST_SIZE");
+}
@@ -0,0 +1,175 @@
+// tests/resource_affinity_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{any, body_partial_json, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn setup(server: &MockServer) -> (Router, tempfile::TempDir) {
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    (create_router(Arc::new(state)), temp_dir)
+}
+
+async fn send(app: &Router, http_method: Method, uri: &str, body: Value) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http_method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+async fn mount_wrong_key_fallback(server: &MockServer) {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({"error": "wrong project"})))
+        .with_priority(u8::MAX)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_cached_content_references_use_the_creating_key() {
+    let server = MockServer::start().await;
+    for key in ["key-1", "key-2"] {
+        Mock::given(method("POST"))
+            .and(path("/v1beta/cachedContents"))
+            .and(query_param("key", key))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"name": format!("cachedContents/{key}")})),
+            )
+            .mount(&server)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .and(query_param("key", "key-1"))
+        .and(body_partial_json(
+            json!({"cachedContent": "cachedContents/key-1"}),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1beta/cachedContents/key-1"))
+        .and(query_param("key", "key-1"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"name": "cachedContents/key-1"})),
+        )
+        .mount(&server)
+        .await;
+    mount_wrong_key_fallback(&server).await;
+    let (app, _dir) = setup(&server).await;
+
+    for _ in 0..2 {
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/v1beta/cachedContents",
+            json!({"model": "models/gemini-2.0-flash"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    for _ in 0..3 {
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/v1beta/models/gemini-2.0-flash:generateContent",
+            json!({"cachedContent": "cachedContents/key-1", "contents": []}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(
+            &app,
+            Method::GET,
+            "/v1beta/cachedContents/key-1",
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_long_running_operations_are_polled_with_the_creating_key() {
+    let server = MockServer::start().await;
+    let create = "/v1beta/models/veo-2.0-generate-001:predictLongRunning";
+    for key in ["key-1", "key-2"] {
+        Mock::given(method("POST"))
+            .and(path(create))
+            .and(query_param("key", key))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                json!({"name": format!("models/veo-2.0-generate-001/operations/op-{key}")}),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/v1beta/models/veo-2.0-generate-001/operations/op-{key}"
+            )))
+            .and(query_param("key", key))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"done": true})))
+            .mount(&server)
+            .await;
+    }
+    mount_wrong_key_fallback(&server).await;
+    let (app, _dir) = setup(&server).await;
+
+    let mut operations = Vec::new();
+    for _ in 0..2 {
+        let (status, body) = send(&app, Method::POST, create, json!({"instances": []})).await;
+        assert_eq!(status, StatusCode::OK);
+        operations.push(body["name"].as_str().unwrap().to_string());
+    }
+
+    for _ in 0..2 {
+        for operation in &operations {
+            let (status, body) = send(
+                &app,
+                Method::GET,
+                &format!("/v1beta/{operation}"),
+                Value::Null,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["done"], true);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// tests/response_cache_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, ResponseCacheConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn create_app(server: &MockServer, temp_dir: &TempDir) -> Router {
+    create_app_with_cache(server, temp_dir, ResponseCacheConfig::default()).await
+}
+
+async fn create_app_with_cache(
+    server: &MockServer,
+    temp_dir: &TempDir,
+    cache: ResponseCacheConfig,
+) -> Router {
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["cache-key".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        response_cache: Some(cache),
+        ..Default::default()
+    };
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    create_router(Arc::new(state))
+}
+
+async fn send(app: &Router, uri: &str, body: Value, cache_control: Option<&str>) -> Response {
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(value) = cache_control {
+        builder = builder.header("cache-control", value);
+    }
+    app.clone()
+        .oneshot(builder.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap()
+}
+
+fn cache_status(response: &Response) -> Option<&str> {
+    response
+        .headers()
+        .get("x-proxy-cache")
+        .map(|v| v.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn test_embedding_is_served_from_cache() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/text-embedding-004:embedContent"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"embedding": {"values": [0.5]}})),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+    let uri = "/v1beta/models/text-embedding-004:embedContent";
+    let body = json!({"content": {"parts": [{"text": "hello"}]}});
+
+    let first = send(&app, uri, body.clone(), None).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(cache_status(&first), Some("MISS"));
+    assert!(first.headers().contains_key("x-proxy-attempts"));
+
+    let second = send(&app, uri, body.clone(), None).await;
+    assert_eq!(second.status(), StatusCode::OK);
+    assert_eq!(cache_status(&second), Some("HIT"));
+    assert!(
+        !second.headers().contains_key("x-proxy-attempts"),
+        "a cache hit makes no upstream attempt"
+    );
+    let cached = to_bytes(second.into_body(), usize::MAX).await.unwrap();
+    let cached: Value = serde_json::from_slice(&cached).unwrap();
+    assert_eq!(cached["embedding"]["values"], json!([0.5]));
+
+    // no-cache forces a fresh upstream call (the second expected hit).
+    let bypass = send(&app, uri, body, Some("no-cache")).await;
+    assert_eq!(cache_status(&bypass), Some("MISS"));
+}
+
+#[tokio::test]
+async fn test_non_deterministic_and_failed_requests_are_not_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:countTokens"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({"error": {}})))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let app = create_app(&server, &temp_dir).await;
+    let generate = "/v1beta/models/gemini-2.0-flash:generateContent";
+    let sampled = json!({"contents": [], "generationConfig": {"temperature": 0.9}});
+
+    for _ in 0..2 {
+        let response = send(&app, generate, sampled.clone(), None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(cache_status(&response), None);
+    }
+
+    let count = "/v1beta/models/gemini-2.0-flash:countTokens";
+    let deterministic = json!({"contents": [], "generationConfig": {"temperature": 0}});
+    for _ in 0..2 {
+        let response = send(&app, count, deterministic.clone(), None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(cache_status(&response), Some("MISS"));
+    }
+}
+
+#[tokio::test]
+async fn test_calls_that_create_resources_are_not_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/cachedContents"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"name": "cachedContents/abc"})),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cache = ResponseCacheConfig {
+        deterministic_only: false,
+        ..Default::default()
+    };
+    let app = create_app_with_cache(&server, &temp_dir, cache).await;
+    let body = json!({"model": "models/gemini-2.0-flash", "contents": []});
+
+    for _ in 0..2 {
+        let response = send(&app, "/v1beta/cachedContents", body.clone(), None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(cache_status(&response), None);
+    }
+}
@@ -0,0 +1,295 @@
+// tests/resumable_upload_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    middleware::from_fn_with_state,
+    response::Response,
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, GroupSelectionConfig, KeyGroup},
+    create_router,
+    middleware::request_size_limit_middleware,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::{collections::HashMap, sync::Arc};
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{any, header, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn send(app: &Router, request: Request<Body>) -> Response {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_resumable_upload_uses_one_key_per_session() {
+    let server = MockServer::start().await;
+    for key in ["key-1", "key-2"] {
+        Mock::given(method("POST"))
+            .and(path("/upload/v1beta/files"))
+            .and(header("x-goog-upload-protocol", "resumable"))
+            .and(header("x-goog-upload-command", "start"))
+            .and(query_param("key", key))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header(
+                    "x-goog-upload-url",
+                    format!(
+                        "{}/upload/v1beta/files?upload_id=id-{key}&upload_protocol=resumable",
+                        server.uri()
+                    )
+                    .as_str(),
+                ),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/v1beta/files"))
+            .and(query_param("upload_id", format!("id-{key}").as_str()))
+            .and(query_param("key", key))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-goog-upload-status", "final")
+                    .set_body_json(json!({"file": {"name": format!("files/f-{key}")}})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/v1beta/files/f-{key}")))
+            .and(query_param("key", key))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"name": format!("files/f-{key}"), "state": "ACTIVE"})),
+            )
+            .mount(&server)
+            .await;
+    }
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({"error": "wrong project"})))
+        .with_priority(u8::MAX)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let state = Arc::new(state);
+    let app = create_router(state.clone())
+        .layer(from_fn_with_state(state, request_size_limit_middleware));
+
+    let mut upload_urls = Vec::new();
+    for _ in 0..2 {
+        let response = send(
+            &app,
+            Request::builder()
+                .method(Method::POST)
+                .uri("/upload/v1beta/files")
+                .header("host", "proxy.local:8080")
+                .header("x-goog-upload-protocol", "resumable")
+                .header("x-goog-upload-command", "start")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"file":{"display_name":"big"}}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let upload_url = response.headers()["x-goog-upload-url"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(upload_url.starts_with("http://proxy.local:8080/upload/v1beta/files?upload_id="));
+        assert!(!upload_url.contains("key="));
+        upload_urls.push(upload_url);
+    }
+
+    // Larger than the 10 MB limit that applies to ordinary requests.
+    let payload = vec![b'x'; 11 * 1024 * 1024];
+    let mut files = Vec::new();
+    for upload_url in upload_urls.iter().rev() {
+        let uri = upload_url.trim_start_matches("http://proxy.local:8080");
+        let response = send(
+            &app,
+            Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("content-length", payload.len())
+                .header("x-goog-upload-command", "upload, finalize")
+                .header("x-goog-upload-offset", "0")
+                .body(Body::from(payload.clone()))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap())
+                .unwrap();
+        files.push(body["file"]["name"].as_str().unwrap().to_string());
+    }
+
+    // The uploaded files stay with the key that uploaded them.
+    for file in &files {
+        let response = send(
+            &app,
+            Request::builder()
+                .uri(format!("/v1beta/{file}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // The session is forgotten once the upload has been finalized.
+    let response = send(
+        &app,
+        Request::builder()
+            .method(Method::POST)
+            .uri(upload_urls[0].trim_start_matches("http://proxy.local:8080"))
+            .header("x-goog-upload-command", "query")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_upload_start_uses_the_client_selected_group() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/upload/v1beta/files"))
+        .and(query_param("key", "key-eval"))
+        .respond_with(ResponseTemplate::new(200).insert_header(
+            "x-goog-upload-url",
+            format!("{}/upload/v1beta/files?upload_id=id-eval", server.uri()).as_str(),
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({"error": "wrong project"})))
+        .with_priority(u8::MAX)
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![
+            KeyGroup {
+                name: "default".to_string(),
+                api_keys: vec!["key-default".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+            KeyGroup {
+                name: "eval".to_string(),
+                api_keys: vec!["key-eval".to_string()],
+                target_url: server.uri(),
+                ..Default::default()
+            },
+        ],
+        group_selection: Some(GroupSelectionConfig {
+            clients: HashMap::from([("harness".to_string(), vec!["eval".to_string()])]),
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let response = send(
+        &app,
+        Request::builder()
+            .method(Method::POST)
+            .uri("/upload/v1beta/files")
+            .header("host", "proxy.local:8080")
+            .header("x-client-id", "harness")
+            .header("x-proxy-group", "eval")
+            .header("x-goog-upload-protocol", "resumable")
+            .header("x-goog-upload-command", "start")
+            .body(Body::from(r#"{"file":{"display_name":"eval"}}"#))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_rate_limited_upload_start_marks_the_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/upload/v1beta/files"))
+        .and(query_param("key", "key-1"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "60")
+                .set_body_json(json!({"error": {"code": 429}})),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/upload/v1beta/files"))
+        .and(query_param("key", "key-2"))
+        .respond_with(ResponseTemplate::new(200).insert_header(
+            "x-goog-upload-url",
+            format!("{}/upload/v1beta/files?upload_id=id-2", server.uri()).as_str(),
+        ))
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    // Whichever key starts first, key-1 answers at most once: its 429 holds
+    // it back for the following starts.
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let response = send(
+            &app,
+            Request::builder()
+                .method(Method::POST)
+                .uri("/upload/v1beta/files")
+                .header("host", "proxy.local:8080")
+                .header("x-goog-upload-protocol", "resumable")
+                .header("x-goog-upload-command", "start")
+                .body(Body::from(r#"{"file":{}}"#))
+                .unwrap(),
+        )
+        .await;
+        statuses.push(response.status());
+    }
+    assert_eq!(
+        statuses
+            .iter()
+            .filter(|s| **s == StatusCode::TOO_MANY_REQUESTS)
+            .count(),
+        1
+    );
+}
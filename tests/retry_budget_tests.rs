@@ -0,0 +1,234 @@
+// tests/retry_budget_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup, KeyWaitQueueConfig, RetryBackoffConfig},
+    create_router,
+    state::AppState,
+};
+use serde_json::json;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tower::ServiceExt;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const GENERATE_PATH: &str = "/v1beta/models/gemini-2.5-flash:generateContent";
+
+async fn app(server: &MockServer, max_attempts: Option<u32>) -> Router {
+    let mut config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: (1..=4).map(|i| format!("key-{i}")).collect(),
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    config.server.max_attempts = max_attempts;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    create_router(Arc::new(state))
+}
+
+/// Sends a request and returns its status and `X-Proxy-Attempts` header.
+async fn generate(app: &Router, headers: &[(&str, &str)]) -> (StatusCode, Option<String>) {
+    let mut request = Request::post(GENERATE_PATH).header("content-type", "application/json");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let body = json!({"contents": [{"parts": [{"text": "hi"}]}]}).to_string();
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let attempts = response
+        .headers()
+        .get("x-proxy-attempts")
+        .map(|v| v.to_str().unwrap().to_string());
+    to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, attempts)
+}
+
+async fn upstream_calls(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap().len()
+}
+
+#[tokio::test]
+async fn test_attempts_are_limited_by_config_and_client() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let app = app(&server, Some(2)).await;
+
+    let (status, attempts) = generate(&app, &[]).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(attempts.as_deref(), Some("2"));
+    assert_eq!(upstream_calls(&server).await, 2);
+
+    let (_, attempts) = generate(&app, &[("x-proxy-max-attempts", "1")]).await;
+    assert_eq!(attempts.as_deref(), Some("1"));
+    assert_eq!(upstream_calls(&server).await, 3);
+}
+
+#[tokio::test]
+async fn test_successful_responses_report_attempts() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .mount(&server)
+        .await;
+    let app = app(&server, None).await;
+
+    let (status, attempts) = generate(&app, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(attempts.as_deref(), Some("2"));
+}
+
+#[tokio::test]
+async fn test_deadline_caps_retry_after_waits_and_slow_attempts() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "60"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+        .mount(&server)
+        .await;
+    let app = app(&server, None).await;
+
+    let started = Instant::now();
+    let (status, attempts) = generate(&app, &[("x-proxy-deadline-ms", "300")]).await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(attempts.as_deref(), Some("1"));
+
+    let started = Instant::now();
+    let (status, attempts) = generate(&app, &[("x-proxy-deadline-ms", "300")]).await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(attempts.as_deref(), Some("1"));
+}
+
+#[tokio::test]
+async fn test_transient_errors_retry_the_same_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .mount(&server)
+        .await;
+    let app = app(&server, None).await;
+
+    let (status, attempts) = generate(&app, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(attempts.as_deref(), Some("3"));
+
+    let requests = server.received_requests().await.unwrap();
+    let keys: Vec<_> = requests
+        .iter()
+        .map(|r| r.url.query().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(keys.len(), 3);
+    assert!(keys[0].contains("key=key-"));
+    assert!(keys.iter().all(|k| *k == keys[0]));
+}
+
+#[tokio::test]
+async fn test_every_key_is_tried_once_its_transient_retries_run_out() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: (1..=4).map(|i| format!("key-{i}")).collect(),
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        transient_retries: Some(1),
+        retry_backoff: Some(RetryBackoffConfig {
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let (status, attempts) = generate(&app, &[]).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(attempts.as_deref(), Some("8"));
+
+    let requests = server.received_requests().await.unwrap();
+    for key in 1..=4 {
+        let tries = requests
+            .iter()
+            .filter(|r| {
+                r.url
+                    .query()
+                    .unwrap_or_default()
+                    .contains(&format!("key=key-{key}"))
+            })
+            .count();
+        assert_eq!(tries, 2, "key-{key} is tried once and retried once");
+    }
+}
+
+#[tokio::test]
+async fn test_exhausted_keys_error_reports_attempts() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "60"))
+        .mount(&server)
+        .await;
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: (1..=4).map(|i| format!("key-{i}")).collect(),
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        key_wait_queue: Some(KeyWaitQueueConfig {
+            max_wait_secs: 1,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state));
+
+    let (status, attempts) = generate(&app, &[]).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(attempts.as_deref(), Some("4"));
+}
@@ -22,8 +22,8 @@ fn test_server_error_handler_handles_gemini_500_error() {
 
     let action = handler.handle(&response, &body_bytes, "test_key");
 
-    // Should return RetryNextKey to switch to the next API key
-    assert!(matches!(action, Some(Action::RetryNextKey)));
+    // Should be retried as a transient error, not blamed on the key
+    assert!(matches!(action, Some(Action::RetryTransient)));
 }
 
 #[test]
@@ -51,8 +51,8 @@ fn test_server_error_handler_handles_all_server_errors() {
         let action = handler.handle(&response, &body_bytes, "test_key");
 
         assert!(
-            matches!(action, Some(Action::RetryNextKey)),
-            "Status code {} should trigger RetryNextKey",
+            matches!(action, Some(Action::RetryTransient)),
+            "Status code {} should trigger RetryTransient",
             status_code.as_u16()
         );
     }
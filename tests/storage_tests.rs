@@ -2,10 +2,16 @@
 
 use gemini_proxy::{
     key_manager::FlattenedKeyInfo,
-    storage::{memory::InMemoryStore, traits::KeyStore},
+    storage::{
+        key_state::MAX_TEMPORARY_BLOCK,
+        memory::InMemoryStore,
+        resource_owner::{owner_ttl, DEFAULT_OWNER_TTL, FILE_OWNER_TTL},
+        traits::KeyStore,
+    },
 };
 use secrecy::Secret;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[tokio::test]
 async fn test_memory_store_basic_operations() {
@@ -89,3 +95,94 @@ async fn test_memory_store_multiple_keys() {
         assert!(candidate_keys.contains(&key.to_string()));
     }
 }
+
+#[tokio::test]
+async fn test_memory_store_caps_huge_rate_limit_durations() {
+    let mut key_info_map = HashMap::new();
+    key_info_map.insert(
+        "test-key".to_string(),
+        FlattenedKeyInfo {
+            key: Secret::new("test-key".to_string()),
+            group_name: "test-group".to_string(),
+            target_url: "https://example.com".to_string(),
+            proxy_url: None,
+        },
+    );
+    let store = InMemoryStore::new(&key_info_map);
+
+    store
+        .set_key_rate_limited("test-key", Duration::from_secs(u64::MAX))
+        .await
+        .unwrap();
+
+    let state = store.get_key_state("test-key").await.unwrap().unwrap();
+    let until = state
+        .temporarily_blocked_until()
+        .expect("key should be blocked");
+    let max_until = chrono::Utc::now() + chrono::Duration::from_std(MAX_TEMPORARY_BLOCK).unwrap();
+    assert!(until <= max_until);
+    assert!(!state.is_available());
+}
+
+#[tokio::test]
+async fn test_memory_store_clears_expired_blocks() {
+    let mut key_info_map = HashMap::new();
+    key_info_map.insert(
+        "test-key".to_string(),
+        FlattenedKeyInfo {
+            key: Secret::new("test-key".to_string()),
+            group_name: "test-group".to_string(),
+            target_url: "https://example.com".to_string(),
+            proxy_url: None,
+        },
+    );
+    let store = InMemoryStore::new(&key_info_map);
+
+    store
+        .set_key_rate_limited("test-key", Duration::from_millis(20))
+        .await
+        .unwrap();
+    assert!(
+        store
+            .get_key_state("test-key")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_blocked
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let state = store.get_key_state("test-key").await.unwrap().unwrap();
+    assert!(state.is_available());
+    assert!(!state.is_blocked, "an expired block is reported as lifted");
+    assert_eq!(state.blocked_until, None);
+    assert!(!store.get_all_key_states().await.unwrap()["test-key"].is_blocked);
+}
+
+#[tokio::test]
+async fn test_memory_store_resource_owners() {
+    let store = InMemoryStore::new(&HashMap::new());
+
+    store
+        .set_resource_owner("files/abc", "key-1")
+        .await
+        .unwrap();
+    store
+        .set_resource_owner("batches/123", "key-2")
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_resource_owner("files/abc").await.unwrap(),
+        Some("key-1".to_string())
+    );
+    assert_eq!(store.get_resource_owners().await.unwrap().len(), 2);
+
+    store.remove_resource_owner("files/abc").await.unwrap();
+    assert_eq!(store.get_resource_owner("files/abc").await.unwrap(), None);
+    assert_eq!(store.get_resource_owners().await.unwrap().len(), 1);
+
+    // Files expire upstream after 48 hours; other resources are kept longer.
+    assert_eq!(owner_ttl("files/abc"), FILE_OWNER_TTL);
+    assert_eq!(owner_ttl("uploads/xyz"), FILE_OWNER_TTL);
+    assert_eq!(owner_ttl("batches/123"), DEFAULT_OWNER_TTL);
+}
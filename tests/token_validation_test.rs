@@ -104,3 +104,33 @@ fn test_token_validation_without_tokenizer() {
         "Should not fail when tokenizer is not initialized"
     );
 }
+
+#[test]
+fn test_token_validation_counts_inline_media() {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    // A PNG header is enough for the estimator: 1536x1536 pixels is 4 tiles.
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend_from_slice(&1536u32.to_be_bytes());
+    png.extend_from_slice(&1536u32.to_be_bytes());
+    let request_body = json!({
+        "contents": [{
+            "parts": [{"inline_data": {"mime_type": "image/png", "data": STANDARD.encode(&png)}}]
+        }]
+    });
+
+    let result = gemini_proxy::handlers::validate_token_count_with_limit(&request_body, Some(1000));
+    match result {
+        Err(AppError::RequestTooLarge { size, max_size }) => {
+            assert_eq!(size, 4 * 258);
+            assert_eq!(max_size, 1000);
+        }
+        _ => panic!(
+            "Expected RequestTooLarge error for image, got: {:?}",
+            result
+        ),
+    }
+    assert!(
+        gemini_proxy::handlers::validate_token_count_with_limit(&request_body, Some(2000)).is_ok()
+    );
+}
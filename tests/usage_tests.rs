@@ -0,0 +1,124 @@
+// tests/usage_tests.rs
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::connect_info::MockConnectInfo,
+    http::{Request, StatusCode},
+    Router,
+};
+use gemini_proxy::{
+    config::{AppConfig, KeyGroup},
+    create_router,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn call(app: &Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_usage_is_recorded_from_buffered_and_streamed_responses() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-2.0-flash:generateContent"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [],
+            "usageMetadata": {
+                "promptTokenCount": 100,
+                "candidatesTokenCount": 20,
+                "cachedContentTokenCount": 40,
+                "thoughtsTokenCount": 5,
+                "totalTokenCount": 125
+            }
+        })))
+        .mount(&server)
+        .await;
+    let events = concat!(
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+        "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":8,\"completion_tokens\":2,\"total_tokens\":10}}\n\n",
+        "data: [DONE]\n\n"
+    );
+    Mock::given(method("POST"))
+        .and(path("/v1beta/openai/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"))
+        .mount(&server)
+        .await;
+
+    let config = AppConfig {
+        groups: vec![KeyGroup {
+            name: "default".to_string(),
+            api_keys: vec!["key-1".to_string()],
+            target_url: server.uri(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (state, _rx) = AppState::new(&config, &temp_dir.path().join("config.yaml"))
+        .await
+        .expect("AppState failed");
+    let app = create_router(Arc::new(state))
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 3000))));
+
+    let (status, _) = call(
+        &app,
+        Request::post("/v1beta/models/gemini-2.0-flash:generateContent")
+            .header("content-type", "application/json")
+            .header("x-client-id", "team-a")
+            .body(Body::from(r#"{"contents":[]}"#))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = call(
+        &app,
+        Request::post("/v1/chat/completions")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"model":"gemini-2.0-flash","stream":true,"messages":[]}"#,
+            ))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, events.as_bytes());
+
+    let (status, body) = call(
+        &app,
+        Request::get("/admin/usage?group_by=client&from=2020-01-01T00:00:00Z")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["totals"]["requests"], 2);
+    assert_eq!(report["totals"]["prompt_tokens"], 108);
+    assert_eq!(report["totals"]["cached_tokens"], 40);
+    let rows = report["rows"].as_array().unwrap();
+    let team_a = rows.iter().find(|row| row["client"] == "team-a").unwrap();
+    assert_eq!(team_a["thoughts_tokens"], 5);
+    assert!(team_a.get("model").is_none());
+
+    let (status, body) = call(
+        &app,
+        Request::get("/admin/usage?to=2020-01-01T00:00:00Z")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["totals"]["requests"], 0);
+}